@@ -0,0 +1,189 @@
+use std::sync::Arc;
+
+use brioche_core::{
+    Brioche,
+    cache::{ArchiveCompression, CacheClient, CacheLayer, CompressionDictionary},
+    recipe::Artifact,
+};
+use futures::TryStreamExt as _;
+
+const NUM_FILES: usize = 16;
+const FILE_SIZE: usize = 1_048_576;
+
+const COMPRESSIONS: &[&str] = &[
+    "default",
+    "level-19",
+    "long-distance-matching",
+    "dictionary",
+    "small-chunks",
+];
+
+fn main() {
+    print_compressed_sizes();
+    divan::main();
+}
+
+#[divan::bench(args = COMPRESSIONS)]
+fn bench_cache_save_artifact(bencher: divan::Bencher, compression: &str) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let (brioche, _context) = runtime.block_on(brioche_test_support::brioche_test());
+    let artifact = runtime.block_on(build_artifact(&brioche));
+    let compression = compression_for(compression);
+
+    bencher
+        .counter(divan::counter::BytesCount::new(NUM_FILES * FILE_SIZE))
+        .with_inputs(|| brioche_with_cache(&brioche, compression.clone()).0)
+        .bench_local_values(|brioche| {
+            runtime.block_on(async {
+                brioche_core::cache::save_artifact(&brioche, artifact.clone())
+                    .await
+                    .unwrap();
+            });
+        });
+}
+
+#[divan::bench(args = COMPRESSIONS)]
+fn bench_cache_load_artifact(bencher: divan::Bencher, compression: &str) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let (brioche, _context) = runtime.block_on(brioche_test_support::brioche_test());
+    let artifact = runtime.block_on(build_artifact(&brioche));
+    let (brioche, cache) = brioche_with_cache(&brioche, compression_for(compression));
+    runtime.block_on(async {
+        brioche_core::cache::save_artifact(&brioche, artifact.clone())
+            .await
+            .unwrap();
+    });
+
+    bencher
+        .counter(divan::counter::BytesCount::new(NUM_FILES * FILE_SIZE))
+        .with_inputs(|| {
+            // Load into a fresh data directory each time, so every blob
+            // needs to be read from the cache
+            runtime.block_on(brioche_test_support::brioche_test_with(|builder| {
+                builder.cache_client(CacheClient::from_store(cache.clone(), false))
+            }))
+        })
+        .bench_local_values(|(brioche, _context)| {
+            runtime.block_on(async {
+                brioche_core::cache::load_artifact(
+                    &brioche,
+                    artifact.hash(),
+                    brioche_core::reporter::job::CacheFetchKind::Bake,
+                )
+                .await
+                .unwrap()
+                .unwrap();
+            });
+        });
+}
+
+/// Print the total size of the cache after saving the same artifact with
+/// each compression setting. Divan only measures time, so sizes get
+/// reported separately.
+#[expect(clippy::print_stdout)]
+fn print_compressed_sizes() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let (brioche, _context) = runtime.block_on(brioche_test_support::brioche_test());
+    let artifact = runtime.block_on(build_artifact(&brioche));
+
+    println!("cache size for {} bytes of data:", NUM_FILES * FILE_SIZE);
+    for compression in COMPRESSIONS {
+        let (brioche, cache) = brioche_with_cache(&brioche, compression_for(compression));
+        let size = runtime.block_on(async {
+            brioche_core::cache::save_artifact(&brioche, artifact.clone())
+                .await
+                .unwrap();
+            cache
+                .list(None)
+                .try_fold(0, |size, meta| async move { Ok(size + meta.size) })
+                .await
+                .unwrap()
+        });
+        println!("  {compression}: {size} bytes");
+    }
+    println!();
+}
+
+fn compression_for(name: &str) -> ArchiveCompression {
+    match name {
+        "default" => ArchiveCompression::default(),
+        "level-19" => ArchiveCompression {
+            level: 19,
+            ..Default::default()
+        },
+        "long-distance-matching" => ArchiveCompression {
+            long_distance_matching: true,
+            ..Default::default()
+        },
+        "dictionary" => ArchiveCompression {
+            dictionary: Some(CompressionDictionary::new(file_contents(0, 65_536))),
+            ..Default::default()
+        },
+        "small-chunks" => ArchiveCompression {
+            min_chunk_size: 65_536,
+            avg_chunk_size: 262_144,
+            max_chunk_size: 1_048_576,
+            ..Default::default()
+        },
+        name => panic!("unknown compression: {name}"),
+    }
+}
+
+/// Create a copy of `brioche` that writes to a new empty cache.
+fn brioche_with_cache(
+    brioche: &Brioche,
+    compression: ArchiveCompression,
+) -> (Brioche, Arc<dyn object_store::ObjectStore>) {
+    let cache = brioche_test_support::new_cache();
+    let mut brioche = brioche.clone();
+    brioche.cache_client = CacheClient {
+        layers: vec![CacheLayer {
+            store: cache.clone(),
+            writable: true,
+            remote: false,
+            trusted_keys: vec![],
+            signing_key: None,
+            compression,
+        }],
+        ..Default::default()
+    };
+    (brioche, cache)
+}
+
+async fn build_artifact(brioche: &Brioche) -> Artifact {
+    let mut files = vec![];
+    for n in 0..NUM_FILES {
+        let blob = brioche_test_support::blob(brioche, file_contents(n, FILE_SIZE)).await;
+        files.push((
+            format!("file-{n}.txt"),
+            brioche_test_support::file(blob, false),
+        ));
+    }
+
+    brioche_test_support::dir(brioche, files).await
+}
+
+/// Build some compressible data, where files share some but not all of
+/// their contents like a typical build output.
+fn file_contents(n: usize, size: usize) -> Vec<u8> {
+    let mut contents = String::new();
+    let mut line = 0;
+    while contents.len() < size {
+        let value = (line * 7919 + n * 104_729) % 100_000;
+        contents.push_str(&format!("line {line}: value={value} name=entry-{n}\n"));
+        line += 1;
+    }
+
+    let mut contents = contents.into_bytes();
+    contents.truncate(size);
+    contents
+}
@@ -0,0 +1,7 @@
+-- Track how long processes take to run, so the longest chains of
+-- processes can be started first
+CREATE TABLE process_durations (
+    duration_key TEXT PRIMARY KEY NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+) STRICT;
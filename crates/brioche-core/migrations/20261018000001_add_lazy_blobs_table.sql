@@ -0,0 +1,7 @@
+-- Blobs from artifacts that were loaded from the cache without fetching
+-- their contents, along with where to find each blob's data in the
+-- cache's chunks
+CREATE TABLE lazy_blobs (
+    blob_hash TEXT PRIMARY KEY NOT NULL,
+    parts_json TEXT NOT NULL
+) STRICT;
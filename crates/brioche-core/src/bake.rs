@@ -10,7 +10,7 @@ use tracing::Instrument as _;
 
 use crate::{
     project::ProjectHash,
-    recipe::{ArtifactDiscriminants, ProxyRecipe},
+    recipe::{ArtifactDiscriminants, CompleteProcessRecipe, ProcessRecipe, ProxyRecipe},
 };
 
 use super::{
@@ -18,12 +18,16 @@ use super::{
     recipe::{Artifact, CreateDirectory, Directory, File, Meta, Recipe, RecipeHash, WithMeta},
 };
 
-pub use process::{ProcessRootfsRecipes, process_rootfs_recipes};
+pub use process::{ProcessRootfsRecipes, bake_process_local, process_rootfs_recipes};
+pub use scheduler::{ProcessPermit, ProcessScheduler};
 
 mod attach_resources;
 mod collect_references;
 mod download;
+mod git_checkout;
 mod process;
+pub mod scheduler;
+mod substitute;
 mod unarchive;
 
 #[derive(Debug, Default)]
@@ -55,6 +59,18 @@ pub async fn bake(
     scope: &BakeScope,
 ) -> anyhow::Result<WithMeta<Artifact>> {
     let recipe_hash = recipe.hash();
+
+    // Plan out the order to run processes in before starting a new bake.
+    // Child bakes are already covered by their parent's plan
+    if !matches!(scope, BakeScope::Child { .. }) {
+        scheduler::plan_process_priorities(brioche, &recipe)
+            .instrument(tracing::info_span!("bake_plan"))
+            .await
+            .unwrap_or_else(|error| {
+                tracing::warn!("failed to plan process priorities: {error:#}");
+            });
+    }
+
     let result = bake_inner(brioche, recipe).await?;
 
     async {
@@ -184,27 +200,8 @@ async fn bake_inner(
     }
 
     // Check the database to see if we've cached this recipe before
-    let mut db_conn = brioche.db_conn.lock().await;
-    let mut db_transaction = db_conn.begin().await?;
-    let input_hash = recipe_hash.to_string();
-    let result = sqlx::query!(
-        r#"
-            SELECT output_artifacts.recipe_json AS artifact_json
-            FROM bakes
-            INNER JOIN recipes AS output_artifacts
-                ON bakes.output_hash = output_artifacts.recipe_hash
-            WHERE bakes.input_hash = ?
-            LIMIT 1
-        "#,
-        input_hash,
-    )
-    .fetch_optional(&mut *db_transaction)
-    .await?;
-    db_transaction.commit().await?;
-    drop(db_conn);
-
-    if let Some(row) = result {
-        let artifact: Artifact = serde_json::from_str(&row.artifact_json)?;
+    let result = get_local_bake(brioche, recipe_hash).await?;
+    if let Some(artifact) = result {
         tracing::Span::current().record("bake_method", "database_hit");
         tracing::trace!(%recipe_hash, artifact_hash = %artifact.hash(), "got bake result from database");
 
@@ -223,19 +220,20 @@ async fn bake_inner(
 
     // Try to get the baked recipe from the cache (if it might be
     // expensive to bake)
-    let artifact_hash_from_cache = if recipe.is_expensive_to_bake() {
-        crate::cache::load_bake(brioche, recipe_hash)
-            .await
-            .inspect_err(|error| {
-                tracing::warn!("failed to load bake from cache: {error:#}");
-            })
-            .ok()
-            .flatten()
-    } else {
-        None
-    };
+    let artifact_hash_from_cache =
+        if recipe.is_expensive_to_bake() && brioche.cache_client.is_available(brioche) {
+            crate::cache::load_bake(brioche, recipe_hash)
+                .await
+                .inspect_err(|error| {
+                    tracing::warn!("failed to load bake from cache: {error:#}");
+                })
+                .ok()
+                .flatten()
+        } else {
+            None
+        };
     let artifact_from_cache = match artifact_hash_from_cache {
-        Some(artifact_hash) => crate::cache::load_artifact(
+        Some(artifact_hash) => crate::cache::load_artifact_lazy(
             brioche,
             artifact_hash,
             crate::reporter::job::CacheFetchKind::Bake,
@@ -328,8 +326,9 @@ async fn bake_inner(
 }
 
 async fn run_bake(brioche: &Brioche, recipe: Recipe, meta: &Arc<Meta>) -> anyhow::Result<Artifact> {
+    let recipe_hash = recipe.hash();
     let scope = BakeScope::Child {
-        parent_hash: recipe.hash(),
+        parent_hash: recipe_hash,
     };
 
     match recipe {
@@ -363,12 +362,15 @@ async fn run_bake(brioche: &Brioche, recipe: Recipe, meta: &Arc<Meta>) -> anyhow
             // lazy processes that bake to the same complete process will
             // only run once (since `bake` is memoized).
             let process = process::bake_lazy_process_to_process(brioche, &scope, process).await?;
-            let result = bake(
-                brioche,
-                WithMeta::new(Recipe::CompleteProcess(process), meta.clone()),
-                &scope,
-            )
-            .await?;
+            let process = Recipe::CompleteProcess(process);
+
+            // Priorities get planned for lazy processes, so carry this
+            // process's priority over to the complete process
+            brioche
+                .process_scheduler
+                .inherit_priority(recipe_hash, process.hash());
+
+            let result = bake(brioche, WithMeta::new(process, meta.clone()), &scope).await?;
             Ok(result.value)
         }
         Recipe::CompleteProcess(process) => {
@@ -576,6 +578,14 @@ async fn run_bake(brioche: &Brioche, recipe: Recipe, meta: &Arc<Meta>) -> anyhow
             let result = bake(brioche, *recipe, &scope).await?;
             Ok(result.value)
         }
+        Recipe::Substitute(substitute) => {
+            let substituted = substitute::bake_substitute(brioche, &scope, substitute).await?;
+            Ok(Artifact::File(substituted))
+        }
+        Recipe::GitCheckout(checkout) => {
+            let checkout = git_checkout::bake_git_checkout(brioche, meta, checkout).await?;
+            Ok(Artifact::Directory(checkout))
+        }
     }
 }
 
@@ -637,6 +647,129 @@ pub async fn save_bake_result(
     Ok(did_insert_bake)
 }
 
+/// Find all of the network inputs needed to bake a recipe that aren't
+/// available locally, including processes with networking enabled.
+/// Recipes that have already been baked or that are in a local cache are
+/// skipped along with everything they depend on.
+/// Used to report every missing input at once when running in offline
+/// mode.
+pub async fn find_offline_missing_inputs(
+    brioche: &Brioche,
+    recipe: &Recipe,
+) -> anyhow::Result<Vec<String>> {
+    let mut missing = vec![];
+    let mut visited = HashSet::new();
+    let mut queue = vec![recipe.clone()];
+
+    while let Some(recipe) = queue.pop() {
+        let recipe_hash = recipe.hash();
+        if !visited.insert(recipe_hash) {
+            continue;
+        }
+
+        if has_local_bake(brioche, recipe_hash).await? {
+            continue;
+        }
+
+        // Baking will try the cache for these, which works offline if
+        // the bake is in a local cache
+        if recipe.is_expensive_to_bake()
+            && crate::cache::load_bake_from_local_layers(brioche, recipe_hash)
+                .await?
+                .is_some()
+        {
+            continue;
+        }
+
+        match &recipe {
+            Recipe::Download(download) => {
+                missing.push(format!("download {}", download.url));
+            }
+            Recipe::GitCheckout(checkout) => {
+                missing.push(format!(
+                    "git checkout of {} at {}",
+                    checkout.repository, checkout.commit
+                ));
+            }
+            Recipe::Proxy(proxy) => {
+                let proxied = crate::recipe::get_recipe(brioche, proxy.recipe).await?;
+                queue.push(proxied);
+            }
+            Recipe::Process(ProcessRecipe {
+                networking: true, ..
+            })
+            | Recipe::CompleteProcess(CompleteProcessRecipe {
+                networking: true, ..
+            }) => {
+                missing.push(format!("process {recipe_hash} with networking enabled"));
+                queue.extend(
+                    crate::references::inline_recipes(&recipe)
+                        .into_iter()
+                        .cloned(),
+                );
+            }
+            _ => {
+                queue.extend(
+                    crate::references::inline_recipes(&recipe)
+                        .into_iter()
+                        .cloned(),
+                );
+            }
+        }
+    }
+
+    Ok(missing)
+}
+
+/// Returns the output artifact for a recipe if it's already been baked
+/// and saved in the local database.
+pub async fn get_local_bake(
+    brioche: &Brioche,
+    recipe_hash: RecipeHash,
+) -> anyhow::Result<Option<Artifact>> {
+    let mut db_conn = brioche.db_conn.lock().await;
+    let mut db_transaction = db_conn.begin().await?;
+    let input_hash = recipe_hash.to_string();
+    let result = sqlx::query!(
+        r#"
+            SELECT output_artifacts.recipe_json AS artifact_json
+            FROM bakes
+            INNER JOIN recipes AS output_artifacts
+                ON bakes.output_hash = output_artifacts.recipe_hash
+            WHERE bakes.input_hash = ?
+            LIMIT 1
+        "#,
+        input_hash,
+    )
+    .fetch_optional(&mut *db_transaction)
+    .await?;
+    db_transaction.commit().await?;
+    drop(db_conn);
+
+    let artifact = result
+        .map(|row| serde_json::from_str(&row.artifact_json))
+        .transpose()?;
+    Ok(artifact)
+}
+
+/// Returns true if the recipe has already been baked and saved in the
+/// local database.
+async fn has_local_bake(brioche: &Brioche, recipe_hash: RecipeHash) -> anyhow::Result<bool> {
+    let mut db_conn = brioche.db_conn.lock().await;
+    let input_hash = recipe_hash.to_string();
+    let existing_bake = sqlx::query!(
+        r#"
+            SELECT output_hash FROM bakes WHERE input_hash = ? LIMIT 1
+        "#,
+        input_hash,
+    )
+    .fetch_optional(&mut *db_conn)
+    .await?;
+    drop(db_conn);
+
+    Ok(existing_bake.is_some())
+}
+
 pub async fn create_proxy(brioche: &Brioche, recipe: Recipe) -> anyhow::Result<Recipe> {
     if let Recipe::Proxy { .. } = recipe {
         return Ok(recipe);
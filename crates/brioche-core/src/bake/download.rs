@@ -5,7 +5,13 @@ use crate::{
 
 #[tracing::instrument(skip(brioche, download), fields(url = %download.url))]
 pub async fn bake_download(brioche: &Brioche, download: DownloadRecipe) -> anyhow::Result<File> {
-    let blob_hash = crate::download::download(brioche, &download.url, Some(download.hash)).await?;
+    let blob_hash = crate::download::download_with_mirrors(
+        brioche,
+        &download.url,
+        &download.mirrors,
+        &download.hash,
+    )
+    .await?;
 
     Ok(File {
         content_blob: blob_hash,
@@ -0,0 +1,257 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use anyhow::Context as _;
+use bstr::{BString, ByteSlice as _};
+
+use crate::{
+    Brioche,
+    recipe::{Artifact, Directory, File, GitCheckout, Meta, WithMeta},
+};
+
+#[tracing::instrument(skip(brioche, meta, checkout), fields(repository = %checkout.repository, commit = %checkout.commit))]
+pub async fn bake_git_checkout(
+    brioche: &Brioche,
+    meta: &Arc<Meta>,
+    checkout: GitCheckout,
+) -> anyhow::Result<Directory> {
+    let commit = parse_commit(&checkout.commit)?;
+
+    let entries =
+        checkout_commit(brioche, &checkout.repository, commit, checkout.submodules).await?;
+    let entries = entries
+        .into_iter()
+        .map(|(path, artifact)| (path, WithMeta::new(artifact, meta.clone())))
+        .collect();
+
+    let directory = Directory::create(brioche, &entries).await?;
+    Ok(directory)
+}
+
+fn parse_commit(commit: &str) -> anyhow::Result<gix::ObjectId> {
+    anyhow::ensure!(
+        commit.len() == 40 && commit.bytes().all(|b| b.is_ascii_hexdigit()),
+        "invalid git commit {commit:?}: expected a full commit hash"
+    );
+    let commit = gix::ObjectId::from_hex(commit.as_bytes())
+        .with_context(|| format!("invalid git commit {commit:?}"))?;
+    Ok(commit)
+}
+
+/// Fetch a commit and return all of the entries in its tree, keyed by
+/// path. Submodules are checked out recursively if `submodules` is set,
+/// otherwise they are left as empty directories.
+async fn checkout_commit(
+    brioche: &Brioche,
+    repository: &url::Url,
+    commit: gix::ObjectId,
+    submodules: bool,
+) -> anyhow::Result<BTreeMap<BString, Artifact>> {
+    let temp_dir = brioche.data_dir.join("git-temp");
+    tokio::fs::create_dir_all(&temp_dir).await?;
+    let repo_path = temp_dir.join(ulid::Ulid::new().to_string());
+
+    let result = async {
+        crate::download::fetch_git_commit(brioche, repository, commit, &repo_path)
+            .await
+            .with_context(|| format!("failed to fetch commit {commit} from {repository}"))?;
+
+        let mut permit = crate::blob::get_save_blob_permit().await?;
+        let checkout = tokio::task::spawn_blocking({
+            let brioche = brioche.clone();
+            let repo_path = repo_path.clone();
+            move || read_commit_tree(&brioche, &mut permit, &repo_path, commit, submodules)
+        })
+        .await??;
+
+        anyhow::Ok(checkout)
+    }
+    .await;
+
+    // Remove the fetched repository, even if reading it failed
+    if tokio::fs::try_exists(&repo_path).await? {
+        tokio::fs::remove_dir_all(&repo_path).await?;
+    }
+
+    let CommitTree {
+        mut entries,
+        submodules: commit_submodules,
+    } = result?;
+
+    for submodule in commit_submodules {
+        let submodule_url = resolve_submodule_url(repository, &submodule.url)
+            .with_context(|| format!("invalid URL for submodule at {}", submodule.path))?;
+        let submodule_entries = Box::pin(checkout_commit(
+            brioche,
+            &submodule_url,
+            submodule.commit,
+            true,
+        ))
+        .await
+        .with_context(|| format!("failed to check out submodule at {}", submodule.path))?;
+
+        for (path, artifact) in submodule_entries {
+            let mut submodule_path = submodule.path.clone();
+            submodule_path.push(b'/');
+            submodule_path.extend_from_slice(&path);
+            entries.insert(submodule_path, artifact);
+        }
+    }
+
+    Ok(entries)
+}
+
+struct CommitTree {
+    entries: BTreeMap<BString, Artifact>,
+    submodules: Vec<Submodule>,
+}
+
+struct Submodule {
+    path: BString,
+    url: String,
+    commit: gix::ObjectId,
+}
+
+fn read_commit_tree(
+    brioche: &Brioche,
+    permit: &mut crate::blob::SaveBlobPermit<'_>,
+    repo_path: &std::path::Path,
+    commit: gix::ObjectId,
+    submodules: bool,
+) -> anyhow::Result<CommitTree> {
+    let repo = gix::open(repo_path)?;
+    let commit = repo.find_commit(commit)?;
+    let tree_id = commit.tree_id()?.detach();
+
+    let mut entries = BTreeMap::new();
+    let mut gitlinks = vec![];
+    let mut buffer = Vec::new();
+
+    let mut trees = vec![(BString::default(), tree_id)];
+    while let Some((tree_path, tree_id)) = trees.pop() {
+        let tree = repo.find_tree(tree_id)?;
+        let tree = tree.decode()?;
+
+        for entry in tree.entries {
+            let path = if tree_path.is_empty() {
+                BString::from(entry.filename)
+            } else {
+                let mut path = tree_path.clone();
+                path.push(b'/');
+                path.extend_from_slice(entry.filename);
+                path
+            };
+
+            let artifact = match entry.mode.kind() {
+                gix::objs::tree::EntryKind::Tree => {
+                    trees.push((path.clone(), entry.oid.to_owned()));
+                    Artifact::Directory(Directory::default())
+                }
+                gix::objs::tree::EntryKind::Blob | gix::objs::tree::EntryKind::BlobExecutable => {
+                    let blob = repo.find_blob(entry.oid)?;
+                    let content_blob = crate::blob::save_blob_from_reader_sync(
+                        brioche,
+                        permit,
+                        &blob.data[..],
+                        crate::blob::SaveBlobOptions::new(),
+                        &mut buffer,
+                    )?;
+
+                    Artifact::File(File {
+                        content_blob,
+                        executable: entry.mode.kind() == gix::objs::tree::EntryKind::BlobExecutable,
+                        resources: Directory::default(),
+                    })
+                }
+                gix::objs::tree::EntryKind::Link => {
+                    let blob = repo.find_blob(entry.oid)?;
+                    Artifact::Symlink {
+                        target: blob.data.clone().into(),
+                    }
+                }
+                gix::objs::tree::EntryKind::Commit => {
+                    // Submodules are represented by a link to a commit in
+                    // another repository, so leave an empty directory
+                    // in its place
+                    gitlinks.push((path.clone(), entry.oid.to_owned()));
+                    Artifact::Directory(Directory::default())
+                }
+            };
+
+            entries.insert(path, artifact);
+        }
+    }
+
+    let submodules = if gitlinks.is_empty() || !submodules {
+        vec![]
+    } else {
+        let submodule_urls = match entries.get(&BString::from(".gitmodules")) {
+            Some(Artifact::File(File { content_blob, .. })) => {
+                let gitmodules_path = crate::blob::local_blob_path(brioche, *content_blob);
+                let gitmodules = std::fs::read(&gitmodules_path)?;
+                parse_gitmodules(&gitmodules)?
+            }
+            _ => HashMap::new(),
+        };
+
+        gitlinks
+            .into_iter()
+            .map(|(path, commit)| {
+                let url = submodule_urls
+                    .get(&path)
+                    .with_context(|| {
+                        format!("no URL found in .gitmodules for submodule at {path}")
+                    })?
+                    .clone();
+                anyhow::Ok(Submodule { path, url, commit })
+            })
+            .collect::<anyhow::Result<_>>()?
+    };
+
+    Ok(CommitTree {
+        entries,
+        submodules,
+    })
+}
+
+/// Parse a `.gitmodules` file, returning each submodule's URL by path.
+fn parse_gitmodules(gitmodules: &[u8]) -> anyhow::Result<HashMap<BString, String>> {
+    let gitmodules = gitmodules
+        .to_str()
+        .context(".gitmodules is not valid UTF-8")?;
+    let config: gix::config::File<'static> =
+        gitmodules.parse().context("failed to parse .gitmodules")?;
+
+    let mut urls = HashMap::new();
+    for section in config.sections_by_name("submodule").into_iter().flatten() {
+        let (Some(path), Some(url)) = (section.value("path"), section.value("url")) else {
+            continue;
+        };
+        let path: BString = crate::fs_utils::logical_path_bytes(&path)
+            .with_context(|| format!("invalid submodule path {path:?}"))?
+            .into();
+        let url = url
+            .to_str()
+            .with_context(|| format!("invalid URL for submodule at {path}"))?
+            .to_string();
+
+        urls.insert(path, url);
+    }
+
+    Ok(urls)
+}
+
+/// Resolve a submodule URL. Relative URLs are resolved against the
+/// superproject's URL, treating it as a directory (matching Git).
+fn resolve_submodule_url(repository: &url::Url, url: &str) -> anyhow::Result<url::Url> {
+    if url.starts_with("./") || url.starts_with("../") {
+        let mut base = repository.clone();
+        let base_path = format!("{}/", base.path().trim_end_matches('/'));
+        base.set_path(&base_path);
+        Ok(base.join(url)?)
+    } else {
+        Ok(url.parse()?)
+    }
+}
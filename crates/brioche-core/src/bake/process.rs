@@ -1,5 +1,5 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -101,6 +101,7 @@ pub async fn bake_lazy_process_to_process(
         platform: process.platform,
         is_unsafe: process.is_unsafe,
         networking: process.networking,
+        jobs: process.jobs,
     })
 }
 
@@ -286,6 +287,40 @@ pub async fn bake_process(
     brioche: &Brioche,
     meta: &Arc<Meta>,
     process: CompleteProcessRecipe,
+) -> anyhow::Result<Artifact> {
+    if process.networking {
+        crate::network::ensure_online(brioche, || {
+            format!(
+                "process {} with networking enabled",
+                Recipe::CompleteProcess(process.clone()).hash()
+            )
+        })?;
+    }
+
+    if let Some(remote_execution) = &brioche.remote_execution {
+        return crate::remote_execution::bake_process_remote(
+            brioche,
+            remote_execution,
+            meta,
+            process,
+        )
+        .await;
+    }
+
+    bake_process_local(brioche, meta, process, None, None).await
+}
+
+/// Run a process in a sandbox on this machine. If `events_output` is set,
+/// the process event log gets copied there once the process exits
+/// (whether it succeeded or not), and it's up to the caller to point
+/// the user at the log if the process fails. `priority` overrides the
+/// priority from this machine's plan, for processes planned elsewhere.
+pub async fn bake_process_local(
+    brioche: &Brioche,
+    meta: &Arc<Meta>,
+    process: CompleteProcessRecipe,
+    events_output: Option<&Path>,
+    priority: Option<u64>,
 ) -> anyhow::Result<Artifact> {
     let current_platform = crate::platform::current_platform();
     anyhow::ensure!(
@@ -295,9 +330,16 @@ pub async fn bake_process(
     );
     let backend = sandbox_backend(brioche, process.platform).await?;
 
-    tracing::debug!("acquiring process semaphore permit");
-    let _permit = brioche.process_semaphore.acquire().await;
-    tracing::debug!("acquired process semaphore permit");
+    let hash = Recipe::CompleteProcess(process.clone()).hash();
+
+    // Processes on the longest critical path get to run first
+    let duration_key = super::scheduler::complete_process_duration_key(&process);
+    let priority = priority.unwrap_or_else(|| brioche.process_scheduler.priority(hash));
+    let jobs = process.jobs.unwrap_or(1);
+
+    tracing::debug!(jobs, priority, "acquiring process scheduler permit");
+    let _permit = brioche.process_scheduler.acquire(jobs, priority).await;
+    tracing::debug!("acquired process scheduler permit");
 
     let created_at = std::time::Instant::now();
     let mut job_status = ProcessStatus::Preparing { created_at };
@@ -305,8 +347,6 @@ pub async fn bake_process(
         status: job_status.clone(),
     });
 
-    let hash = Recipe::CompleteProcess(process.clone()).hash();
-
     let temp_dir = brioche.data_dir.join("process-temp");
     let bake_dir = temp_dir.join(ulid::Ulid::new().to_string());
     let bake_dir = BakeDir::create(bake_dir).await?;
@@ -601,8 +641,17 @@ pub async fn bake_process(
     drop(event_writer_tx);
     event_writer_task.await??;
 
+    if let Some(events_output) = events_output {
+        tokio::fs::copy(&events_path, events_output)
+            .await
+            .with_context(|| format!("failed to copy events to {}", events_output.display()))?;
+    }
+
     match result {
         Ok(()) => {}
+        Err(error) if events_output.is_some() => {
+            return Err(error);
+        }
         Err(error) => {
             return Err(error).with_context(|| {
                 format!(
@@ -613,6 +662,12 @@ pub async fn bake_process(
         }
     }
 
+    super::scheduler::save_process_duration(brioche, &duration_key, created_at.elapsed())
+        .await
+        .unwrap_or_else(|error| {
+            tracing::warn!("failed to save process duration: {error:#}");
+        });
+
     let result = crate::input::create_input(
         brioche,
         crate::input::InputOptions {
@@ -1835,34 +1890,46 @@ pub fn process_rootfs_recipes(platform: crate::platform::Platform) -> ProcessRoo
             let sh = Recipe::Unarchive(Unarchive {
                 archive: ArchiveFormat::Tar,
                 compression: CompressionFormat::Zstd,
+                strip_components: 0,
+                include: BTreeSet::new(),
                 file: Box::new(WithMeta::without_meta(Recipe::Download(DownloadRecipe {
                     url: "https://development-content.brioche.dev/github.com/tangramdotdev/bootstrap/2023-07-06/dash_amd64_linux.tar.zstd".parse().unwrap(),
-                    hash: crate::Hash::Sha256 { value: hex::decode("ff52ae7e883ee4cbb0878f0e17decc18cd80b364147881fb576440e72e0129b2").unwrap() }
+                    hash: crate::Hash::Sha256 { value: hex::decode("ff52ae7e883ee4cbb0878f0e17decc18cd80b364147881fb576440e72e0129b2").unwrap() },
+                    mirrors: vec![],
                 }))),
             });
             let env = Recipe::Unarchive(Unarchive {
                 archive: ArchiveFormat::Tar,
                 compression: CompressionFormat::Zstd,
+                strip_components: 0,
+                include: BTreeSet::new(),
                 file: Box::new(WithMeta::without_meta(Recipe::Download(DownloadRecipe {
                     url: "https://development-content.brioche.dev/github.com/tangramdotdev/bootstrap/2023-07-06/env_amd64_linux.tar.zstd".parse().unwrap(),
-                    hash: crate::Hash::Sha256 { value: hex::decode("8f5b15a9b5c695663ca2caefa0077c3889fcf65793c9a20ceca4ab12c7007453").unwrap() }
+                    hash: crate::Hash::Sha256 { value: hex::decode("8f5b15a9b5c695663ca2caefa0077c3889fcf65793c9a20ceca4ab12c7007453").unwrap() },
+                    mirrors: vec![],
                 }))),
             });
             let utils = Recipe::Unarchive(Unarchive {
                 archive: ArchiveFormat::Tar,
                 compression: CompressionFormat::Zstd,
+                strip_components: 0,
+                include: BTreeSet::new(),
                 file: Box::new(WithMeta::without_meta(Recipe::Download(DownloadRecipe {
                     url: "https://development-content.brioche.dev/github.com/tangramdotdev/bootstrap/2023-07-06/utils_amd64_linux.tar.zstd".parse().unwrap(),
-                    hash: crate::Hash::Sha256 { value: hex::decode("eb29ea059fcd9ca457841f5c79151721a74761a31610d694bce61a62f4de6d33").unwrap() }
+                    hash: crate::Hash::Sha256 { value: hex::decode("eb29ea059fcd9ca457841f5c79151721a74761a31610d694bce61a62f4de6d33").unwrap() },
+                    mirrors: vec![],
                 }))),
             });
             let proot = Recipe::Peel {
                 directory: Box::new(WithMeta::without_meta(Recipe::Unarchive(Unarchive {
                     archive: ArchiveFormat::Tar,
                     compression: CompressionFormat::Zstd,
+                    strip_components: 0,
+                    include: BTreeSet::new(),
                     file: Box::new(WithMeta::without_meta(Recipe::Download(DownloadRecipe {
                         url: "https://development-content.brioche.dev/github.com/brioche-dev/brioche-packages/812e80250e15793a33c19b770320bcc455f30b13/x86_64-linux/proot.tar.zstd".parse().unwrap(),
-                        hash: crate::Hash::Sha256 { value: hex::decode("f0f4fb4dbe0dd4b050d4336c11d77c1c6b233ee944a4922f3f3a6eaabaedf385").unwrap() }
+                        hash: crate::Hash::Sha256 { value: hex::decode("f0f4fb4dbe0dd4b050d4336c11d77c1c6b233ee944a4922f3f3a6eaabaedf385").unwrap() },
+                        mirrors: vec![],
                     }))),
                 }))),
                 depth: 1,
@@ -1879,25 +1946,34 @@ pub fn process_rootfs_recipes(platform: crate::platform::Platform) -> ProcessRoo
             let sh = Recipe::Unarchive(Unarchive {
                 archive: ArchiveFormat::Tar,
                 compression: CompressionFormat::Zstd,
+                strip_components: 0,
+                include: BTreeSet::new(),
                 file: Box::new(WithMeta::without_meta(Recipe::Download(DownloadRecipe {
                     url: "https://development-content.brioche.dev/github.com/tangramdotdev/bootstrap/2023-07-06/dash_arm64_linux.tar.zstd".parse().unwrap(),
-                    hash: crate::Hash::Sha256 { value: hex::decode("29ac173dee09ff377fd49d3451a382d79273c79780b8841c9860dfc2d4b353d2").unwrap() }
+                    hash: crate::Hash::Sha256 { value: hex::decode("29ac173dee09ff377fd49d3451a382d79273c79780b8841c9860dfc2d4b353d2").unwrap() },
+                    mirrors: vec![],
                 }))),
             });
             let env = Recipe::Unarchive(Unarchive {
                 archive: ArchiveFormat::Tar,
                 compression: CompressionFormat::Zstd,
+                strip_components: 0,
+                include: BTreeSet::new(),
                 file: Box::new(WithMeta::without_meta(Recipe::Download(DownloadRecipe {
                     url: "https://development-content.brioche.dev/github.com/tangramdotdev/bootstrap/2023-07-06/env_arm64_linux.tar.zstd".parse().unwrap(),
-                    hash: crate::Hash::Sha256 { value: hex::decode("0b84d04b2768b6803aee78da8d54393ccbfe8730950b3cc305e8347fff5ad3d7").unwrap() }
+                    hash: crate::Hash::Sha256 { value: hex::decode("0b84d04b2768b6803aee78da8d54393ccbfe8730950b3cc305e8347fff5ad3d7").unwrap() },
+                    mirrors: vec![],
                 }))),
             });
             let utils = Recipe::Unarchive(Unarchive {
                 archive: ArchiveFormat::Tar,
                 compression: CompressionFormat::Zstd,
+                strip_components: 0,
+                include: BTreeSet::new(),
                 file: Box::new(WithMeta::without_meta(Recipe::Download(DownloadRecipe {
                     url: "https://development-content.brioche.dev/github.com/tangramdotdev/bootstrap/2023-07-06/utils_arm64_linux.tar.zstd".parse().unwrap(),
-                    hash: crate::Hash::Sha256 { value: hex::decode("36c0dcfb02e61a07f4654e1ca6047cdefb17ce1f2e37fcb2ba5dc20695b3e273").unwrap() }
+                    hash: crate::Hash::Sha256 { value: hex::decode("36c0dcfb02e61a07f4654e1ca6047cdefb17ce1f2e37fcb2ba5dc20695b3e273").unwrap() },
+                    mirrors: vec![],
                 }))),
             });
 
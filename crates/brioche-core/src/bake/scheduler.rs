@@ -0,0 +1,662 @@
+use std::{
+    collections::{BinaryHeap, HashMap, HashSet},
+    sync::Arc,
+};
+
+use joinery::JoinableIterator as _;
+use sqlx::{Acquire as _, Arguments as _};
+
+use crate::{
+    Brioche,
+    recipe::{
+        CompleteProcessRecipe, CompleteProcessTemplate, CompleteProcessTemplateComponent,
+        ProcessRecipe, ProcessTemplate, ProcessTemplateComponent, Recipe, RecipeHash,
+    },
+};
+
+/// The duration assumed for a process that has never been run before.
+const DEFAULT_PROCESS_DURATION_MS: u64 = 60_000;
+
+/// Limits how many processes run at once, based on the number of jobs
+/// each process uses. When processes are waiting, the one with the
+/// highest priority (the longest estimated critical path) runs first.
+pub struct ProcessScheduler {
+    max_jobs: u32,
+    state: std::sync::Mutex<SchedulerState>,
+    priorities: std::sync::RwLock<HashMap<RecipeHash, u64>>,
+    planned: std::sync::Mutex<HashSet<RecipeHash>>,
+}
+
+struct SchedulerState {
+    available_jobs: u32,
+    waiters: BinaryHeap<Waiter>,
+    next_waiter_id: u64,
+}
+
+impl ProcessScheduler {
+    pub fn new(max_jobs: u32) -> Self {
+        let max_jobs = max_jobs.max(1);
+        Self {
+            max_jobs,
+            state: std::sync::Mutex::new(SchedulerState {
+                available_jobs: max_jobs,
+                waiters: BinaryHeap::new(),
+                next_waiter_id: 0,
+            }),
+            priorities: std::sync::RwLock::default(),
+            planned: std::sync::Mutex::default(),
+        }
+    }
+
+    pub fn max_jobs(&self) -> u32 {
+        self.max_jobs
+    }
+
+    /// Wait until there's room to run a process using `jobs` jobs. Processes
+    /// asking for more than the maximum are capped to the maximum, so
+    /// they run alone.
+    pub async fn acquire(self: &Arc<Self>, jobs: u32, priority: u64) -> ProcessPermit {
+        let jobs = jobs.clamp(1, self.max_jobs);
+
+        let rx = {
+            let mut state = self.state.lock().expect("scheduler lock poisoned");
+            if state.waiters.is_empty() && state.available_jobs >= jobs {
+                state.available_jobs -= jobs;
+                None
+            } else {
+                let (tx, rx) = tokio::sync::oneshot::channel();
+                let id = state.next_waiter_id;
+                state.next_waiter_id += 1;
+                state.waiters.push(Waiter {
+                    priority,
+                    id,
+                    jobs,
+                    tx,
+                });
+                Some(rx)
+            }
+        };
+
+        if let Some(rx) = rx {
+            // The jobs are reserved for us before we get woken up. The
+            // sender is only dropped if the scheduler is, which can't
+            // happen while we hold a reference to it
+            rx.await.expect("process scheduler dropped");
+        }
+
+        ProcessPermit {
+            scheduler: self.clone(),
+            jobs,
+        }
+    }
+
+    /// Get the priority for a process recipe, based on the most recent plan.
+    pub fn priority(&self, recipe_hash: RecipeHash) -> u64 {
+        let priorities = self.priorities.read().expect("priorities lock poisoned");
+        priorities.get(&recipe_hash).copied().unwrap_or(0)
+    }
+
+    /// Give `recipe_hash` the planned priority of `planned_hash`. Used for
+    /// recipes derived from a planned recipe, such as the complete process
+    /// for a lazy process.
+    pub fn inherit_priority(&self, planned_hash: RecipeHash, recipe_hash: RecipeHash) {
+        let mut priorities = self.priorities.write().expect("priorities lock poisoned");
+        if let Some(priority) = priorities.get(&planned_hash).copied() {
+            let current = priorities.entry(recipe_hash).or_default();
+            *current = (*current).max(priority);
+        }
+    }
+
+    fn release(&self, jobs: u32) {
+        let mut state = self.state.lock().expect("scheduler lock poisoned");
+        state.available_jobs += jobs;
+
+        while let Some(waiter) = state.waiters.peek() {
+            // Strictly follow priority order, so large processes don't get
+            // starved by smaller ones
+            if waiter.jobs > state.available_jobs {
+                break;
+            }
+
+            let waiter = state.waiters.pop().expect("waiter disappeared");
+            state.available_jobs -= waiter.jobs;
+            if waiter.tx.send(()).is_err() {
+                // The waiting task was cancelled, so give back its jobs
+                state.available_jobs += waiter.jobs;
+            }
+        }
+    }
+}
+
+pub struct ProcessPermit {
+    scheduler: Arc<ProcessScheduler>,
+    jobs: u32,
+}
+
+impl Drop for ProcessPermit {
+    fn drop(&mut self) {
+        self.scheduler.release(self.jobs);
+    }
+}
+
+struct Waiter {
+    priority: u64,
+    id: u64,
+    jobs: u32,
+    tx: tokio::sync::oneshot::Sender<()>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // Highest priority first, then first-come first-served
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+/// Pick the default maximum number of jobs: one per CPU, but no more than
+/// the available memory allows for at `memory_per_job_mib` each.
+pub fn default_max_jobs(memory_per_job_mib: u64) -> u32 {
+    let cpus = std::thread::available_parallelism().map_or(1, std::num::NonZero::get);
+    let cpus = u32::try_from(cpus).unwrap_or(u32::MAX);
+
+    let memory_jobs = available_memory_mib()
+        .filter(|_| memory_per_job_mib > 0)
+        .map(|memory_mib| memory_mib / memory_per_job_mib)
+        .map(|jobs| u32::try_from(jobs).unwrap_or(u32::MAX));
+
+    match memory_jobs {
+        Some(memory_jobs) => cpus.min(memory_jobs).max(1),
+        None => cpus.max(1),
+    }
+}
+
+fn available_memory_mib() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let available = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))?;
+    let available_kib = available
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(available_kib / 1024)
+}
+
+/// A key used to estimate a process's duration from previous runs of
+/// similar processes. Processes get rebuilt whenever their inputs change,
+/// so the key only includes the platform and the literal parts of the
+/// command and arguments (usually the build script), which tend to stay
+/// the same between versions. Planned priorities are keyed by recipe
+/// hash instead, so processes sharing a key still get their own priority.
+pub fn process_duration_key(process: &ProcessRecipe) -> String {
+    let args = process
+        .args
+        .iter()
+        .map(process_template_literals)
+        .collect::<Vec<_>>();
+    duration_key(
+        process.platform,
+        &process_template_literals(&process.command),
+        &args,
+    )
+}
+
+pub fn complete_process_duration_key(process: &CompleteProcessRecipe) -> String {
+    let args = process
+        .args
+        .iter()
+        .map(complete_process_template_literals)
+        .collect::<Vec<_>>();
+    duration_key(
+        process.platform,
+        &complete_process_template_literals(&process.command),
+        &args,
+    )
+}
+
+fn process_template_literals(template: &ProcessTemplate) -> Vec<Option<&[u8]>> {
+    template
+        .components
+        .iter()
+        .map(|component| match component {
+            ProcessTemplateComponent::Literal { value } => Some(&value[..]),
+            _ => None,
+        })
+        .collect()
+}
+
+fn complete_process_template_literals(template: &CompleteProcessTemplate) -> Vec<Option<&[u8]>> {
+    template
+        .components
+        .iter()
+        .map(|component| match component {
+            CompleteProcessTemplateComponent::Literal { value } => Some(&value[..]),
+            _ => None,
+        })
+        .collect()
+}
+
+fn duration_key(
+    platform: crate::platform::Platform,
+    command: &[Option<&[u8]>],
+    args: &[Vec<Option<&[u8]>>],
+) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(platform.to_string().as_bytes());
+    hasher.update(b"\0command");
+    hash_template_literals(&mut hasher, command);
+    for arg in args {
+        hasher.update(b"\0arg");
+        hash_template_literals(&mut hasher, arg);
+    }
+
+    hasher.finalize().to_hex().to_string()
+}
+
+fn hash_template_literals(hasher: &mut blake3::Hasher, components: &[Option<&[u8]>]) {
+    for component in components {
+        match component {
+            Some(literal) => {
+                hasher.update(b"\0literal");
+                hasher.update(&(literal.len() as u64).to_le_bytes());
+                hasher.update(literal);
+            }
+            None => {
+                hasher.update(b"\0other");
+            }
+        }
+    }
+}
+
+/// Estimate the critical path through each process needed to bake
+/// `recipe`, and use it as the process's priority. A process's critical
+/// path is its own estimated duration plus the longest chain of
+/// processes that depend on it. Recipes that have already been baked
+/// are skipped, and each recipe only gets planned once.
+pub async fn plan_process_priorities(brioche: &Brioche, recipe: &Recipe) -> anyhow::Result<()> {
+    let recipe_hash = recipe.hash();
+    {
+        let mut planned = brioche
+            .process_scheduler
+            .planned
+            .lock()
+            .expect("planned lock poisoned");
+        if !planned.insert(recipe_hash) {
+            return Ok(());
+        }
+    }
+
+    let plan = PlanGraph::build(brioche, recipe).await?;
+
+    let duration_keys = plan
+        .nodes
+        .values()
+        .filter_map(|node| node.duration_key.clone())
+        .collect::<HashSet<_>>();
+    let durations = load_process_durations(brioche, duration_keys).await?;
+
+    let priorities = plan.critical_paths(&durations);
+
+    let mut scheduler_priorities = brioche
+        .process_scheduler
+        .priorities
+        .write()
+        .expect("priorities lock poisoned");
+    for (recipe_hash, priority) in priorities {
+        let current = scheduler_priorities.entry(recipe_hash).or_default();
+        *current = (*current).max(priority);
+    }
+
+    Ok(())
+}
+
+/// The recipes that still need to be baked, in topological order.
+#[derive(Debug, Default)]
+struct PlanGraph {
+    nodes: HashMap<RecipeHash, PlanNode>,
+
+    /// Every recipe comes before the recipes it depends on.
+    order: Vec<RecipeHash>,
+}
+
+#[derive(Debug, Default)]
+struct PlanNode {
+    duration_key: Option<String>,
+    children: Vec<RecipeHash>,
+}
+
+impl PlanGraph {
+    async fn build(brioche: &Brioche, recipe: &Recipe) -> anyhow::Result<Self> {
+        let mut graph = Self::default();
+        let mut visited = HashSet::new();
+        let mut stack = vec![(recipe.hash(), Some(recipe.clone()))];
+
+        // Depth-first search, recording each recipe once all of its
+        // children have been recorded. An entry without a recipe means
+        // all of that recipe's children are done
+        while let Some((recipe_hash, recipe)) = stack.pop() {
+            let Some(recipe) = recipe else {
+                graph.order.push(recipe_hash);
+                continue;
+            };
+
+            if !visited.insert(recipe_hash) {
+                continue;
+            }
+            if super::has_local_bake(brioche, recipe_hash).await? {
+                continue;
+            }
+
+            let children = match &recipe {
+                Recipe::Proxy(proxy) => {
+                    vec![crate::recipe::get_recipe(brioche, proxy.recipe).await?]
+                }
+                _ => crate::references::inline_recipes(&recipe)
+                    .into_iter()
+                    .cloned()
+                    .collect(),
+            };
+            let children = children
+                .into_iter()
+                .map(|child| (child.hash(), child))
+                .collect::<Vec<_>>();
+            let duration_key = match &recipe {
+                Recipe::Process(process) => Some(process_duration_key(process)),
+                Recipe::CompleteProcess(process) => Some(complete_process_duration_key(process)),
+                _ => None,
+            };
+
+            graph.nodes.insert(
+                recipe_hash,
+                PlanNode {
+                    duration_key,
+                    children: children.iter().map(|(child_hash, _)| *child_hash).collect(),
+                },
+            );
+            stack.push((recipe_hash, None));
+            stack.extend(
+                children
+                    .into_iter()
+                    .map(|(child_hash, child)| (child_hash, Some(child))),
+            );
+        }
+
+        graph.order.reverse();
+        Ok(graph)
+    }
+
+    /// Find the longest path of process durations ending at each process,
+    /// keyed by recipe hash. Each recipe is only visited once, so this is
+    /// linear in the size of the graph.
+    fn critical_paths(&self, durations: &HashMap<String, u64>) -> HashMap<RecipeHash, u64> {
+        let mut path_ms = HashMap::<RecipeHash, u64>::new();
+        let mut priorities = HashMap::<RecipeHash, u64>::new();
+
+        for recipe_hash in &self.order {
+            let node = &self.nodes[recipe_hash];
+            let recipe_path_ms = path_ms.get(recipe_hash).copied().unwrap_or(0);
+
+            let child_path_ms = match &node.duration_key {
+                Some(key) => {
+                    let duration_ms = durations
+                        .get(key)
+                        .copied()
+                        .unwrap_or(DEFAULT_PROCESS_DURATION_MS);
+                    let critical_path_ms = recipe_path_ms.saturating_add(duration_ms);
+
+                    priorities.insert(*recipe_hash, critical_path_ms);
+                    critical_path_ms
+                }
+                None => recipe_path_ms,
+            };
+
+            for child in &node.children {
+                let child_path = path_ms.entry(*child).or_default();
+                *child_path = (*child_path).max(child_path_ms);
+            }
+        }
+
+        priorities
+    }
+}
+
+/// Load the historical durations for each of `duration_keys`. Keys for
+/// processes that have never run are left out.
+async fn load_process_durations(
+    brioche: &Brioche,
+    duration_keys: HashSet<String>,
+) -> anyhow::Result<HashMap<String, u64>> {
+    let duration_keys = duration_keys.into_iter().collect::<Vec<_>>();
+    let mut durations = HashMap::new();
+
+    let mut db_conn = brioche.db_conn.lock().await;
+    let mut db_transaction = db_conn.begin().await?;
+
+    // Load in batches, so we don't hit the maximum number of variables
+    // per query
+    for batch in duration_keys.chunks(900) {
+        let mut arguments = sqlx::sqlite::SqliteArguments::default();
+        for duration_key in batch {
+            arguments
+                .add(duration_key.clone())
+                .map_err(|error| anyhow::anyhow!(error))?;
+        }
+
+        let placeholders = std::iter::repeat("?").take(batch.len()).join_with(", ");
+
+        let rows = sqlx::query_as_with::<_, (String, i64), _>(
+            &format!(
+                r#"
+                    SELECT duration_key, duration_ms
+                    FROM process_durations
+                    WHERE duration_key IN ({placeholders})
+                "#
+            ),
+            arguments,
+        )
+        .fetch_all(&mut *db_transaction)
+        .await?;
+
+        durations.extend(rows.into_iter().map(|(duration_key, duration_ms)| {
+            (duration_key, u64::try_from(duration_ms).unwrap_or(0))
+        }));
+    }
+
+    db_transaction.commit().await?;
+    drop(db_conn);
+
+    Ok(durations)
+}
+
+pub async fn save_process_duration(
+    brioche: &Brioche,
+    duration_key: &str,
+    duration: std::time::Duration,
+) -> anyhow::Result<()> {
+    let duration_ms = i64::try_from(duration.as_millis()).unwrap_or(i64::MAX);
+
+    let mut db_conn = brioche.db_conn.lock().await;
+    sqlx::query!(
+        r#"
+            INSERT INTO process_durations (duration_key, duration_ms)
+            VALUES (?, ?)
+            ON CONFLICT (duration_key) DO UPDATE SET
+                duration_ms = excluded.duration_ms,
+                updated_at = CURRENT_TIMESTAMP
+        "#,
+        duration_key,
+        duration_ms,
+    )
+    .execute(&mut *db_conn)
+    .await?;
+    drop(db_conn);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use crate::recipe::{Recipe, RecipeHash};
+
+    use super::{PlanGraph, PlanNode, ProcessScheduler};
+
+    #[tokio::test]
+    async fn test_process_scheduler_priority_order() {
+        let scheduler = Arc::new(ProcessScheduler::new(2));
+
+        // Fill up the scheduler so everything else has to wait
+        let permit = scheduler.acquire(2, 0).await;
+
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut tasks = vec![];
+        for (name, priority) in [("low", 1), ("high", 100), ("medium", 50)] {
+            let scheduler = scheduler.clone();
+            let order_tx = order_tx.clone();
+            tasks.push(tokio::spawn(async move {
+                let _permit = scheduler.acquire(2, priority).await;
+                order_tx.send(name).unwrap();
+            }));
+
+            // Make sure each task starts waiting before the next
+            while scheduler.state.lock().unwrap().waiters.len() < tasks.len() {
+                tokio::task::yield_now().await;
+            }
+        }
+
+        drop(permit);
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let mut order = vec![];
+        while let Ok(name) = order_rx.try_recv() {
+            order.push(name);
+        }
+        assert_eq!(order, ["high", "medium", "low"]);
+    }
+
+    #[tokio::test]
+    async fn test_process_scheduler_caps_jobs() {
+        let scheduler = Arc::new(ProcessScheduler::new(4));
+
+        // Processes asking for more than the max can still run
+        let permit = scheduler.acquire(16, 0).await;
+        assert_eq!(permit.jobs, 4);
+        drop(permit);
+
+        let first = scheduler.acquire(3, 0).await;
+        let second = scheduler.acquire(1, 0).await;
+        assert_eq!(scheduler.state.lock().unwrap().available_jobs, 0);
+        drop((first, second));
+        assert_eq!(scheduler.state.lock().unwrap().available_jobs, 4);
+    }
+
+    #[test]
+    fn test_process_scheduler_inherit_priority() {
+        let scheduler = ProcessScheduler::new(1);
+        let lazy = Recipe::Symlink {
+            target: "lazy".into(),
+        }
+        .hash();
+        let complete = Recipe::Symlink {
+            target: "complete".into(),
+        }
+        .hash();
+
+        scheduler.priorities.write().unwrap().insert(lazy, 100);
+        scheduler.inherit_priority(lazy, complete);
+
+        assert_eq!(scheduler.priority(complete), 100);
+    }
+
+    #[test]
+    fn test_duration_key_includes_command() {
+        let platform = crate::platform::Platform::X86_64Linux;
+        let args = vec![vec![Some(&b"build.sh"[..])]];
+
+        assert_ne!(
+            super::duration_key(platform, &[Some(&b"/bin/bash"[..])], &args),
+            super::duration_key(platform, &[Some(&b"/bin/sh"[..])], &args),
+        );
+    }
+
+    #[test]
+    fn test_plan_graph_critical_paths_diamonds() {
+        fn recipe_hash(name: &str) -> RecipeHash {
+            Recipe::Symlink {
+                target: name.into(),
+            }
+            .hash()
+        }
+
+        // A chain of diamonds, where each layer has two processes that
+        // both depend on the next layer. Walking every path would take
+        // exponential time
+        let layers = 64;
+        let mut graph = PlanGraph::default();
+        for layer in 0..layers {
+            let next = if layer + 1 < layers {
+                vec![
+                    recipe_hash(&format!("left{}", layer + 1)),
+                    recipe_hash(&format!("right{}", layer + 1)),
+                ]
+            } else {
+                vec![]
+            };
+            for side in ["left", "right"] {
+                let name = format!("{side}{layer}");
+                graph.nodes.insert(
+                    recipe_hash(&name),
+                    PlanNode {
+                        duration_key: Some(name),
+                        children: next.clone(),
+                    },
+                );
+            }
+        }
+        graph.order = (0..layers)
+            .flat_map(|layer| {
+                [
+                    recipe_hash(&format!("left{layer}")),
+                    recipe_hash(&format!("right{layer}")),
+                ]
+            })
+            .collect();
+
+        let durations = HashMap::from([("left0".to_string(), 5), ("right0".to_string(), 10)]);
+        let priorities = graph.critical_paths(&durations);
+
+        assert_eq!(priorities[&recipe_hash("left0")], 5);
+        assert_eq!(priorities[&recipe_hash("right0")], 10);
+        assert_eq!(
+            priorities[&recipe_hash("left1")],
+            10 + super::DEFAULT_PROCESS_DURATION_MS
+        );
+
+        let last = recipe_hash(&format!("left{}", layers - 1));
+        assert_eq!(
+            priorities[&last],
+            10 + (layers - 1) * super::DEFAULT_PROCESS_DURATION_MS,
+        );
+    }
+}
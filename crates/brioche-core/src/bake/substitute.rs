@@ -0,0 +1,134 @@
+use anyhow::Context as _;
+use bstr::BString;
+
+use crate::{
+    Brioche,
+    recipe::{Artifact, Directory, File, ProcessTemplate, ProcessTemplateComponent, Substitute},
+};
+
+#[tracing::instrument(skip(brioche, substitute), fields(file_recipe = %substitute.file.hash()))]
+pub async fn bake_substitute(
+    brioche: &Brioche,
+    scope: &super::BakeScope,
+    substitute: Substitute,
+) -> anyhow::Result<File> {
+    let file = super::bake(brioche, *substitute.file, scope).await?;
+    let Artifact::File(File {
+        content_blob,
+        executable,
+        mut resources,
+    }) = file.value
+    else {
+        anyhow::bail!("tried substituting contents of non-file artifact");
+    };
+
+    let mut replacements = vec![];
+    for (placeholder, template) in substitute.replacements {
+        anyhow::ensure!(
+            !placeholder.is_empty(),
+            "substitution placeholder must not be empty"
+        );
+
+        let value = render_template(brioche, scope, template, &mut resources)
+            .await
+            .with_context(|| format!("failed to render replacement for {placeholder:?}"))?;
+        replacements.push((placeholder, value));
+    }
+
+    let blob_path = {
+        let mut permit = crate::blob::get_save_blob_permit().await?;
+        crate::blob::blob_path(brioche, &mut permit, content_blob).await?
+    };
+    let content = tokio::fs::read(&blob_path)
+        .await
+        .with_context(|| format!("failed to read blob {content_blob}"))?;
+
+    let substituted = substitute_literals(&content, &replacements);
+
+    let content_blob = {
+        let mut permit = crate::blob::get_save_blob_permit().await?;
+        crate::blob::save_blob(
+            brioche,
+            &mut permit,
+            &substituted,
+            crate::blob::SaveBlobOptions::default(),
+        )
+        .await?
+    };
+
+    Ok(File {
+        content_blob,
+        executable,
+        resources,
+    })
+}
+
+/// Render a template as a literal value. Inputs are added as resources of
+/// the output file, and render as their path within the resource
+/// directory (`substitute/<artifact hash>`), so the baked file can be used
+/// on any machine. Other paths aren't allowed, since they would point into
+/// the local machine's output directory.
+async fn render_template(
+    brioche: &Brioche,
+    scope: &super::BakeScope,
+    template: ProcessTemplate,
+    resources: &mut Directory,
+) -> anyhow::Result<BString> {
+    let mut result = BString::default();
+    for component in template.components {
+        match component {
+            ProcessTemplateComponent::Literal { value } => {
+                result.extend_from_slice(&value);
+            }
+            ProcessTemplateComponent::Input { recipe } => {
+                let artifact = super::bake(brioche, recipe, scope).await?;
+                let resource_path = format!("substitute/{}", artifact.value.hash());
+                resources
+                    .insert(brioche, resource_path.as_bytes(), Some(artifact.value))
+                    .await?;
+                result.extend_from_slice(resource_path.as_bytes());
+            }
+            ProcessTemplateComponent::OutputPath
+            | ProcessTemplateComponent::ResourceDir
+            | ProcessTemplateComponent::InputResourceDirs
+            | ProcessTemplateComponent::HomeDir
+            | ProcessTemplateComponent::WorkDir
+            | ProcessTemplateComponent::TempDir => {
+                anyhow::bail!(
+                    "unsupported template component for substitution: only literals and inputs are allowed"
+                );
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Replace each occurrence of a placeholder in `content` with its value.
+/// All placeholders are replaced in a single pass, so replaced values are
+/// never substituted again. When multiple placeholders match at the same
+/// position, the longest one wins.
+fn substitute_literals(content: &[u8], replacements: &[(BString, BString)]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(content.len());
+    let mut remaining = content;
+
+    while !remaining.is_empty() {
+        let matched = replacements
+            .iter()
+            .filter(|(placeholder, _)| remaining.starts_with(placeholder))
+            .max_by_key(|(placeholder, _)| placeholder.len());
+
+        match matched {
+            Some((placeholder, value)) => {
+                result.extend_from_slice(value);
+                remaining = &remaining[placeholder.len()..];
+            }
+            None => {
+                result.push(remaining[0]);
+                remaining = &remaining[1..];
+            }
+        }
+    }
+
+    result
+}
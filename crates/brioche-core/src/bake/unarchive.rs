@@ -1,7 +1,12 @@
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    io::Read as _,
+    sync::Arc,
+};
 
 use anyhow::Context as _;
-use bstr::BString;
+use bstr::{BString, ByteSlice as _};
+use wax::Pattern as _;
 
 use crate::{
     Brioche,
@@ -12,6 +17,8 @@ use crate::{
     reporter::job::{NewJob, UpdateJob},
 };
 
+mod cpio;
+
 #[expect(clippy::cast_possible_truncation)]
 #[tracing::instrument(skip(brioche, unarchive), fields(file_recipe = %unarchive.file.hash(), archive = ?unarchive.archive, compression = ?unarchive.compression))]
 pub async fn bake_unarchive(
@@ -31,6 +38,8 @@ pub async fn bake_unarchive(
 
     tracing::debug!(%blob_hash, archive = ?unarchive.archive, compression = ?unarchive.compression, "starting unarchive");
 
+    let entry_filter = EntryFilter::new(unarchive.strip_components, &unarchive.include)?;
+
     let job_id = brioche.reporter.add_job(NewJob::Unarchive {
         started_at: std::time::Instant::now(),
     });
@@ -43,6 +52,16 @@ pub async fn bake_unarchive(
     let uncompressed_archive_size = archive_file.metadata().await?.len();
     let archive_file = tokio::io::BufReader::new(archive_file);
 
+    // Included tar hardlinks can point to excluded files, which are found
+    // with an extra pass over the archive's headers
+    let link_targets_archive_file =
+        if unarchive.archive == ArchiveFormat::Tar && !unarchive.include.is_empty() {
+            let file = tokio::fs::File::open(&archive_path).await?;
+            Some(tokio::io::BufReader::new(file))
+        } else {
+            None
+        };
+
     let (entry_tx, mut entry_rx) = tokio::sync::mpsc::channel(16);
 
     let mut permit = crate::blob::get_save_blob_permit().await?;
@@ -51,18 +70,42 @@ pub async fn bake_unarchive(
         move || {
             match unarchive.archive {
                 ArchiveFormat::Tar => {
-                    let decompressed_archive_file = unarchive.compression.decompress(archive_file);
                     let decompressed_archive_file =
-                        tokio_util::io::SyncIoBridge::new(decompressed_archive_file);
+                        unarchive.compression.decompress_blocking(archive_file)?;
 
                     let mut archive = tar::Archive::new(decompressed_archive_file);
 
                     let mut buffer = Vec::new();
 
+                    // Paths of excluded entries that included hardlinks
+                    // point to
+                    let excluded_link_targets = match link_targets_archive_file {
+                        Some(link_targets_archive_file) => {
+                            let link_targets_archive = unarchive
+                                .compression
+                                .decompress_blocking(link_targets_archive_file)?;
+                            find_excluded_tar_link_targets(&entry_filter, link_targets_archive)?
+                        }
+                        None => HashSet::new(),
+                    };
+
+                    // Files and symlinks that hardlinks can point to, keyed
+                    // by path
+                    let mut link_targets = HashMap::<BString, ArchiveEntry>::new();
+
                     for archive_entry in archive.entries()? {
                         let archive_entry = archive_entry?;
-                        let entry_path =
-                            bstr::BString::new(archive_entry.path_bytes().into_owned());
+                        let raw_entry_path = archive_entry.path_bytes();
+                        let Some(entry_path) = entry_filter.strip(&raw_entry_path)? else {
+                            continue;
+                        };
+                        let entry_type = archive_entry.header().entry_type();
+                        let is_included = entry_filter.includes(&entry_path);
+                        if !is_included && !excluded_link_targets.contains(&entry_path) {
+                            // Skip entries excluded by the filter without
+                            // reading their contents
+                            continue;
+                        }
                         let entry_mode = archive_entry.header().mode()?;
 
                         let position = archive_entry.raw_file_position();
@@ -77,7 +120,7 @@ pub async fn bake_unarchive(
                             },
                         );
 
-                        let entry = match archive_entry.header().entry_type() {
+                        let entry = match entry_type {
                             tar::EntryType::Regular => {
                                 let entry_blob_hash = crate::blob::save_blob_from_reader_sync(
                                     &brioche,
@@ -109,9 +152,13 @@ pub async fn bake_unarchive(
                                         format!("unsupported tar archive: no link name for hardlink entry at {entry_path}")
                                     })?;
 
-                                ArchiveEntry::Link {
-                                    link_name: link_name.into_owned().into(),
-                                }
+                                let link_name = entry_filter.strip(&link_name)?.with_context(|| {
+                                    format!("unsupported tar archive: link target for entry at {entry_path} is outside the extracted paths")
+                                })?;
+
+                                link_targets.get(&link_name).cloned().with_context(|| {
+                                    format!("unsupported tar archive: could not find target for link entry at {entry_path}")
+                                })?
                             }
                             tar::EntryType::Directory => ArchiveEntry::Directory,
                             tar::EntryType::XGlobalHeader | tar::EntryType::XHeader => {
@@ -125,7 +172,16 @@ pub async fn bake_unarchive(
                             }
                         };
 
-                        entry_tx.blocking_send((entry_path, entry))?;
+                        if matches!(
+                            entry,
+                            ArchiveEntry::File { .. } | ArchiveEntry::Symlink { .. }
+                        ) {
+                            link_targets.insert(entry_path.clone(), entry.clone());
+                        }
+
+                        if is_included {
+                            entry_tx.blocking_send((entry_path, entry))?;
+                        }
                     }
                 }
                 ArchiveFormat::Zip => {
@@ -146,9 +202,11 @@ pub async fn bake_unarchive(
                                 archive_file.name(),
                             );
                         };
-                        let entry_path = bstr::BString::new(
-                            entry_path.as_os_str().as_encoded_bytes().to_owned(),
-                        );
+                        let Some(entry_path) =
+                            entry_filter.filter(entry_path.as_os_str().as_encoded_bytes())?
+                        else {
+                            continue;
+                        };
 
                         let entry = if archive_file.is_dir() {
                             ArchiveEntry::Directory
@@ -182,6 +240,157 @@ pub async fn bake_unarchive(
                         entry_tx.blocking_send((entry_path, entry))?;
                     }
                 }
+                ArchiveFormat::Ar => {
+                    let decompressed_archive_file =
+                        unarchive.compression.decompress_blocking(archive_file)?;
+
+                    let mut archive = ar::Archive::new(decompressed_archive_file);
+                    let mut buffer = Vec::new();
+
+                    while let Some(archive_entry) = archive.next_entry() {
+                        let archive_entry = archive_entry?;
+                        let Some(entry_path) =
+                            entry_filter.filter(archive_entry.header().identifier())?
+                        else {
+                            continue;
+                        };
+                        let executable = archive_entry.header().mode() & 0o100 != 0;
+
+                        let entry_blob_hash = crate::blob::save_blob_from_reader_sync(
+                            &brioche,
+                            &mut permit,
+                            archive_entry,
+                            crate::blob::SaveBlobOptions::new(),
+                            &mut buffer,
+                        )?;
+
+                        entry_tx.blocking_send((
+                            entry_path,
+                            ArchiveEntry::File {
+                                content_blob: entry_blob_hash,
+                                executable,
+                            },
+                        ))?;
+                    }
+                }
+                ArchiveFormat::Cpio => {
+                    let decompressed_archive_file =
+                        unarchive.compression.decompress_blocking(archive_file)?;
+
+                    let mut archive = cpio::CpioReader::new(decompressed_archive_file);
+                    let mut buffer = Vec::new();
+
+                    // In newc archives, the contents of a hardlinked file are
+                    // only stored with the last entry for the file, so the
+                    // paths of the earlier entries are held until then
+                    let mut pending_links = HashMap::<(u64, u64), Vec<(BString, bool)>>::new();
+
+                    while let Some(header) = archive.next_entry()? {
+                        let entry_path = entry_filter.filter(&header.path)?;
+
+                        match header.file_type() {
+                            cpio::S_IFREG => {
+                                let executable = header.mode & 0o100 != 0;
+                                let is_newc_hardlink =
+                                    header.format == cpio::CpioFormat::Newc && header.nlink > 1;
+                                let file_id = (header.dev, header.ino);
+
+                                if is_newc_hardlink && header.size == 0 {
+                                    if let Some(entry_path) = entry_path {
+                                        pending_links
+                                            .entry(file_id)
+                                            .or_default()
+                                            .push((entry_path, executable));
+                                    }
+                                    continue;
+                                }
+
+                                let linked_paths = if is_newc_hardlink {
+                                    pending_links.remove(&file_id).unwrap_or_default()
+                                } else {
+                                    vec![]
+                                };
+                                let paths = entry_path
+                                    .map(|entry_path| (entry_path, executable))
+                                    .into_iter()
+                                    .chain(linked_paths)
+                                    .collect::<Vec<_>>();
+                                if paths.is_empty() {
+                                    continue;
+                                }
+
+                                let entry_blob_hash = crate::blob::save_blob_from_reader_sync(
+                                    &brioche,
+                                    &mut permit,
+                                    archive.entry_reader(),
+                                    crate::blob::SaveBlobOptions::new(),
+                                    &mut buffer,
+                                )?;
+
+                                for (path, executable) in paths {
+                                    entry_tx.blocking_send((
+                                        path,
+                                        ArchiveEntry::File {
+                                            content_blob: entry_blob_hash,
+                                            executable,
+                                        },
+                                    ))?;
+                                }
+                            }
+                            cpio::S_IFLNK => {
+                                let Some(entry_path) = entry_path else {
+                                    continue;
+                                };
+
+                                let mut target = vec![];
+                                archive.entry_reader().read_to_end(&mut target)?;
+
+                                entry_tx.blocking_send((
+                                    entry_path,
+                                    ArchiveEntry::Symlink {
+                                        target: target.into(),
+                                    },
+                                ))?;
+                            }
+                            cpio::S_IFDIR => {
+                                let Some(entry_path) = entry_path else {
+                                    continue;
+                                };
+
+                                entry_tx.blocking_send((entry_path, ArchiveEntry::Directory))?;
+                            }
+                            other => {
+                                anyhow::bail!(
+                                    "unsupported cpio archive: unsupported file type {other:o} at {}",
+                                    header.path,
+                                );
+                            }
+                        }
+                    }
+
+                    // Any leftover hardlinks have no stored contents, meaning
+                    // the linked file is empty
+                    let pending_links = pending_links.into_values().flatten().collect::<Vec<_>>();
+                    if !pending_links.is_empty() {
+                        let empty_blob_hash = crate::blob::save_blob_from_reader_sync(
+                            &brioche,
+                            &mut permit,
+                            std::io::empty(),
+                            crate::blob::SaveBlobOptions::new(),
+                            &mut buffer,
+                        )?;
+
+                        for (path, executable) in pending_links {
+                            entry_tx.blocking_send((
+                                path,
+                                ArchiveEntry::File {
+                                    content_blob: empty_blob_hash,
+                                    executable,
+                                },
+                            ))?;
+                        }
+                    }
+                }
             };
 
             anyhow::Ok(())
@@ -200,36 +409,16 @@ pub async fn bake_unarchive(
                 ArchiveEntry::File {
                     content_blob,
                     executable,
-                } => Some(Artifact::File(File {
+                } => Artifact::File(File {
                     content_blob,
                     executable,
                     resources: Directory::default(),
-                })),
-                ArchiveEntry::Symlink { target } => Some(Artifact::Symlink { target }),
-                ArchiveEntry::Link { link_name } => {
-                    let linked_entry = directory_entries.get(&link_name).with_context(|| {
-                        format!("unsupported tar archive: could not find target for link entry at {entry_path}")
-                    })?;
-                    Some(linked_entry.value.clone())
-                }
-                ArchiveEntry::Directory => Some(Artifact::Directory(Directory::default())),
+                }),
+                ArchiveEntry::Symlink { target } => Artifact::Symlink { target },
+                ArchiveEntry::Directory => Artifact::Directory(Directory::default()),
             };
 
-            let entry_path = crate::fs_utils::logical_path_bytes(&entry_path);
-            let Ok(entry_path) = entry_path else {
-                continue;
-            };
-            if entry_path.is_empty() {
-                continue;
-            }
-            let Some(entry_artifact) = entry_artifact else {
-                continue;
-            };
-
-            directory_entries.insert(
-                entry_path.into(),
-                WithMeta::new(entry_artifact, meta.clone()),
-            );
+            directory_entries.insert(entry_path, WithMeta::new(entry_artifact, meta.clone()));
         }
 
         brioche.reporter.update_job(
@@ -250,6 +439,53 @@ pub async fn bake_unarchive(
     Ok(directory)
 }
 
+/// Find the paths of excluded tar entries that included hardlinks point
+/// to, either directly or through other excluded hardlinks. Only the entry
+/// headers are read.
+fn find_excluded_tar_link_targets(
+    entry_filter: &EntryFilter,
+    archive: impl std::io::Read,
+) -> anyhow::Result<HashSet<BString>> {
+    let mut archive = tar::Archive::new(archive);
+
+    let mut links = HashMap::<BString, BString>::new();
+    let mut included_link_targets = vec![];
+    for archive_entry in archive.entries()? {
+        let archive_entry = archive_entry?;
+        if !archive_entry.header().entry_type().is_hard_link() {
+            continue;
+        }
+
+        let Some(entry_path) = entry_filter.strip(&archive_entry.path_bytes())? else {
+            continue;
+        };
+        let Some(link_name) = archive_entry.link_name_bytes() else {
+            continue;
+        };
+        let Some(link_name) = entry_filter.strip(&link_name)? else {
+            continue;
+        };
+
+        if entry_filter.includes(&entry_path) {
+            included_link_targets.push(link_name.clone());
+        }
+        links.insert(entry_path, link_name);
+    }
+
+    let mut excluded_link_targets = HashSet::new();
+    for mut target in included_link_targets {
+        while !entry_filter.includes(&target) && excluded_link_targets.insert(target.clone()) {
+            match links.get(&target) {
+                Some(next_target) => target = next_target.clone(),
+                None => break,
+            }
+        }
+    }
+
+    Ok(excluded_link_targets)
+}
+
+#[derive(Clone)]
 enum ArchiveEntry {
     File {
         content_blob: BlobHash,
@@ -258,8 +494,79 @@ enum ArchiveEntry {
     Symlink {
         target: BString,
     },
-    Link {
-        link_name: BString,
-    },
     Directory,
 }
+
+/// Decides which archive entries get extracted, and where. Entries are
+/// filtered before their contents are read, so skipped entries never get
+/// saved as blobs.
+struct EntryFilter {
+    strip_components: usize,
+    include: Vec<wax::Glob<'static>>,
+}
+
+impl EntryFilter {
+    fn new(strip_components: u32, include: &BTreeSet<BString>) -> anyhow::Result<Self> {
+        let include = include
+            .iter()
+            .map(|pattern| {
+                let pattern =
+                    std::str::from_utf8(pattern).context("include pattern is not valid UTF-8")?;
+                anyhow::ensure!(
+                    !pattern.contains('!'),
+                    "include patterns with '!' are not supported"
+                );
+                let pattern = wax::Glob::new(pattern)
+                    .with_context(|| format!("invalid include pattern {pattern:?}"))?;
+                anyhow::Ok(pattern.into_owned())
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            strip_components: strip_components.try_into()?,
+            include,
+        })
+    }
+
+    /// Normalize an archive path and remove the leading components. Returns
+    /// `None` if nothing is left of the path, or an error if the path
+    /// escapes the archive (e.g. with `..`).
+    fn strip(&self, path: &[u8]) -> anyhow::Result<Option<BString>> {
+        let path = crate::fs_utils::logical_path_bytes(path)
+            .with_context(|| format!("invalid path in archive: {:?}", path.as_bstr()))?;
+        let stripped = path
+            .split_str("/")
+            .skip(self.strip_components)
+            .collect::<Vec<_>>();
+        if stripped.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(bstr::join("/", stripped).into()))
+    }
+
+    /// Returns the path to extract an entry to, or `None` if the entry
+    /// should be skipped. An entry is included if it or any of its parent
+    /// directories matches an include pattern.
+    fn filter(&self, path: &[u8]) -> anyhow::Result<Option<BString>> {
+        let Some(path) = self.strip(path)? else {
+            return Ok(None);
+        };
+        Ok(self.includes(&path).then_some(path))
+    }
+
+    /// Returns true if an already-stripped path matches the include
+    /// patterns, or if there are no include patterns.
+    fn includes(&self, path: &[u8]) -> bool {
+        if self.include.is_empty() {
+            return true;
+        }
+
+        let path_string = String::from_utf8_lossy(path);
+        path_string
+            .match_indices('/')
+            .map(|(index, _)| &path_string[..index])
+            .chain([&*path_string])
+            .any(|subpath| self.include.iter().any(|glob| glob.is_match(subpath)))
+    }
+}
@@ -0,0 +1,235 @@
+use std::io::Read;
+
+use anyhow::Context as _;
+use bstr::{BString, ByteSlice as _};
+
+const NEWC_MAGIC: &[u8] = b"070701";
+const NEWC_CRC_MAGIC: &[u8] = b"070702";
+const ODC_MAGIC: &[u8] = b"070707";
+const TRAILER_NAME: &[u8] = b"TRAILER!!!";
+
+const NEWC_HEADER_LEN: u64 = 110;
+
+pub const S_IFMT: u32 = 0o170_000;
+pub const S_IFDIR: u32 = 0o040_000;
+pub const S_IFREG: u32 = 0o100_000;
+pub const S_IFLNK: u32 = 0o120_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpioFormat {
+    Newc,
+    Odc,
+}
+
+#[derive(Debug)]
+pub struct CpioHeader {
+    pub format: CpioFormat,
+    pub path: BString,
+    pub dev: u64,
+    pub ino: u64,
+    pub mode: u32,
+    pub nlink: u64,
+    pub size: u64,
+}
+
+impl CpioHeader {
+    pub const fn file_type(&self) -> u32 {
+        self.mode & S_IFMT
+    }
+}
+
+/// A streaming reader for cpio archives in the "newc" or "odc" format.
+pub struct CpioReader<R> {
+    reader: R,
+    remaining: u64,
+    padding: u64,
+    finished: bool,
+}
+
+impl<R> CpioReader<R>
+where
+    R: Read,
+{
+    pub const fn new(reader: R) -> Self {
+        Self {
+            reader,
+            remaining: 0,
+            padding: 0,
+            finished: false,
+        }
+    }
+
+    /// Read the header of the next entry, skipping over any unread contents
+    /// of the previous entry. Returns `None` once the trailer is reached.
+    pub fn next_entry(&mut self) -> anyhow::Result<Option<CpioHeader>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let skip_len = self.remaining + self.padding;
+        self.skip(skip_len)?;
+        self.remaining = 0;
+        self.padding = 0;
+
+        let mut magic = [0; 6];
+        self.reader
+            .read_exact(&mut magic)
+            .context("unsupported cpio archive: failed to read entry header")?;
+
+        let header = match &magic[..] {
+            NEWC_MAGIC | NEWC_CRC_MAGIC => self.read_newc_header()?,
+            ODC_MAGIC => self.read_odc_header()?,
+            _ => {
+                anyhow::bail!(
+                    "unsupported cpio archive: unrecognized header magic {:?}",
+                    magic.as_bstr()
+                );
+            }
+        };
+
+        if header.path == TRAILER_NAME {
+            self.finished = true;
+            return Ok(None);
+        }
+
+        Ok(Some(header))
+    }
+
+    /// Returns a reader for the contents of the current entry.
+    pub const fn entry_reader(&mut self) -> CpioEntryReader<'_, R> {
+        CpioEntryReader { archive: self }
+    }
+
+    fn read_newc_header(&mut self) -> anyhow::Result<CpioHeader> {
+        let mut fields = [0; 13 * 8];
+        self.reader
+            .read_exact(&mut fields)
+            .context("unsupported cpio archive: failed to read entry header")?;
+        let field = |index: usize| parse_number(&fields[index * 8..(index + 1) * 8], 16);
+
+        let ino = field(0)?;
+        let mode = field(1)?;
+        let nlink = field(4)?;
+        let size = field(6)?;
+        let dev_major = field(7)?;
+        let dev_minor = field(8)?;
+        let name_size = field(11)?;
+
+        let path = self.read_name(name_size)?;
+
+        // Both the header (including the name) and the file contents are
+        // padded to a multiple of 4 bytes
+        self.skip(padding_len(NEWC_HEADER_LEN + name_size, 4))?;
+        self.remaining = size;
+        self.padding = padding_len(size, 4);
+
+        Ok(CpioHeader {
+            format: CpioFormat::Newc,
+            path,
+            dev: (dev_major << 32) | dev_minor,
+            ino,
+            mode: mode.try_into().context("invalid cpio entry mode")?,
+            nlink,
+            size,
+        })
+    }
+
+    fn read_odc_header(&mut self) -> anyhow::Result<CpioHeader> {
+        let mut fields = [0; 70];
+        self.reader
+            .read_exact(&mut fields)
+            .context("unsupported cpio archive: failed to read entry header")?;
+
+        let dev = parse_number(&fields[0..6], 8)?;
+        let ino = parse_number(&fields[6..12], 8)?;
+        let mode = parse_number(&fields[12..18], 8)?;
+        let nlink = parse_number(&fields[30..36], 8)?;
+        let name_size = parse_number(&fields[53..59], 8)?;
+        let size = parse_number(&fields[59..70], 8)?;
+
+        let path = self.read_name(name_size)?;
+
+        self.remaining = size;
+        self.padding = 0;
+
+        Ok(CpioHeader {
+            format: CpioFormat::Odc,
+            path,
+            dev,
+            ino,
+            mode: mode.try_into().context("invalid cpio entry mode")?,
+            nlink,
+            size,
+        })
+    }
+
+    fn read_name(&mut self, name_size: u64) -> anyhow::Result<BString> {
+        anyhow::ensure!(
+            name_size > 0,
+            "unsupported cpio archive: entry has an empty name"
+        );
+
+        let mut name = vec![];
+        (&mut self.reader).take(name_size).read_to_end(&mut name)?;
+        anyhow::ensure!(
+            name.len() as u64 == name_size,
+            "unsupported cpio archive: unexpected end of archive while reading entry name"
+        );
+
+        // The name includes a trailing NUL byte
+        let name = name.strip_suffix(b"\0").unwrap_or(&name);
+        Ok(name.into())
+    }
+
+    fn skip(&mut self, len: u64) -> anyhow::Result<()> {
+        let skipped = std::io::copy(&mut (&mut self.reader).take(len), &mut std::io::sink())?;
+        anyhow::ensure!(
+            skipped == len,
+            "unsupported cpio archive: unexpected end of archive"
+        );
+        Ok(())
+    }
+}
+
+pub struct CpioEntryReader<'a, R> {
+    archive: &'a mut CpioReader<R>,
+}
+
+impl<R> Read for CpioEntryReader<'_, R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let max_len = usize::try_from(self.archive.remaining).unwrap_or(usize::MAX);
+        let len = buf.len().min(max_len);
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let read_len = self.archive.reader.read(&mut buf[..len])?;
+        if read_len == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+
+        self.archive.remaining -= read_len as u64;
+        Ok(read_len)
+    }
+}
+
+fn parse_number(field: &[u8], radix: u32) -> anyhow::Result<u64> {
+    let field = std::str::from_utf8(field)
+        .ok()
+        .map(|field| field.trim_matches(|c: char| c == ' ' || c == '\0'))
+        .with_context(|| format!("invalid cpio header field {:?}", field.as_bstr()))?;
+    if field.is_empty() {
+        return Ok(0);
+    }
+
+    let value = u64::from_str_radix(field, radix)
+        .with_context(|| format!("invalid cpio header field {field:?}"))?;
+    Ok(value)
+}
+
+const fn padding_len(len: u64, alignment: u64) -> u64 {
+    (alignment - len % alignment) % alignment
+}
@@ -471,7 +471,7 @@ pub async fn find_blob(brioche: &Brioche, hash: &Hash) -> anyhow::Result<Option<
 
 pub async fn blob_path(
     brioche: &Brioche,
-    _permit: &mut SaveBlobPermit<'_>,
+    permit: &mut SaveBlobPermit<'_>,
     blob_hash: BlobHash,
 ) -> anyhow::Result<PathBuf> {
     let local_path = local_blob_path(brioche, blob_hash);
@@ -480,6 +480,15 @@ pub async fn blob_path(
         return Ok(local_path);
     };
 
+    // If the blob is from an artifact that was loaded from the cache
+    // lazily, fetch it from the cache now
+    let fetched = crate::cache::lazy_blobs::fetch_lazy_blob(brioche, permit, blob_hash)
+        .await
+        .with_context(|| format!("failed to fetch blob {blob_hash} from cache"))?;
+    if fetched {
+        return Ok(local_path);
+    }
+
     if let Some(local_path_dir) = local_path.parent() {
         tokio::fs::create_dir_all(&local_path_dir).await?;
     }
@@ -1,6 +1,7 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use anyhow::Context as _;
+use base64::Engine as _;
 use tokio::io::AsyncWriteExt as _;
 
 use crate::{
@@ -11,50 +12,217 @@ use crate::{
 };
 
 mod archive;
+pub mod chunk_cache;
+pub mod lazy_blobs;
+pub mod prune;
+pub mod server;
+pub mod signing;
+
+pub use archive::{ArchiveCompression, CompressionDictionary};
 
 pub const DEFAULT_CACHE_URL: &str = "https://cache.brioche.dev/";
 pub const DEFAULT_CACHE_MAX_CONCURRENT_OPERATIONS: usize = 200;
 
+/// Reads from and writes to a chain of caches. Reads try each cache in
+/// order until one has the requested object, and writes go to the
+/// first writable cache.
 #[derive(Debug, Default, Clone)]
 pub struct CacheClient {
-    pub store: Option<Arc<dyn object_store::ObjectStore>>,
-    pub writable: bool,
+    pub layers: Vec<CacheLayer>,
     pub max_concurrent_chunk_fetches: Option<usize>,
+
+    /// Allow artifacts to be loaded without fetching their file contents
+    /// up front. See [`load_artifact_lazy`].
+    pub lazy_fetch: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheLayer {
+    pub store: Arc<dyn object_store::ObjectStore>,
+    pub writable: bool,
+
+    /// Whether the cache is accessed over the network. Remote caches
+    /// can't be used in offline mode.
+    pub remote: bool,
+
+    /// Keys trusted to sign bakes and project sources read from this
+    /// cache. Entries without a valid signature from one of these keys
+    /// are ignored. If empty, signatures aren't checked.
+    pub trusted_keys: Vec<signing::PublicKey>,
+
+    /// Key used to sign bakes and project sources written to this cache.
+    pub signing_key: Option<Arc<signing::SigningKey>>,
+
+    /// How artifacts and chunks written to this cache get compressed.
+    pub compression: ArchiveCompression,
 }
 
 impl CacheClient {
-    fn store(&self) -> Option<Arc<dyn object_store::ObjectStore>> {
-        self.store.clone()
+    /// Create a client with a single local cache.
+    pub fn from_store(store: Arc<dyn object_store::ObjectStore>, writable: bool) -> Self {
+        Self {
+            layers: vec![CacheLayer {
+                store,
+                writable,
+                remote: false,
+                trusted_keys: vec![],
+                signing_key: None,
+                compression: ArchiveCompression::default(),
+            }],
+            max_concurrent_chunk_fetches: None,
+            lazy_fetch: false,
+        }
     }
 
-    fn writable_store(&self) -> anyhow::Result<Arc<dyn object_store::ObjectStore>> {
-        let Some(store) = self.try_writable_store() else {
+    fn writable_layer(&self) -> anyhow::Result<&CacheLayer> {
+        let Some(layer) = self.layers.iter().find(|layer| layer.writable) else {
             anyhow::bail!("tried to write to cache, but no writable cache is configured");
         };
 
-        Ok(store)
+        Ok(layer)
+    }
+
+    /// Returns true if any cache can be written to.
+    pub fn is_writable(&self) -> bool {
+        self.layers.iter().any(|layer| layer.writable)
     }
 
-    fn try_writable_store(&self) -> Option<Arc<dyn object_store::ObjectStore>> {
-        if !self.writable {
-            return None;
+    /// Returns true if any cache can be used right now, i.e. it's either
+    /// local or Brioche isn't running in offline mode.
+    pub fn is_available(&self, brioche: &Brioche) -> bool {
+        self.layers
+            .iter()
+            .any(|layer| !(layer.remote && brioche.offline))
+    }
+}
+
+/// Try to load an object from each cache in turn, returning the first
+/// one found. Remote caches are skipped in offline mode, and an offline
+/// error is returned if the object wasn't found in any local cache. A
+/// cache that fails is skipped with a warning, so an error is only
+/// returned if every cache failed.
+async fn load_from_layers<T, F, Fut>(
+    brioche: &Brioche,
+    needed: impl FnOnce() -> String,
+    mut load: F,
+) -> anyhow::Result<Option<T>>
+where
+    F: FnMut(CacheLayer) -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<Option<T>>>,
+{
+    let mut skipped_remote = false;
+    let mut num_loaded = 0;
+    let mut last_error = None;
+    for layer in &brioche.cache_client.layers {
+        if layer.remote && brioche.offline {
+            skipped_remote = true;
+            continue;
         }
 
-        self.store()
+        match load(layer.clone()).await {
+            Ok(Some(value)) => {
+                return Ok(Some(value));
+            }
+            Ok(None) => {
+                num_loaded += 1;
+            }
+            Err(error) => {
+                tracing::warn!("failed to load from cache, trying next cache: {error:#}");
+                last_error = Some(error);
+            }
+        }
+    }
+
+    if skipped_remote {
+        crate::network::ensure_online(brioche, needed)?;
+    }
+
+    match last_error {
+        Some(error) if num_loaded == 0 => Err(error.context("failed to load from every cache")),
+        _ => Ok(None),
     }
 }
 
+fn writable_layer<'a>(
+    brioche: &'a Brioche,
+    needed: impl FnOnce() -> String,
+) -> anyhow::Result<&'a CacheLayer> {
+    let layer = brioche.cache_client.writable_layer()?;
+    if layer.remote {
+        crate::network::ensure_online(brioche, needed)?;
+    }
+
+    Ok(layer)
+}
+
 pub async fn cache_client_from_config_or_default(
     config: Option<&crate::config::CacheConfig>,
+    network_options: &crate::network::NetworkOptions,
+) -> anyhow::Result<CacheClient> {
+    let configs = config.map(std::slice::from_ref).unwrap_or_default();
+    cache_client_from_configs(configs, network_options).await
+}
+
+/// Build a cache client from a list of caches. Caches with a higher
+/// priority are tried first, and caches with the same priority are tried
+/// in the order they're listed. Uses the default cache if the list is
+/// empty.
+pub async fn cache_client_from_configs(
+    configs: &[crate::config::CacheConfig],
+    network_options: &crate::network::NetworkOptions,
 ) -> anyhow::Result<CacheClient> {
-    let max_concurrent_operations = config
-        .map_or(DEFAULT_CACHE_MAX_CONCURRENT_OPERATIONS, |config| {
-            config.max_concurrent_operations
-        });
-    let (url, writable) = match config {
-        Some(config) => (config.url.clone(), !config.read_only),
-        None => (DEFAULT_CACHE_URL.parse()?, false),
+    let default_config;
+    let mut configs = configs.iter().collect::<Vec<_>>();
+    if configs.is_empty() {
+        default_config = crate::config::CacheConfig {
+            url: DEFAULT_CACHE_URL.parse()?,
+            max_concurrent_operations: DEFAULT_CACHE_MAX_CONCURRENT_OPERATIONS,
+            read_only: true,
+            allow_http: None,
+            priority: 0,
+            trusted_keys: vec![],
+            signing_key_path: None,
+            auth: None,
+            headers: BTreeMap::new(),
+            compression: crate::config::CacheCompressionConfig::default(),
+        };
+        configs.push(&default_config);
+    }
+
+    // Stable sort, so ties keep the configured order
+    configs.sort_by_key(|config| std::cmp::Reverse(config.priority));
+
+    let num_writable = configs.iter().filter(|config| !config.read_only).count();
+    anyhow::ensure!(
+        num_writable <= 1,
+        "{num_writable} writable caches are configured, but only one cache can be written to (set `read_only = true` for the others)"
+    );
+
+    let mut layers = vec![];
+    for config in configs {
+        let layer = cache_layer_from_config(config, network_options).await?;
+        layers.push(layer);
+    }
+
+    Ok(CacheClient {
+        layers,
+        max_concurrent_chunk_fetches: Some(DEFAULT_CACHE_MAX_CONCURRENT_OPERATIONS),
+        lazy_fetch: true,
+    })
+}
+
+async fn cache_layer_from_config(
+    config: &crate::config::CacheConfig,
+    network_options: &crate::network::NetworkOptions,
+) -> anyhow::Result<CacheLayer> {
+    let max_concurrent_operations = config.max_concurrent_operations;
+    let url = config.url.clone();
+    let writable = !config.read_only;
+    let signing_key = match &config.signing_key_path {
+        Some(path) => Some(Arc::new(signing::SigningKey::load(path).await?)),
+        None => None,
     };
+    let compression = archive_compression_from_config(&config.compression).await?;
 
     let retry_config = object_store::RetryConfig {
         backoff: object_store::BackoffConfig {
@@ -68,10 +236,26 @@ pub async fn cache_client_from_config_or_default(
 
     let mut client_options = object_store::ClientOptions::new()
         .with_user_agent(http::HeaderValue::from_static(crate::USER_AGENT));
-    if let Some(allow_http) = config.and_then(|config| config.allow_http) {
+    if let Some(allow_http) = config.allow_http {
         client_options = client_options.with_allow_http(allow_http);
     }
-
+    let headers = cache_request_headers(config).await?;
+    if !headers.is_empty() {
+        anyhow::ensure!(
+            matches!(url.scheme(), "http" | "https"),
+            "cache auth and headers are only supported for http:// and https:// caches"
+        );
+        client_options = client_options.with_default_headers(headers);
+    }
+    let remote = matches!(
+        url.scheme(),
+        "http" | "https" | "s3" | "gs" | "az" | "azure"
+    );
+    let client_options = if remote {
+        network_options.apply_to_object_store(&url, client_options)?
+    } else {
+        client_options
+    };
     let store: Arc<dyn object_store::ObjectStore> = match url.scheme() {
         "http" | "https" => {
             let store = object_store::http::HttpBuilder::new()
@@ -83,10 +267,7 @@ pub async fn cache_client_from_config_or_default(
             Arc::new(store)
         }
         "s3" => {
-            let bucket = url
-                .host_str()
-                .context("S3 cache URL must include a bucket name")?;
-            let prefix = url.path().trim_start_matches('/').to_string();
+            let (bucket, prefix) = crate::object_store_utils::bucket_and_prefix(&url)?;
 
             let aws_config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
 
@@ -109,6 +290,30 @@ pub async fn cache_client_from_config_or_default(
             let store = object_store::limit::LimitStore::new(store, max_concurrent_operations);
             Arc::new(store)
         }
+        "gs" => {
+            let (bucket, prefix) = crate::object_store_utils::bucket_and_prefix(&url)?;
+
+            let store = crate::object_store_utils::gcs_builder(bucket)
+                .with_client_options(client_options)
+                .with_retry(retry_config)
+                .build()?;
+            let store = object_store::prefix::PrefixStore::new(store, prefix);
+
+            let store = object_store::limit::LimitStore::new(store, max_concurrent_operations);
+            Arc::new(store)
+        }
+        "az" | "azure" => {
+            let (container, prefix) = crate::object_store_utils::bucket_and_prefix(&url)?;
+
+            let store = crate::object_store_utils::azure_builder(container)
+                .with_client_options(client_options)
+                .with_retry(retry_config)
+                .build()?;
+            let store = object_store::prefix::PrefixStore::new(store, prefix);
+
+            let store = object_store::limit::LimitStore::new(store, max_concurrent_operations);
+            Arc::new(store)
+        }
         "file" => {
             let path = url
                 .to_file_path()
@@ -130,25 +335,131 @@ pub async fn cache_client_from_config_or_default(
         }
     };
 
-    Ok(CacheClient {
-        store: Some(store),
+    Ok(CacheLayer {
+        store,
         writable,
-        max_concurrent_chunk_fetches: Some(DEFAULT_CACHE_MAX_CONCURRENT_OPERATIONS),
+        remote,
+        trusted_keys: config.trusted_keys.clone(),
+        signing_key,
+        compression,
     })
 }
 
+async fn archive_compression_from_config(
+    config: &crate::config::CacheCompressionConfig,
+) -> anyhow::Result<ArchiveCompression> {
+    let defaults = ArchiveCompression::default();
+    let dictionary = match &config.dictionary_path {
+        Some(path) => {
+            let data = tokio::fs::read(path).await.with_context(|| {
+                format!("failed to read compression dictionary {}", path.display())
+            })?;
+            Some(CompressionDictionary::new(data))
+        }
+        None => None,
+    };
+
+    let compression = ArchiveCompression {
+        level: config.level.unwrap_or(defaults.level),
+        long_distance_matching: config.long_distance_matching,
+        dictionary,
+        min_chunk_size: config.min_chunk_size.unwrap_or(defaults.min_chunk_size),
+        avg_chunk_size: config.avg_chunk_size.unwrap_or(defaults.avg_chunk_size),
+        max_chunk_size: config.max_chunk_size.unwrap_or(defaults.max_chunk_size),
+    };
+    compression
+        .validate()
+        .context("invalid cache compression config")?;
+
+    Ok(compression)
+}
+
+/// Build the headers sent with every request to a cache. Header values
+/// are marked as sensitive so they don't show up in logs.
+async fn cache_request_headers(
+    config: &crate::config::CacheConfig,
+) -> anyhow::Result<http::HeaderMap> {
+    let mut headers = http::HeaderMap::new();
+
+    if let Some(auth) = &config.auth {
+        let authorization = match auth {
+            crate::config::CacheAuthConfig::Bearer { token } => {
+                let token = token.read().await?;
+                format!("Bearer {token}")
+            }
+            crate::config::CacheAuthConfig::Basic { username, password } => {
+                let password = password.read().await?;
+                let credentials = base64::engine::general_purpose::STANDARD
+                    .encode(format!("{username}:{password}"));
+                format!("Basic {credentials}")
+            }
+        };
+        let mut authorization =
+            http::HeaderValue::try_from(authorization).context("invalid credentials for cache")?;
+        authorization.set_sensitive(true);
+        headers.insert(http::header::AUTHORIZATION, authorization);
+    }
+
+    for (name, value) in &config.headers {
+        let name = http::HeaderName::try_from(name)
+            .with_context(|| format!("invalid cache header name {name:?}"))?;
+        let value = value.read().await?;
+        let mut value = http::HeaderValue::try_from(value)
+            .with_context(|| format!("invalid value for cache header {name}"))?;
+        value.set_sensitive(true);
+        headers.insert(name, value);
+    }
+
+    Ok(headers)
+}
+
 #[tracing::instrument(skip(brioche))]
 pub async fn load_bake(
     brioche: &Brioche,
     input_hash: RecipeHash,
 ) -> anyhow::Result<Option<RecipeHash>> {
-    let Some(store) = brioche.cache_client.store.clone() else {
-        return Ok(None);
-    };
+    load_from_layers(
+        brioche,
+        || format!("bake {input_hash} from cache"),
+        |layer| async move { load_bake_from_layer(&layer, input_hash).await },
+    )
+    .await
+}
 
+/// Load a bake only from caches that don't need network access, e.g.
+/// a `file://` cache. Used to check what can be baked in offline mode.
+#[tracing::instrument(skip(brioche))]
+pub async fn load_bake_from_local_layers(
+    brioche: &Brioche,
+    input_hash: RecipeHash,
+) -> anyhow::Result<Option<RecipeHash>> {
+    for layer in brioche
+        .cache_client
+        .layers
+        .iter()
+        .filter(|layer| !layer.remote)
+    {
+        match load_bake_from_layer(layer, input_hash).await {
+            Ok(Some(output_hash)) => {
+                return Ok(Some(output_hash));
+            }
+            Ok(None) => {}
+            Err(error) => {
+                tracing::warn!("failed to load bake from cache, trying next cache: {error:#}");
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+async fn load_bake_from_layer(
+    layer: &CacheLayer,
+    input_hash: RecipeHash,
+) -> anyhow::Result<Option<RecipeHash>> {
     let bake_output_path =
         object_store::path::Path::from_iter(["bakes", &input_hash.to_string(), "output.json"]);
-    let bake_output_object = store.get(&bake_output_path).await;
+    let bake_output_object = layer.store.get(&bake_output_path).await;
     let bake_output_object = match bake_output_object {
         Ok(bake_output_object) => bake_output_object,
         Err(object_store::Error::NotFound { .. }) => return Ok(None),
@@ -161,6 +472,19 @@ pub async fn load_bake(
     let bake_output: CachedBakeOutput = serde_json::from_slice(&bake_output_json[..])
         .with_context(|| format!("failed to deserialize cache object at '{bake_output_path}'"))?;
 
+    if !layer.trusted_keys.is_empty() {
+        let message = bake_signature_message(input_hash, bake_output.output_hash);
+        let is_trusted = signing::is_trusted(
+            &layer.trusted_keys,
+            &message,
+            bake_output.signature.as_ref(),
+        );
+        if !is_trusted {
+            tracing::warn!(%input_hash, "ignoring bake from cache without a trusted signature");
+            return Ok(None);
+        }
+    }
+
     Ok(Some(bake_output.output_hash))
 }
 
@@ -170,14 +494,22 @@ pub async fn save_bake(
     input_hash: RecipeHash,
     output_hash: RecipeHash,
 ) -> anyhow::Result<bool> {
-    let store = brioche.cache_client.writable_store()?;
+    let layer = writable_layer(brioche, || format!("save bake {input_hash} to cache"))?;
 
     let bake_output_path =
         object_store::path::Path::from_iter(["bakes", &input_hash.to_string(), "output.json"]);
-    let bake_output = CachedBakeOutput { output_hash };
+    let signature = layer
+        .signing_key
+        .as_ref()
+        .map(|key| key.sign(&bake_signature_message(input_hash, output_hash)));
+    let bake_output = CachedBakeOutput {
+        output_hash,
+        signature,
+    };
     let bake_output_json = serde_json::to_string(&bake_output)?;
 
-    let put_result = store
+    let put_result = layer
+        .store
         .put_opts(
             &bake_output_path,
             bake_output_json.into(),
@@ -199,16 +531,93 @@ pub async fn save_bake(
     Ok(did_create)
 }
 
+/// Returns true if the writable cache already has a bake for the recipe.
+pub async fn writable_cache_has_bake(
+    brioche: &Brioche,
+    input_hash: RecipeHash,
+) -> anyhow::Result<bool> {
+    let path =
+        object_store::path::Path::from_iter(["bakes", &input_hash.to_string(), "output.json"]);
+    writable_cache_has_object(brioche, &path).await
+}
+
+/// Returns true if the writable cache already has the artifact.
+pub async fn writable_cache_has_artifact(
+    brioche: &Brioche,
+    artifact_hash: RecipeHash,
+) -> anyhow::Result<bool> {
+    let artifact_filename = format!("{artifact_hash}.bar.zst");
+    let path = object_store::path::Path::from_iter(["artifacts", &artifact_filename]);
+    writable_cache_has_object(brioche, &path).await
+}
+
+async fn writable_cache_has_object(
+    brioche: &Brioche,
+    path: &object_store::path::Path,
+) -> anyhow::Result<bool> {
+    let layer = writable_layer(brioche, || format!("check for {path} in cache"))?;
+    match layer.store.head(path).await {
+        Ok(_) => Ok(true),
+        Err(object_store::Error::NotFound { .. }) => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
+
 #[tracing::instrument(skip(brioche))]
 pub async fn load_artifact(
     brioche: &Brioche,
     artifact_hash: RecipeHash,
     fetch_kind: CacheFetchKind,
 ) -> anyhow::Result<Option<Artifact>> {
-    let Some(store) = brioche.cache_client.store.clone() else {
-        return Ok(None);
-    };
+    load_from_layers(
+        brioche,
+        || format!("artifact {artifact_hash} from cache"),
+        |layer| {
+            let fetch_kind = fetch_kind.clone();
+            async move {
+                load_artifact_from_layer(brioche, &layer, artifact_hash, fetch_kind, false).await
+            }
+        },
+    )
+    .await
+}
 
+/// Load an artifact from the cache, but (if lazy fetching is enabled)
+/// skip fetching the contents of its files. Each file's contents get
+/// fetched when they're first needed, such as when creating an output
+/// or running a process. This avoids downloading all of a large artifact
+/// when only a few of its files get used.
+#[tracing::instrument(skip(brioche))]
+pub async fn load_artifact_lazy(
+    brioche: &Brioche,
+    artifact_hash: RecipeHash,
+    fetch_kind: CacheFetchKind,
+) -> anyhow::Result<Option<Artifact>> {
+    let lazy = brioche.cache_client.lazy_fetch;
+    load_from_layers(
+        brioche,
+        || format!("artifact {artifact_hash} from cache"),
+        |layer| {
+            let fetch_kind = fetch_kind.clone();
+            async move {
+                load_artifact_from_layer(brioche, &layer, artifact_hash, fetch_kind, lazy).await
+            }
+        },
+    )
+    .await
+}
+
+/// Load an artifact from a single cache. Any chunks the artifact needs
+/// get fetched from the same cache, going through the local chunk cache
+/// for remote caches.
+async fn load_artifact_from_layer(
+    brioche: &Brioche,
+    layer: &CacheLayer,
+    artifact_hash: RecipeHash,
+    fetch_kind: CacheFetchKind,
+    lazy: bool,
+) -> anyhow::Result<Option<Artifact>> {
+    let store = &layer.store;
     let artifact_filename = format!("{artifact_hash}.bar.zst");
     let artifact_path = object_store::path::Path::from_iter(["artifacts", &artifact_filename]);
 
@@ -226,8 +635,20 @@ pub async fn load_artifact(
     let mut archive_reader =
         async_compression::tokio::bufread::ZstdDecoder::new(archive_reader_compressed);
 
-    let artifact =
-        archive::read_artifact_archive(brioche, &store, fetch_kind, &mut archive_reader).await?;
+    let chunk_cache = if layer.remote {
+        brioche.chunk_cache.clone()
+    } else {
+        None
+    };
+    let artifact = archive::read_artifact_archive(
+        brioche,
+        store,
+        chunk_cache,
+        fetch_kind,
+        lazy,
+        &mut archive_reader,
+    )
+    .await?;
 
     let actual_hash = artifact.hash();
     anyhow::ensure!(
@@ -240,7 +661,10 @@ pub async fn load_artifact(
 
 #[tracing::instrument(skip_all, fields(artifact_hash = %artifact.hash()))]
 pub async fn save_artifact(brioche: &Brioche, artifact: Artifact) -> anyhow::Result<bool> {
-    let store = brioche.cache_client.writable_store()?;
+    let layer = writable_layer(brioche, || {
+        format!("save artifact {} to cache", artifact.hash())
+    })?;
+    let store = &layer.store;
 
     let artifact_filename = format!("{}.bar.zst", artifact.hash());
     let artifact_path = object_store::path::Path::from_iter(["artifacts", &artifact_filename]);
@@ -263,10 +687,19 @@ pub async fn save_artifact(brioche: &Brioche, artifact: Artifact) -> anyhow::Res
         }
     }
 
+    let compression = &layer.compression;
     let mut archive_compressed = vec![];
-    let mut archive_writer =
-        async_compression::tokio::write::ZstdEncoder::new(&mut archive_compressed);
-    archive::write_artifact_archive(brioche, artifact, &store, &mut archive_writer).await?;
+    let mut archive_writer = async_compression::tokio::write::ZstdEncoder::with_quality_and_params(
+        &mut archive_compressed,
+        async_compression::Level::Precise(compression.level),
+        &[
+            async_compression::zstd::CParameter::enable_long_distance_matching(
+                compression.long_distance_matching,
+            ),
+        ],
+    );
+    archive::write_artifact_archive(brioche, artifact, store, compression, &mut archive_writer)
+        .await?;
     archive_writer.shutdown().await?;
 
     let put_result = store
@@ -296,13 +729,21 @@ pub async fn load_project_artifact_hash(
     brioche: &Brioche,
     project_hash: ProjectHash,
 ) -> anyhow::Result<Option<RecipeHash>> {
-    let Some(store) = brioche.cache_client.store.clone() else {
-        return Ok(None);
-    };
+    load_from_layers(
+        brioche,
+        || format!("project {project_hash} from cache"),
+        |layer| async move { load_project_artifact_hash_from_layer(&layer, project_hash).await },
+    )
+    .await
+}
 
+async fn load_project_artifact_hash_from_layer(
+    layer: &CacheLayer,
+    project_hash: ProjectHash,
+) -> anyhow::Result<Option<RecipeHash>> {
     let project_source_path =
         object_store::path::Path::from_iter(["projects", &project_hash.to_string(), "source.json"]);
-    let project_source_object = store.get(&project_source_path).await;
+    let project_source_object = layer.store.get(&project_source_path).await;
     let project_source_object = match project_source_object {
         Ok(project_source_object) => project_source_object,
         Err(object_store::Error::NotFound { .. }) => return Ok(None),
@@ -317,6 +758,19 @@ pub async fn load_project_artifact_hash(
             format!("failed to deserialize cache object at '{project_source_path}'")
         })?;
 
+    if !layer.trusted_keys.is_empty() {
+        let message = project_signature_message(project_hash, project_source.artifact_hash);
+        let is_trusted = signing::is_trusted(
+            &layer.trusted_keys,
+            &message,
+            project_source.signature.as_ref(),
+        );
+        if !is_trusted {
+            tracing::warn!(%project_hash, "ignoring project from cache without a trusted signature");
+            return Ok(None);
+        }
+    }
+
     Ok(Some(project_source.artifact_hash))
 }
 
@@ -326,14 +780,22 @@ pub async fn save_project_artifact_hash(
     project_hash: ProjectHash,
     artifact_hash: RecipeHash,
 ) -> anyhow::Result<bool> {
-    let store = brioche.cache_client.writable_store()?;
+    let layer = writable_layer(brioche, || format!("save project {project_hash} to cache"))?;
 
     let project_source_path =
         object_store::path::Path::from_iter(["projects", &project_hash.to_string(), "source.json"]);
-    let project_source = CachedProjectSource { artifact_hash };
+    let signature = layer
+        .signing_key
+        .as_ref()
+        .map(|key| key.sign(&project_signature_message(project_hash, artifact_hash)));
+    let project_source = CachedProjectSource {
+        artifact_hash,
+        signature,
+    };
     let project_source_json = serde_json::to_string(&project_source)?;
 
-    let put_result = store
+    let put_result = layer
+        .store
         .put_opts(
             &project_source_path,
             project_source_json.into(),
@@ -355,14 +817,30 @@ pub async fn save_project_artifact_hash(
     Ok(did_create)
 }
 
-#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+/// The message signed for a bake, which ties the input recipe to the
+/// output artifact.
+fn bake_signature_message(input_hash: RecipeHash, output_hash: RecipeHash) -> Vec<u8> {
+    format!("brioche-cache-bake-v1:{input_hash}:{output_hash}").into_bytes()
+}
+
+fn project_signature_message(project_hash: ProjectHash, artifact_hash: RecipeHash) -> Vec<u8> {
+    format!("brioche-cache-project-v1:{project_hash}:{artifact_hash}").into_bytes()
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CachedBakeOutput {
     output_hash: RecipeHash,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<signing::CacheSignature>,
 }
 
-#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CachedProjectSource {
     artifact_hash: RecipeHash,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<signing::CacheSignature>,
 }
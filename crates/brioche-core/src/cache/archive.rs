@@ -12,7 +12,7 @@
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
     ops::Range,
-    sync::Arc,
+    sync::{Arc, OnceLock, RwLock},
 };
 
 use anyhow::Context as _;
@@ -24,11 +24,13 @@ use crate::{
     blob::{BlobHash, SaveBlobOptions},
     recipe::{Artifact, Recipe, RecipeHash},
     reporter::{
-        JobId,
+        JobId, Reporter,
         job::{CacheFetchKind, NewJob, UpdateJob},
     },
 };
 
+use super::chunk_cache::ChunkCache;
+
 const MARKER: &[u8; 32] = b"brioche_artifact_archive_v0     ";
 
 const BLOBS_CHUNKING_THRESHOLD: u64 = 2_097_152;
@@ -36,15 +38,176 @@ const CDC_MIN_CHUNK_SIZE: u32 = 524_288;
 const CDC_AVG_CHUNK_SIZE: u32 = 1_048_576;
 const CDC_MAX_CHUNK_SIZE: u32 = 8_388_608;
 
+/// Settings used to compress an artifact archive and its chunks. When
+/// any setting differs from the default, the settings get recorded in the
+/// archive itself, so readers don't need to be configured to match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveCompression {
+    /// The zstd compression level. 0 uses zstd's default level.
+    pub level: i32,
+
+    /// Enable zstd's long-distance matching, which finds repeated data
+    /// that's much further apart at the cost of using more memory.
+    pub long_distance_matching: bool,
+
+    /// A trained zstd dictionary used to compress chunks. The archive
+    /// itself is never compressed with the dictionary, since readers need
+    /// to read the archive to find out which dictionary to use.
+    pub dictionary: Option<CompressionDictionary>,
+
+    pub min_chunk_size: u32,
+    pub avg_chunk_size: u32,
+    pub max_chunk_size: u32,
+}
+
+impl Default for ArchiveCompression {
+    fn default() -> Self {
+        Self {
+            level: 0,
+            long_distance_matching: false,
+            dictionary: None,
+            min_chunk_size: CDC_MIN_CHUNK_SIZE,
+            avg_chunk_size: CDC_AVG_CHUNK_SIZE,
+            max_chunk_size: CDC_MAX_CHUNK_SIZE,
+        }
+    }
+}
+
+impl ArchiveCompression {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let levels = zstd::compression_level_range();
+        anyhow::ensure!(
+            levels.contains(&self.level),
+            "zstd compression level must be between {} and {}, but got {}",
+            levels.start(),
+            levels.end(),
+            self.level,
+        );
+
+        validate_chunk_size(
+            "min",
+            self.min_chunk_size,
+            fastcdc::v2020::MINIMUM_MIN..=fastcdc::v2020::MINIMUM_MAX,
+        )?;
+        validate_chunk_size(
+            "average",
+            self.avg_chunk_size,
+            fastcdc::v2020::AVERAGE_MIN..=fastcdc::v2020::AVERAGE_MAX,
+        )?;
+        validate_chunk_size(
+            "max",
+            self.max_chunk_size,
+            fastcdc::v2020::MAXIMUM_MIN..=fastcdc::v2020::MAXIMUM_MAX,
+        )?;
+        anyhow::ensure!(
+            self.min_chunk_size <= self.avg_chunk_size
+                && self.avg_chunk_size <= self.max_chunk_size,
+            "chunk sizes must satisfy min <= average <= max",
+        );
+
+        Ok(())
+    }
+
+    /// Compress a chunk's data, using the dictionary if there is one.
+    fn compress_chunk(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut compressor = match &self.dictionary {
+            Some(dictionary) => {
+                zstd::bulk::Compressor::with_dictionary(self.level, &dictionary.data)?
+            }
+            None => zstd::bulk::Compressor::new(self.level)?,
+        };
+        compressor.set_parameter(zstd::zstd_safe::CParameter::EnableLongDistanceMatching(
+            self.long_distance_matching,
+        ))?;
+
+        let compressed = compressor.compress(data)?;
+        Ok(compressed)
+    }
+}
+
+fn validate_chunk_size(
+    name: &str,
+    size: u32,
+    range: std::ops::RangeInclusive<u32>,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        range.contains(&size),
+        "{name} chunk size must be between {} and {} bytes, but got {size}",
+        range.start(),
+        range.end(),
+    );
+    Ok(())
+}
+
+/// A trained zstd dictionary, such as one created with `zstd --train`.
+/// Dictionaries are stored in the cache by hash, so any reader can find
+/// the dictionary an archive's chunks were compressed with.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CompressionDictionary {
+    hash: blake3::Hash,
+    data: Arc<[u8]>,
+}
+
+impl CompressionDictionary {
+    pub fn new(data: Vec<u8>) -> Self {
+        let hash = blake3::hash(&data);
+        Self {
+            hash,
+            data: data.into(),
+        }
+    }
+
+    pub fn hash(&self) -> blake3::Hash {
+        self.hash
+    }
+}
+
+impl std::fmt::Debug for CompressionDictionary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Skip the dictionary data, which can be hundreds of kilobytes
+        f.debug_struct("CompressionDictionary")
+            .field("hash", &self.hash)
+            .finish_non_exhaustive()
+    }
+}
+
 pub async fn write_artifact_archive(
     brioche: &Brioche,
     artifact: Artifact,
     store: &Arc<dyn object_store::ObjectStore>,
+    compression: &ArchiveCompression,
     writer: &mut (impl tokio::io::AsyncWrite + Unpin + Send),
 ) -> anyhow::Result<()> {
     // Write the marker for a valid archive
     writer.write_all(MARKER).await?;
 
+    // Record the compression settings if they aren't the defaults. This
+    // is skipped otherwise so archives stay readable by older versions
+    if *compression != ArchiveCompression::default() {
+        // Write the compression entry: tag, level, long-distance matching
+        // flag, chunk sizes, dictionary hash
+        writer.write_all(b"z").await?;
+        writer.write_i32(compression.level).await?;
+        let long_distance_matching_tag = if compression.long_distance_matching {
+            b"l+"
+        } else {
+            b"l-"
+        };
+        writer.write_all(long_distance_matching_tag).await?;
+        writer.write_u32(compression.min_chunk_size).await?;
+        writer.write_u32(compression.avg_chunk_size).await?;
+        writer.write_u32(compression.max_chunk_size).await?;
+        match &compression.dictionary {
+            Some(dictionary) => {
+                writer.write_all(b"+").await?;
+                writer.write_all(dictionary.hash.as_bytes()).await?;
+            }
+            None => {
+                writer.write_all(b"-").await?;
+            }
+        }
+    }
+
     // Track all the blobs we need to add in the archive
     let mut artifact_blobs = BTreeSet::<BlobHash>::new();
 
@@ -104,6 +267,10 @@ pub async fn write_artifact_archive(
         }
     }
 
+    // Make sure we have every blob locally, in case the artifact was
+    // loaded from a cache lazily
+    super::lazy_blobs::fetch_lazy_blobs(brioche, artifact_blobs.iter().copied()).await?;
+
     // Get the list of blobs in the archive plus their lengths, in the
     // order to store them in the archive
     let blobs = tokio::task::spawn_blocking({
@@ -150,8 +317,18 @@ pub async fn write_artifact_archive(
         // Write a "start chunk" tag. Following this will be a list of chunks
         writer.write_all(b"C").await?;
 
+        // Make sure the dictionary is in the cache before any chunks
+        // compressed with it
+        let dictionary_hash = match &compression.dictionary {
+            Some(dictionary) => {
+                save_dictionary(store, dictionary).await?;
+                Some(dictionary.hash)
+            }
+            None => None,
+        };
+
         let (blobs_reader, mut blobs_writer) =
-            tokio::io::simplex(CDC_MAX_CHUNK_SIZE.try_into().unwrap());
+            tokio::io::simplex(compression.max_chunk_size.try_into()?);
 
         let read_blobs_task = tokio::spawn({
             let brioche = brioche.clone();
@@ -178,9 +355,9 @@ pub async fn write_artifact_archive(
         // Use the FastCDC algorithm to divide the blob data into chunks
         let mut chunks = fastcdc::v2020::AsyncStreamCDC::new(
             blobs_reader,
-            CDC_MIN_CHUNK_SIZE,
-            CDC_AVG_CHUNK_SIZE,
-            CDC_MAX_CHUNK_SIZE,
+            compression.min_chunk_size,
+            compression.avg_chunk_size,
+            compression.max_chunk_size,
         );
         let chunks = chunks.as_stream();
         let mut chunks = std::pin::pin!(chunks);
@@ -192,12 +369,12 @@ pub async fn write_artifact_archive(
             // we're chunking the concatenation of all the blobs together,
             // meaning chunks can be made of multiple blobs or parts of blobs.
             let chunk_hash = blake3::hash(&chunk.data);
-            let chunk_compressed_filename = format!("{chunk_hash}.zst");
+            let chunk_compressed_filename = chunk_filename(chunk_hash, dictionary_hash);
             let chunk_path =
                 object_store::path::Path::from_iter(["chunks", &chunk_compressed_filename]);
 
             // Compress the chunk data
-            let chunk_compressed = zstd::encode_all(&chunk.data[..], 0)?;
+            let chunk_compressed = compression.compress_chunk(&chunk.data)?;
 
             // Try to write the compressed chunk to the cache
             let result = store
@@ -342,12 +519,34 @@ pub enum DataEntry {
 pub struct ChunkEntry {
     pub hash: blake3::Hash,
     pub artifact_range: Range<u64>,
+
+    /// The hash of the dictionary the chunk was compressed with, if any.
+    pub dictionary: Option<blake3::Hash>,
+}
+
+impl ChunkEntry {
+    pub fn filename(&self) -> String {
+        chunk_filename(self.hash, self.dictionary)
+    }
+}
+
+/// The filename of a compressed chunk within the cache's `chunks/`
+/// directory. Chunks compressed with a dictionary can only be read using
+/// the same dictionary, so they're stored separately from chunks compressed
+/// without one.
+pub(super) fn chunk_filename(chunk_hash: blake3::Hash, dictionary: Option<blake3::Hash>) -> String {
+    match dictionary {
+        Some(dictionary_hash) => format!("{chunk_hash}.{dictionary_hash}.zst"),
+        None => format!("{chunk_hash}.zst"),
+    }
 }
 
 pub async fn read_artifact_archive(
     brioche: &Brioche,
     store: &Arc<dyn object_store::ObjectStore>,
+    chunk_cache: Option<Arc<ChunkCache>>,
     fetch_kind: CacheFetchKind,
+    lazy: bool,
     mut reader: &mut (impl tokio::io::AsyncRead + Unpin),
 ) -> anyhow::Result<Artifact> {
     let job_id = brioche.reporter.add_job(NewJob::CacheFetch {
@@ -359,6 +558,232 @@ pub async fn read_artifact_archive(
         started_at: std::time::Instant::now(),
     });
 
+    let ArchiveIndex {
+        entries,
+        blobs,
+        data,
+    } = read_archive_index(reader).await?;
+
+    // Determine which blobs we need to read from the archive. We skip over
+    // any blobs that we already have locally
+    let (needed_blobs, empty_blobs) = tokio::task::spawn_blocking({
+        let brioche = brioche.clone();
+        move || {
+            let mut needed_blobs = vec![];
+            let mut empty_blobs = vec![];
+            for (blob_hash, range) in blobs {
+                let blob_path = crate::blob::local_blob_path(&brioche, blob_hash);
+                if !blob_path.try_exists()? {
+                    if range.is_empty() {
+                        // Blob doesn't exist locally but is empty. We'll
+                        // create it separately from the rest to avoid
+                        // special-case rules around ranges
+                        empty_blobs.push(blob_hash);
+                    } else {
+                        // Blob doesn't exist locally, so add it to the list
+                        // we need from the archive
+                        needed_blobs.push((blob_hash, range));
+                    }
+                }
+            }
+
+            // Make sure the blob is sorted based on the order it appears
+            // in the archive, since we can't rewind the reader
+            needed_blobs.sort_by_key(|(_, range)| range.start);
+
+            anyhow::Ok((needed_blobs, empty_blobs))
+        }
+    })
+    .await??;
+
+    // When reading lazily from chunks, record where each blob can be found
+    // instead of fetching it. The blob gets fetched later, if and when
+    // it's actually needed. Inline data is already part of the archive,
+    // so there's no reason to skip it
+    let needed_blobs = match &data {
+        DataEntry::Chunks { chunks } if lazy => {
+            let lazy_blobs = needed_blobs
+                .into_iter()
+                .map(|(blob_hash, blob_range)| {
+                    let parts = blob_chunks(chunks, blob_hash, &blob_range)?
+                        .into_iter()
+                        .map(|(chunk, range)| LazyBlobPart {
+                            chunk_hash: chunk.hash,
+                            chunk_range: chunk.artifact_range,
+                            dictionary: chunk.dictionary,
+                            range,
+                        })
+                        .collect();
+                    anyhow::Ok((blob_hash, parts))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            super::lazy_blobs::save_lazy_blobs(brioche, &lazy_blobs).await?;
+
+            vec![]
+        }
+        _ => needed_blobs,
+    };
+
+    let total_needed_bytes = needed_blobs
+        .iter()
+        .map(|(_, range)| range.end.saturating_sub(range.start))
+        .sum::<u64>();
+    let total_needed_blobs: u64 = needed_blobs.len().try_into()?;
+    brioche.reporter.update_job(
+        job_id,
+        UpdateJob::CacheFetchUpdate {
+            downloaded_data: None,
+            total_data: Some(total_needed_bytes),
+            downloaded_blobs: None,
+            total_blobs: Some(total_needed_blobs),
+        },
+    );
+
+    // If the archive contained a hash for an empty blob, create it separately
+    // since we don't need to read the archive to create it. Normally, this
+    // loop will run at most once, but could run multiple times if a malformed
+    // archive has different hashes for the empty blob
+    for blob_hash in empty_blobs {
+        let mut permit = crate::blob::get_save_blob_permit().await?;
+
+        // Create the empty blob and validate the hash
+        crate::blob::save_blob(
+            brioche,
+            &mut permit,
+            &[],
+            SaveBlobOptions::new().expected_blob_hash(Some(blob_hash)),
+        )
+        .await?;
+    }
+
+    match data {
+        DataEntry::Inline => {
+            // The archive data is inline in the archive, so we're reading
+            // from the same reader right at the end of the archive
+
+            let mut permit = crate::blob::get_save_blob_permit().await?;
+
+            let mut inline_offset = 0;
+            let mut buffer = vec![];
+            for (blob_hash, range) in needed_blobs {
+                // Advance the reader to the start of the next needed blob
+                let Some(skip_length) = range.start.checked_sub(inline_offset) else {
+                    unreachable!("needed blobs are out of order");
+                };
+                reader_consume_exact(reader, skip_length).await?;
+
+                let length = range.end.checked_sub(range.start);
+                let Some(length) = length else {
+                    unreachable!("needed blob range is invalid");
+                };
+
+                // Read the blob
+                let blob_reader = (&mut reader).take(length);
+                crate::blob::save_blob_from_reader(
+                    brioche,
+                    &mut permit,
+                    blob_reader,
+                    SaveBlobOptions::new().expected_blob_hash(Some(blob_hash)),
+                    &mut buffer,
+                )
+                .await?;
+
+                brioche.reporter.update_job(
+                    job_id,
+                    UpdateJob::CacheFetchAdd {
+                        downloaded_data: Some(length),
+                        downloaded_blobs: Some(1),
+                    },
+                );
+
+                inline_offset = range.end;
+            }
+        }
+        DataEntry::Chunks { chunks } => {
+            // The archive data is broken up into chunks, so we need to
+            // determine which parts of which chunks we need to read
+
+            let needed_blob_chunks = needed_blobs
+                .into_iter()
+                .map(|(blob_hash, blob_range)| {
+                    let chunks = blob_chunks(&chunks, blob_hash, &blob_range)?;
+                    anyhow::Ok((blob_hash, chunks))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let fetches = group_blob_fetches(needed_blob_chunks);
+
+            // Fetch all the blobs from the chunks concurrently
+            let concurrent_chunk_fetches = brioche
+                .cache_client
+                .max_concurrent_chunk_fetches
+                .unwrap_or(100);
+            futures::stream::iter(fetches)
+                .map(Ok)
+                .try_for_each_concurrent(concurrent_chunk_fetches, |fetch| {
+                    let brioche = brioche.clone();
+                    let store = store.clone();
+                    let chunk_cache = chunk_cache.clone();
+                    async move {
+                        let mut permit = crate::blob::get_save_blob_permit().await?;
+                        fetch_blobs_from_chunks(
+                            brioche,
+                            store,
+                            chunk_cache,
+                            &mut permit,
+                            job_id,
+                            fetch,
+                        )
+                        .await
+                    }
+                })
+                .await?;
+        }
+    }
+
+    // Now that we have all the blobs, build the final artifact
+
+    let mut result: Option<ArtifactBuilder> = None;
+    for entry in entries {
+        insert_into_artifact(&mut result, &entry.path, &entry.path.components, entry.node)?;
+    }
+
+    let mut new_recipes = HashMap::new();
+    let result = result.context("no artifact entries in archive")?;
+    let result = result.build(&mut new_recipes);
+
+    brioche.reporter.update_job(
+        job_id,
+        UpdateJob::CacheFetchUpdate {
+            downloaded_data: Some(total_needed_bytes),
+            total_data: Some(total_needed_bytes),
+            downloaded_blobs: Some(total_needed_blobs),
+            total_blobs: Some(total_needed_blobs),
+        },
+    );
+
+    crate::recipe::save_recipes(brioche, new_recipes.values()).await?;
+
+    brioche.reporter.update_job(
+        job_id,
+        UpdateJob::CacheFetchFinish {
+            finished_at: std::time::Instant::now(),
+        },
+    );
+
+    Ok(result)
+}
+
+/// The entries, blobs, and data layout of an artifact archive, which
+/// are read from the start of the archive before any blob data.
+struct ArchiveIndex {
+    entries: Vec<ArtifactEntry>,
+    blobs: BTreeMap<BlobHash, Range<u64>>,
+    data: DataEntry,
+}
+
+async fn read_archive_index(
+    reader: &mut (impl tokio::io::AsyncRead + Unpin),
+) -> anyhow::Result<ArchiveIndex> {
     // Read and validate the marker from the archive
     let mut marker = [0; MARKER.len()];
     reader.read_exact(&mut marker).await?;
@@ -372,6 +797,7 @@ pub async fn read_artifact_archive(
     let mut blob_offset = 0;
     let mut chunk_offset = 0;
     let mut data = None;
+    let mut dictionary = None;
 
     loop {
         // Read the next tag from the archive, or exit if we've hit the end
@@ -382,6 +808,48 @@ pub async fn read_artifact_archive(
         }
 
         match &tag {
+            b"z" => {
+                // Compression tag: read the compression level, long-distance
+                // matching flag, chunk sizes, and dictionary hash. Only the
+                // dictionary is needed to decompress chunks, the rest are
+                // recorded for reference
+
+                anyhow::ensure!(
+                    entries.is_empty() && data.is_none(),
+                    "unexpected compression entry while reading artifact archive"
+                );
+
+                let _level = reader.read_i32().await?;
+
+                let mut long_distance_matching_tag = [0; 2];
+                reader.read_exact(&mut long_distance_matching_tag).await?;
+                anyhow::ensure!(
+                    matches!(&long_distance_matching_tag, b"l+" | b"l-"),
+                    "invalid long-distance matching flag while reading compression entry"
+                );
+
+                let min_chunk_size = reader.read_u32().await?;
+                let avg_chunk_size = reader.read_u32().await?;
+                let max_chunk_size = reader.read_u32().await?;
+                anyhow::ensure!(
+                    min_chunk_size <= avg_chunk_size && avg_chunk_size <= max_chunk_size,
+                    "invalid chunk sizes while reading compression entry"
+                );
+
+                let mut dictionary_tag = [0; 1];
+                reader.read_exact(&mut dictionary_tag).await?;
+                match &dictionary_tag {
+                    b"+" => {
+                        let mut dictionary_hash = [0; blake3::OUT_LEN];
+                        reader.read_exact(&mut dictionary_hash).await?;
+                        dictionary = Some(blake3::Hash::from_bytes(dictionary_hash));
+                    }
+                    b"-" => {}
+                    _ => {
+                        anyhow::bail!("invalid dictionary flag while reading compression entry");
+                    }
+                }
+            }
             b"f" => {
                 // File tag: read the path, executable bit, and blob hash
 
@@ -511,6 +979,7 @@ pub async fn read_artifact_archive(
                     ChunkEntry {
                         hash: chunk_hash,
                         artifact_range: chunk_offset..chunk_end_offset,
+                        dictionary,
                     },
                 );
 
@@ -537,250 +1006,202 @@ pub async fn read_artifact_archive(
         "artifact archive does not have any entries"
     );
 
-    // Determine which blobs we need to read from the archive. We skip over
-    // any blobs that we already have locally
-    let (needed_blobs, empty_blobs) = tokio::task::spawn_blocking({
-        let brioche = brioche.clone();
-        move || {
-            let mut needed_blobs = vec![];
-            let mut empty_blobs = vec![];
-            for (blob_hash, range) in blobs {
-                let blob_path = crate::blob::local_blob_path(&brioche, blob_hash);
-                if !blob_path.try_exists()? {
-                    if range.is_empty() {
-                        // Blob doesn't exist locally but is empty. We'll
-                        // create it separately from the rest to avoid
-                        // special-case rules around ranges
-                        empty_blobs.push(blob_hash);
-                    } else {
-                        // Blob doesn't exist locally, so add it to the list
-                        // we need from the archive
-                        needed_blobs.push((blob_hash, range));
-                    }
-                }
-            }
-
-            // Make sure the blob is sorted based on the order it appears
-            // in the archive, since we can't rewind the reader
-            needed_blobs.sort_by_key(|(_, range)| range.start);
-
-            anyhow::Ok((needed_blobs, empty_blobs))
-        }
+    Ok(ArchiveIndex {
+        entries,
+        blobs,
+        data,
     })
-    .await??;
-
-    let total_needed_bytes = needed_blobs
-        .iter()
-        .map(|(_, range)| range.end.saturating_sub(range.start))
-        .sum::<u64>();
-    let total_needed_blobs: u64 = needed_blobs.len().try_into()?;
-    brioche.reporter.update_job(
-        job_id,
-        UpdateJob::CacheFetchUpdate {
-            downloaded_data: None,
-            total_data: Some(total_needed_bytes),
-            downloaded_blobs: None,
-            total_blobs: Some(total_needed_blobs),
-        },
-    );
-
-    // If the archive contained a hash for an empty blob, create it separately
-    // since we don't need to read the archive to create it. Normally, this
-    // loop will run at most once, but could run multiple times if a malformed
-    // archive has different hashes for the empty blob
-    for blob_hash in empty_blobs {
-        let mut permit = crate::blob::get_save_blob_permit().await?;
-
-        // Create the empty blob and validate the hash
-        crate::blob::save_blob(
-            brioche,
-            &mut permit,
-            &[],
-            SaveBlobOptions::new().expected_blob_hash(Some(blob_hash)),
-        )
-        .await?;
-    }
-
-    match data {
-        DataEntry::Inline => {
-            // The archive data is inline in the archive, so we're reading
-            // from the same reader right at the end of the archive
-
-            let mut permit = crate::blob::get_save_blob_permit().await?;
-
-            let mut inline_offset = 0;
-            let mut buffer = vec![];
-            for (blob_hash, range) in needed_blobs {
-                // Advance the reader to the start of the next needed blob
-                let Some(skip_length) = range.start.checked_sub(inline_offset) else {
-                    unreachable!("needed blobs are out of order");
-                };
-                reader_consume_exact(reader, skip_length).await?;
-
-                let length = range.end.checked_sub(range.start);
-                let Some(length) = length else {
-                    unreachable!("needed blob range is invalid");
-                };
-
-                // Read the blob
-                let blob_reader = (&mut reader).take(length);
-                crate::blob::save_blob_from_reader(
-                    brioche,
-                    &mut permit,
-                    blob_reader,
-                    SaveBlobOptions::new().expected_blob_hash(Some(blob_hash)),
-                    &mut buffer,
-                )
-                .await?;
-
-                brioche.reporter.update_job(
-                    job_id,
-                    UpdateJob::CacheFetchAdd {
-                        downloaded_data: Some(length),
-                        downloaded_blobs: Some(1),
-                    },
-                );
-
-                inline_offset = range.end;
-            }
-        }
-        DataEntry::Chunks { chunks } => {
-            // The archive data is broken up into chunks, so we need to
-            // determine which parts of which chunks we need to read
-
-            // For each blob, determine if we can read it by reading from
-            // a single chunk or if we need to read it from multiple chunks.
-            // We also group blobs that can all be read from the same chunk.
-            let mut single_chunks = HashMap::<_, Vec<_>>::new();
-            let mut multi_chunks = HashMap::new();
-
-            for (blob_hash, blob_range) in needed_blobs {
-                // Get the chunk containing the first byte of the blob
-                let head_chunk = chunks.range(..=blob_range.start).next_back();
-                let Some((_, head_chunk)) = head_chunk else {
-                    anyhow::bail!("no chunk found containing data range for blob {blob_hash}");
-                };
-                let head_chunk = head_chunk.clone();
-
-                // Get any extra the chunks needed to read the rest of the
-                // blob. This excludes the head chunk that we already found
-                let rest_chunks = if blob_range.end > blob_range.start {
-                    chunks
-                        .range((blob_range.start + 1)..blob_range.end)
-                        .map(|(_, chunk)| chunk.clone())
-                        .collect::<Vec<_>>()
-                } else {
-                    vec![]
-                };
-
-                if rest_chunks.is_empty() {
-                    // This blob comes entirely from one chunk, so add it
-                    // to the list of blobs that only need this single chunk
-
-                    let chunk_blobs = single_chunks.entry(head_chunk).or_default();
-                    chunk_blobs.push((blob_hash, blob_range));
-                } else {
-                    // The blob comes from multiple chunks, so add all the
-                    // chunks to a list for this blob
+}
 
-                    let chunks = [head_chunk]
-                        .into_iter()
-                        .chain(rest_chunks)
-                        .map(|chunk| {
-                            // Get the range of bytes needed from this chunk
-                            // for this blob. Indices are relative to all
-                            // of the artifact's data
+/// Read the chunks an artifact archive is made from. Only reads the
+/// start of the archive.
+pub(super) async fn read_artifact_archive_chunks(
+    reader: &mut (impl tokio::io::AsyncRead + Unpin),
+) -> anyhow::Result<Vec<ChunkEntry>> {
+    let ArchiveIndex { data, .. } = read_archive_index(reader).await?;
+    let chunks = match data {
+        DataEntry::Inline => vec![],
+        DataEntry::Chunks { chunks } => chunks.into_values().collect(),
+    };
+    Ok(chunks)
+}
 
-                            let start_range =
-                                std::cmp::max(chunk.artifact_range.start, blob_range.start);
-                            let end_range = std::cmp::min(chunk.artifact_range.end, blob_range.end);
+/// Determine which chunks contain the data for a blob. Returns each chunk
+/// along with the range of the blob's data from that chunk. Ranges are
+/// relative to all of the artifact's data.
+fn blob_chunks(
+    chunks: &BTreeMap<u64, ChunkEntry>,
+    blob_hash: BlobHash,
+    blob_range: &Range<u64>,
+) -> anyhow::Result<Vec<(ChunkEntry, Range<u64>)>> {
+    // Get the chunk containing the first byte of the blob
+    let head_chunk = chunks.range(..=blob_range.start).next_back();
+    let Some((_, head_chunk)) = head_chunk else {
+        anyhow::bail!("no chunk found containing data range for blob {blob_hash}");
+    };
 
-                            (chunk, start_range..end_range)
-                        })
-                        .collect::<Vec<_>>();
-                    multi_chunks.insert(blob_hash, chunks);
-                }
-            }
+    // Get any extra the chunks needed to read the rest of the
+    // blob. This excludes the head chunk that we already found
+    let rest_chunks = if blob_range.end > blob_range.start {
+        chunks
+            .range((blob_range.start + 1)..blob_range.end)
+            .map(|(_, chunk)| chunk)
+            .collect::<Vec<_>>()
+    } else {
+        vec![]
+    };
 
-            // Build a list of things to fetch from the blobs and chunks
-            let fetches =
-                single_chunks
-                    .into_iter()
-                    .map(|(chunk, blobs)| BlobsFetch::BlobsFromChunk { chunk, blobs })
-                    .chain(multi_chunks.into_iter().map(|(blob_hash, chunks)| {
-                        BlobsFetch::BlobFromChunks { blob_hash, chunks }
-                    }));
+    let blob_chunks = [head_chunk]
+        .into_iter()
+        .chain(rest_chunks)
+        .map(|chunk| {
+            // Get the range of bytes needed from this chunk for this blob
+            let start_range = std::cmp::max(chunk.artifact_range.start, blob_range.start);
+            let end_range = std::cmp::min(chunk.artifact_range.end, blob_range.end);
+
+            (chunk.clone(), start_range..end_range)
+        })
+        .collect();
+    Ok(blob_chunks)
+}
 
-            // Fetch all the blobs from the chunks concurrently
-            let concurrent_chunk_fetches = brioche
-                .cache_client
-                .max_concurrent_chunk_fetches
-                .unwrap_or(100);
-            futures::stream::iter(fetches)
-                .map(Ok)
-                .try_for_each_concurrent(concurrent_chunk_fetches, |fetch| {
-                    fetch_blobs_from_chunks(brioche.clone(), store.clone(), job_id, fetch)
-                })
-                .await?;
+/// Build the list of fetches needed to get each blob from its chunks.
+/// Blobs that can be read from a single chunk get grouped together, so
+/// each chunk only gets read once.
+fn group_blob_fetches(
+    blob_chunks: impl IntoIterator<Item = (BlobHash, Vec<(ChunkEntry, Range<u64>)>)>,
+) -> Vec<BlobsFetch> {
+    let mut single_chunks = HashMap::<_, Vec<_>>::new();
+    let mut fetches = vec![];
+
+    for (blob_hash, mut chunks) in blob_chunks {
+        if chunks.len() == 1 {
+            // This blob comes entirely from one chunk, so add it
+            // to the list of blobs that only need this single chunk
+            let (chunk, range) = chunks.remove(0);
+            single_chunks
+                .entry(chunk)
+                .or_default()
+                .push((blob_hash, range));
+        } else {
+            fetches.push(BlobsFetch::BlobFromChunks { blob_hash, chunks });
         }
     }
 
-    // Now that we have all the blobs, build the final artifact
+    fetches.extend(single_chunks.into_iter().map(|(chunk, mut blobs)| {
+        // Blobs need to be read in the order they appear in the chunk,
+        // since we can't rewind the reader
+        blobs.sort_by_key(|(_, range)| range.start);
+        BlobsFetch::BlobsFromChunk { chunk, blobs }
+    }));
 
-    let mut result: Option<ArtifactBuilder> = None;
-    for entry in entries {
-        insert_into_artifact(&mut result, &entry.path, &entry.path.components, entry.node)?;
-    }
+    fetches
+}
 
-    let mut new_recipes = HashMap::new();
-    let result = result.context("no artifact entries in archive")?;
-    let result = result.build(&mut new_recipes);
+/// Build the list of fetches needed to get blobs that were skipped when
+/// reading an artifact archive lazily.
+pub(super) fn lazy_blob_fetches(
+    lazy_blobs: impl IntoIterator<Item = (BlobHash, Vec<LazyBlobPart>)>,
+) -> Vec<BlobsFetch> {
+    let blob_chunks = lazy_blobs.into_iter().map(|(blob_hash, parts)| {
+        let chunks = parts
+            .into_iter()
+            .map(|part| {
+                let chunk = ChunkEntry {
+                    hash: part.chunk_hash,
+                    artifact_range: part.chunk_range,
+                    dictionary: part.dictionary,
+                };
+                (chunk, part.range)
+            })
+            .collect();
+        (blob_hash, chunks)
+    });
+    group_blob_fetches(blob_chunks)
+}
 
-    brioche.reporter.update_job(
+pub(super) async fn fetch_blobs_from_chunks(
+    brioche: Brioche,
+    store: Arc<dyn object_store::ObjectStore>,
+    chunk_cache: Option<Arc<ChunkCache>>,
+    permit: &mut crate::blob::SaveBlobPermit<'_>,
+    job_id: JobId,
+    fetch: BlobsFetch,
+) -> anyhow::Result<()> {
+    let cached_chunks = Arc::new(std::sync::Mutex::new(vec![]));
+    let result = fetch_blobs_from_chunks_once(
+        brioche.clone(),
+        store.clone(),
+        chunk_cache.clone(),
+        cached_chunks.clone(),
+        permit,
         job_id,
-        UpdateJob::CacheFetchUpdate {
-            downloaded_data: Some(total_needed_bytes),
-            total_data: Some(total_needed_bytes),
-            downloaded_blobs: Some(total_needed_blobs),
-            total_blobs: Some(total_needed_blobs),
-        },
-    );
+        fetch.clone(),
+    )
+    .await;
+    let error = match result {
+        Ok(()) => return Ok(()),
+        Err(error) => error,
+    };
 
-    crate::recipe::save_recipes(brioche, new_recipes.values()).await?;
+    let cached_chunks = std::mem::take(
+        &mut *cached_chunks
+            .lock()
+            .map_err(|_| anyhow::anyhow!("failed to acquire cached chunks lock"))?,
+    );
+    let Some(chunk_cache) = chunk_cache.filter(|_| !cached_chunks.is_empty()) else {
+        return Err(error);
+    };
 
-    brioche.reporter.update_job(
-        job_id,
-        UpdateJob::CacheFetchFinish {
-            finished_at: std::time::Instant::now(),
-        },
+    // A chunk from the local chunk cache could have been corrupted on
+    // disk, so evict every chunk we read from the local cache and try
+    // again, which fetches them from the store
+    tracing::warn!(
+        num_cached_chunks = cached_chunks.len(),
+        "failed to fetch blobs using the chunk cache, retrying with chunks from the store: {error:#}"
     );
+    for chunk_filename in &cached_chunks {
+        chunk_cache.remove(chunk_filename).await?;
+    }
 
-    Ok(result)
+    fetch_blobs_from_chunks_once(
+        brioche,
+        store,
+        Some(chunk_cache),
+        Arc::new(std::sync::Mutex::new(vec![])),
+        permit,
+        job_id,
+        fetch,
+    )
+    .await
 }
 
-async fn fetch_blobs_from_chunks(
+/// Fetch blobs from chunks. The filenames of chunks read from the local
+/// chunk cache get added to `cached_chunks`.
+async fn fetch_blobs_from_chunks_once(
     brioche: Brioche,
     store: Arc<dyn object_store::ObjectStore>,
+    chunk_cache: Option<Arc<ChunkCache>>,
+    cached_chunks: Arc<std::sync::Mutex<Vec<String>>>,
+    permit: &mut crate::blob::SaveBlobPermit<'_>,
     job_id: JobId,
     fetch: BlobsFetch,
 ) -> anyhow::Result<()> {
-    let mut permit = crate::blob::get_save_blob_permit().await?;
-
     match fetch {
         BlobsFetch::BlobsFromChunk { chunk, blobs } => {
             // Fetch one or more blobs from a single chunk. Because the
             // chunk is compressed, we have to read from the start
 
             // Get the chunk object from the cache
-            let chunk_compressed_filename = format!("{}.zst", chunk.hash);
-            let chunk_path =
-                object_store::path::Path::from_iter(["chunks", &chunk_compressed_filename]);
-            let chunk_object = store.get(&chunk_path).await?;
-            let chunk_stream_compressed = chunk_object.into_stream();
-            let chunk_reader_compressed =
-                tokio_util::io::StreamReader::new(chunk_stream_compressed);
-            let mut chunk_reader =
-                async_compression::tokio::bufread::ZstdDecoder::new(chunk_reader_compressed);
+            let mut chunk_reader = open_chunk(
+                &brioche.reporter,
+                &store,
+                chunk_cache.as_deref(),
+                &cached_chunks,
+                job_id,
+                &chunk,
+            )
+            .await?;
 
             let mut artifact_offset = chunk.artifact_range.start;
             for (blob_hash, range) in blobs {
@@ -798,7 +1219,7 @@ async fn fetch_blobs_from_chunks(
                 let blob_reader = (&mut chunk_reader).take(length);
                 crate::blob::save_blob_from_reader(
                     &brioche,
-                    &mut permit,
+                    permit,
                     blob_reader,
                     SaveBlobOptions::new().expected_blob_hash(Some(blob_hash)),
                     &mut vec![],
@@ -828,19 +1249,15 @@ async fn fetch_blobs_from_chunks(
                     // Read each part of each chunk from the cache to the
                     // writer, which reassembles the original blob
                     for (chunk, range) in chunks {
-                        let chunk_compressed_filename = format!("{}.zst", chunk.hash);
-                        let chunk_path = object_store::path::Path::from_iter([
-                            "chunks",
-                            &chunk_compressed_filename,
-                        ]);
-
-                        let chunk_object = store.get(&chunk_path).await?;
-                        let chunk_stream_compressed = chunk_object.into_stream();
-                        let chunk_reader_compressed =
-                            tokio_util::io::StreamReader::new(chunk_stream_compressed);
-                        let mut chunk_reader = async_compression::tokio::bufread::ZstdDecoder::new(
-                            chunk_reader_compressed,
-                        );
+                        let mut chunk_reader = open_chunk(
+                            &reporter,
+                            &store,
+                            chunk_cache.as_deref(),
+                            &cached_chunks,
+                            job_id,
+                            &chunk,
+                        )
+                        .await?;
 
                         // Advance the reader to the part of the chunk
                         // needed for the blob
@@ -880,7 +1297,7 @@ async fn fetch_blobs_from_chunks(
             // Save the blob from the combined parts of the chunks
             crate::blob::save_blob_from_reader(
                 &brioche,
-                &mut permit,
+                permit,
                 blob_reader,
                 SaveBlobOptions::new().expected_blob_hash(Some(blob_hash)),
                 &mut vec![],
@@ -902,6 +1319,136 @@ async fn fetch_blobs_from_chunks(
     Ok(())
 }
 
+/// Open a chunk for reading, decompressing it with its dictionary if it
+/// has one. Chunks are read through the local chunk cache when one is
+/// given, and the filenames of chunks that were already cached get added
+/// to `cached_chunks`.
+async fn open_chunk(
+    reporter: &Reporter,
+    store: &Arc<dyn object_store::ObjectStore>,
+    chunk_cache: Option<&ChunkCache>,
+    cached_chunks: &std::sync::Mutex<Vec<String>>,
+    job_id: JobId,
+    chunk: &ChunkEntry,
+) -> anyhow::Result<Box<dyn tokio::io::AsyncRead + Send + Unpin>> {
+    let chunk_compressed_filename = chunk.filename();
+    let chunk_reader_compressed: Box<dyn tokio::io::AsyncBufRead + Send + Unpin> = match chunk_cache
+    {
+        Some(chunk_cache) => {
+            let (chunk_file, is_cached) = chunk_cache
+                .open_or_fetch(store, &chunk_compressed_filename)
+                .await?;
+            reporter.update_job(
+                job_id,
+                UpdateJob::CacheFetchAddChunks {
+                    chunk_cache_hits: u64::from(is_cached),
+                    chunk_cache_misses: u64::from(!is_cached),
+                },
+            );
+            if is_cached {
+                cached_chunks
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("failed to acquire cached chunks lock"))?
+                    .push(chunk_compressed_filename.clone());
+            }
+
+            Box::new(tokio::io::BufReader::new(chunk_file))
+        }
+        None => {
+            let chunk_path =
+                object_store::path::Path::from_iter(["chunks", &chunk_compressed_filename]);
+            let chunk_object = store.get(&chunk_path).await?;
+            let chunk_stream_compressed = chunk_object.into_stream();
+            Box::new(tokio_util::io::StreamReader::new(chunk_stream_compressed))
+        }
+    };
+
+    let chunk_reader = match chunk.dictionary {
+        Some(dictionary_hash) => {
+            let dictionary = load_dictionary(store, dictionary_hash).await?;
+            async_compression::tokio::bufread::ZstdDecoder::with_dict(
+                chunk_reader_compressed,
+                &dictionary,
+            )?
+        }
+        None => async_compression::tokio::bufread::ZstdDecoder::new(chunk_reader_compressed),
+    };
+    Ok(Box::new(chunk_reader))
+}
+
+fn dictionary_path(dictionary_hash: blake3::Hash) -> object_store::path::Path {
+    object_store::path::Path::from_iter(["dictionaries", &format!("{dictionary_hash}.zdict")])
+}
+
+/// Upload a dictionary to the cache, unless it's already there.
+async fn save_dictionary(
+    store: &Arc<dyn object_store::ObjectStore>,
+    dictionary: &CompressionDictionary,
+) -> anyhow::Result<()> {
+    let path = dictionary_path(dictionary.hash);
+    match store.head(&path).await {
+        Ok(_) => return Ok(()),
+        Err(object_store::Error::NotFound { .. }) => {}
+        Err(error) => return Err(error.into()),
+    }
+
+    let result = store
+        .put_opts(
+            &path,
+            dictionary.data.to_vec().into(),
+            object_store::PutOptions {
+                mode: object_store::PutMode::Create,
+                ..Default::default()
+            },
+        )
+        .await;
+    match result {
+        Ok(_) | Err(object_store::Error::AlreadyExists { .. }) => Ok(()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Load a dictionary from the cache. Dictionaries are small and get used
+/// for every chunk compressed with them, so they're kept in memory once
+/// they've been loaded.
+async fn load_dictionary(
+    store: &Arc<dyn object_store::ObjectStore>,
+    dictionary_hash: blake3::Hash,
+) -> anyhow::Result<Arc<[u8]>> {
+    static DICTIONARIES: OnceLock<RwLock<HashMap<blake3::Hash, Arc<[u8]>>>> = OnceLock::new();
+    let dictionaries = DICTIONARIES.get_or_init(|| RwLock::new(HashMap::new()));
+
+    {
+        let dictionaries = dictionaries
+            .read()
+            .map_err(|_| anyhow::anyhow!("failed to acquire dictionaries lock"))?;
+        if let Some(dictionary) = dictionaries.get(&dictionary_hash) {
+            return Ok(dictionary.clone());
+        }
+    }
+
+    let path = dictionary_path(dictionary_hash);
+    let data = store
+        .get(&path)
+        .await
+        .with_context(|| format!("failed to get dictionary {dictionary_hash} from cache"))?
+        .bytes()
+        .await?;
+    let actual_hash = blake3::hash(&data);
+    anyhow::ensure!(
+        actual_hash == dictionary_hash,
+        "dictionary from cache at {path} has hash {actual_hash}, but expected {dictionary_hash}"
+    );
+
+    let dictionary: Arc<[u8]> = data.to_vec().into();
+    let mut dictionaries = dictionaries
+        .write()
+        .map_err(|_| anyhow::anyhow!("failed to acquire dictionaries lock"))?;
+    dictionaries.insert(dictionary_hash, dictionary.clone());
+
+    Ok(dictionary)
+}
+
 fn insert_into_artifact(
     container: &mut Option<ArtifactBuilder>,
     full_path: &ArtifactPath,
@@ -1032,7 +1579,23 @@ impl ArtifactBuilder {
     }
 }
 
-enum BlobsFetch {
+/// Part of a blob that wasn't fetched when reading an artifact archive
+/// lazily. Records which range of the artifact's data holds this part of
+/// the blob, and the chunk it can be read from.
+#[serde_with::serde_as]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LazyBlobPart {
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    pub chunk_hash: blake3::Hash,
+    pub chunk_range: Range<u64>,
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    pub dictionary: Option<blake3::Hash>,
+    pub range: Range<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub(super) enum BlobsFetch {
     BlobsFromChunk {
         chunk: ChunkEntry,
         blobs: Vec<(BlobHash, Range<u64>)>,
@@ -1043,6 +1606,30 @@ enum BlobsFetch {
     },
 }
 
+impl BlobsFetch {
+    pub(super) fn blob_hashes(&self) -> Vec<BlobHash> {
+        match self {
+            Self::BlobsFromChunk { blobs, .. } => {
+                blobs.iter().map(|(blob_hash, _)| *blob_hash).collect()
+            }
+            Self::BlobFromChunks { blob_hash, .. } => vec![*blob_hash],
+        }
+    }
+
+    pub(super) fn length(&self) -> u64 {
+        match self {
+            Self::BlobsFromChunk { blobs, .. } => blobs
+                .iter()
+                .map(|(_, range)| range.end.saturating_sub(range.start))
+                .sum(),
+            Self::BlobFromChunks { chunks, .. } => chunks
+                .iter()
+                .map(|(_, range)| range.end.saturating_sub(range.start))
+                .sum(),
+        }
+    }
+}
+
 async fn read_path(
     reader: &mut (impl tokio::io::AsyncRead + Unpin),
 ) -> anyhow::Result<ArtifactPath> {
@@ -0,0 +1,242 @@
+//! A local cache of compressed chunks fetched from remote caches, so
+//! artifacts that share chunks don't need to download them again.
+//!
+//! Each chunk is stored as its own file in the data directory, which lets
+//! every Brioche process using the same data directory share the cache.
+//! A chunk's modified time records when it was last used, and the least
+//! recently used chunks get evicted once the cache grows past its
+//! maximum size.
+
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use anyhow::Context as _;
+use futures::TryStreamExt as _;
+use tokio::io::{AsyncSeekExt as _, AsyncWriteExt as _};
+
+const STALE_TEMP_FILE_AGE: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+#[derive(Debug)]
+pub struct ChunkCache {
+    dir: PathBuf,
+    max_size: u64,
+
+    /// Bytes added since the last eviction pass. Evicting requires listing
+    /// the whole cache, so we only do it after enough new data has been
+    /// added.
+    added_since_evict: AtomicU64,
+    evict_lock: tokio::sync::Mutex<()>,
+
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl ChunkCache {
+    pub fn new(dir: PathBuf, max_size: u64) -> Self {
+        Self {
+            dir,
+            max_size,
+            added_since_evict: AtomicU64::new(0),
+            evict_lock: tokio::sync::Mutex::new(()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The number of chunk hits and misses seen by this process.
+    pub fn stats(&self) -> ChunkCacheStats {
+        ChunkCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Open a compressed chunk from the local cache, or fetch it from
+    /// `store` and save it to the local cache first. `chunk_filename` is
+    /// the chunk's filename within the cache's `chunks/` directory. Returns
+    /// the chunk file along with whether it was already cached.
+    pub async fn open_or_fetch(
+        &self,
+        store: &Arc<dyn object_store::ObjectStore>,
+        chunk_filename: &str,
+    ) -> anyhow::Result<(tokio::fs::File, bool)> {
+        let chunk_path = self.dir.join(chunk_filename);
+
+        if let Some(file) = open_and_touch(chunk_path.clone()).await? {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok((file, true));
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("failed to create directory {}", self.dir.display()))?;
+
+        // Write to a temporary file first, so other processes never see a
+        // partially-written chunk
+        let temp_path = self
+            .dir
+            .join(format!("{chunk_filename}.{}.tmp", ulid::Ulid::new()));
+        let mut file = tokio::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+            .await
+            .with_context(|| format!("failed to create {}", temp_path.display()))?;
+
+        let result = async {
+            let chunk_object_path = object_store::path::Path::from_iter(["chunks", chunk_filename]);
+            let mut chunk_stream = store.get(&chunk_object_path).await?.into_stream();
+            let mut chunk_length = 0;
+            while let Some(bytes) = chunk_stream.try_next().await? {
+                file.write_all(&bytes).await?;
+                let bytes_length: u64 = bytes.len().try_into()?;
+                chunk_length += bytes_length;
+            }
+            file.flush().await?;
+
+            tokio::fs::rename(&temp_path, &chunk_path).await?;
+            anyhow::Ok(chunk_length)
+        }
+        .await;
+        let chunk_length = match result {
+            Ok(chunk_length) => chunk_length,
+            Err(error) => {
+                let _ = tokio::fs::remove_file(&temp_path).await;
+                return Err(error);
+            }
+        };
+
+        // Read from the handle we already have, since another process
+        // could evict the chunk as soon as it's been renamed
+        file.seek(std::io::SeekFrom::Start(0)).await?;
+
+        let added = self
+            .added_since_evict
+            .fetch_add(chunk_length, Ordering::Relaxed)
+            + chunk_length;
+        if added >= self.max_size / 10 {
+            let result = self.evict().await;
+            if let Err(error) = result {
+                tracing::warn!("failed to evict chunks from chunk cache: {error:#}");
+            }
+        }
+
+        Ok((file, false))
+    }
+
+    /// Remove a chunk from the local cache, so the next call to
+    /// [`Self::open_or_fetch`] fetches it from the store again. Used when a
+    /// cached chunk turns out to be corrupted.
+    pub async fn remove(&self, chunk_filename: &str) -> anyhow::Result<()> {
+        let chunk_path = self.dir.join(chunk_filename);
+        crate::fs_utils::try_remove(&chunk_path).await?;
+        Ok(())
+    }
+
+    /// Remove the least recently used chunks until the cache is under its
+    /// maximum size.
+    pub async fn evict(&self) -> anyhow::Result<()> {
+        // Skip if another task is already evicting
+        let Ok(_guard) = self.evict_lock.try_lock() else {
+            return Ok(());
+        };
+        self.added_since_evict.store(0, Ordering::Relaxed);
+
+        let mut entries = vec![];
+        let mut total_size = 0;
+        let mut dir = match tokio::fs::read_dir(&self.dir).await {
+            Ok(dir) => dir,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(());
+            }
+            Err(error) => {
+                return Err(error).with_context(|| {
+                    format!("failed to read chunk cache at {}", self.dir.display())
+                });
+            }
+        };
+        while let Some(entry) = dir.next_entry().await? {
+            let file_name = entry.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+
+            let metadata = match entry.metadata().await {
+                Ok(metadata) => metadata,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(error.into()),
+            };
+            let modified = metadata.modified()?;
+
+            if file_name.ends_with(".tmp") {
+                // Clean up temporary files left behind by processes that
+                // exited while fetching a chunk
+                let age = modified.elapsed().unwrap_or_default();
+                if age > STALE_TEMP_FILE_AGE {
+                    crate::fs_utils::try_remove(&entry.path()).await?;
+                }
+                continue;
+            } else if !file_name.ends_with(".zst") {
+                continue;
+            }
+
+            total_size += metadata.len();
+            entries.push((modified, metadata.len(), entry.path()));
+        }
+
+        if total_size <= self.max_size {
+            return Ok(());
+        }
+
+        entries.sort_by_key(|(modified, _, _)| *modified);
+        for (_, size, path) in entries {
+            if total_size <= self.max_size {
+                break;
+            }
+
+            crate::fs_utils::try_remove(&path).await?;
+            total_size = total_size.saturating_sub(size);
+        }
+
+        Ok(())
+    }
+}
+
+/// Open a cached chunk and mark it as recently used. Returns `None` if
+/// the chunk isn't cached.
+async fn open_and_touch(path: PathBuf) -> anyhow::Result<Option<tokio::fs::File>> {
+    let file = tokio::task::spawn_blocking(move || {
+        let file = match std::fs::File::open(&path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(None);
+            }
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to open {}", path.display()));
+            }
+        };
+
+        // Not being able to update the modified time only affects the
+        // eviction order, so ignore errors
+        let _ = file.set_modified(std::time::SystemTime::now());
+
+        anyhow::Ok(Some(file))
+    })
+    .await??;
+
+    Ok(file.map(tokio::fs::File::from_std))
+}
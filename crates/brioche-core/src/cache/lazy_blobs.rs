@@ -0,0 +1,273 @@
+//! Blobs from artifacts that were loaded from the cache lazily.
+//!
+//! Usually only a few files from a large artifact are ever used (e.g. a
+//! single tool out of a whole toolchain), so when an artifact gets loaded
+//! lazily, we only record where each blob's data lives in the cache's
+//! chunks. Each blob then gets fetched the first time its contents are
+//! needed, which happens through [`crate::blob::blob_path`].
+
+use futures::{StreamExt as _, TryStreamExt as _};
+use joinery::JoinableIterator as _;
+use sqlx::{Acquire as _, Arguments as _};
+
+use crate::{
+    Brioche,
+    blob::{BlobHash, SaveBlobPermit},
+    reporter::{
+        JobId,
+        job::{CacheFetchKind, NewJob, UpdateJob},
+    },
+};
+
+use super::archive::{BlobsFetch, LazyBlobPart};
+
+pub(super) async fn save_lazy_blobs(
+    brioche: &Brioche,
+    lazy_blobs: &[(BlobHash, Vec<LazyBlobPart>)],
+) -> anyhow::Result<()> {
+    if lazy_blobs.is_empty() {
+        return Ok(());
+    }
+
+    let mut db_conn = brioche.db_conn.lock().await;
+    let mut db_transaction = db_conn.begin().await?;
+
+    // Save in batches, so we don't hit the maximum number of variables
+    // per query
+    for batch in lazy_blobs.chunks(400) {
+        let mut arguments = sqlx::sqlite::SqliteArguments::default();
+        for (blob_hash, parts) in batch {
+            arguments
+                .add(blob_hash.to_string())
+                .map_err(|error| anyhow::anyhow!(error))?;
+            arguments
+                .add(serde_json::to_string(parts)?)
+                .map_err(|error| anyhow::anyhow!(error))?;
+        }
+
+        let placeholders = std::iter::repeat("(?, ?)")
+            .take(batch.len())
+            .join_with(", ");
+
+        sqlx::query_with(
+            &format!(
+                r#"
+                    INSERT INTO lazy_blobs (blob_hash, parts_json)
+                    VALUES {placeholders}
+                    ON CONFLICT (blob_hash) DO UPDATE SET
+                        parts_json = excluded.parts_json
+                "#
+            ),
+            arguments,
+        )
+        .execute(&mut *db_transaction)
+        .await?;
+    }
+
+    db_transaction.commit().await?;
+    drop(db_conn);
+
+    Ok(())
+}
+
+/// Fetch a blob that was skipped when loading an artifact from the cache
+/// lazily. Returns `false` if the blob didn't come from a lazily-loaded
+/// artifact.
+pub async fn fetch_lazy_blob(
+    brioche: &Brioche,
+    permit: &mut SaveBlobPermit<'_>,
+    blob_hash: BlobHash,
+) -> anyhow::Result<bool> {
+    let lazy_blobs = load_lazy_blobs(brioche, [blob_hash]).await?;
+    if lazy_blobs.is_empty() {
+        return Ok(false);
+    }
+
+    let fetches = super::archive::lazy_blob_fetches(lazy_blobs);
+    let job_id = add_fetch_job(brioche, &fetches)?;
+    for fetch in fetches {
+        fetch_from_caches(brioche, permit, job_id, fetch).await?;
+    }
+    finish_fetch_job(brioche, job_id);
+
+    Ok(true)
+}
+
+/// Fetch any of the given blobs that were skipped when loading artifacts
+/// from the cache lazily. Blobs that didn't come from a lazily-loaded
+/// artifact are ignored.
+pub async fn fetch_lazy_blobs(
+    brioche: &Brioche,
+    blob_hashes: impl IntoIterator<Item = BlobHash>,
+) -> anyhow::Result<()> {
+    let lazy_blobs = load_lazy_blobs(brioche, blob_hashes).await?;
+    if lazy_blobs.is_empty() {
+        return Ok(());
+    }
+
+    let fetches = super::archive::lazy_blob_fetches(lazy_blobs);
+    let job_id = add_fetch_job(brioche, &fetches)?;
+
+    let concurrent_chunk_fetches = brioche
+        .cache_client
+        .max_concurrent_chunk_fetches
+        .unwrap_or(100);
+    futures::stream::iter(fetches)
+        .map(Ok)
+        .try_for_each_concurrent(concurrent_chunk_fetches, |fetch| async move {
+            let mut permit = crate::blob::get_save_blob_permit().await?;
+            fetch_from_caches(brioche, &mut permit, job_id, fetch).await
+        })
+        .await?;
+
+    finish_fetch_job(brioche, job_id);
+
+    Ok(())
+}
+
+/// Fetch blobs from their chunks, trying each cache in turn. Chunks are
+/// shared between artifacts, so any cache could have them, not just the
+/// one the artifact was loaded from.
+async fn fetch_from_caches(
+    brioche: &Brioche,
+    permit: &mut SaveBlobPermit<'_>,
+    job_id: JobId,
+    fetch: BlobsFetch,
+) -> anyhow::Result<()> {
+    let mut last_error = None;
+    let mut skipped_remote = false;
+    for layer in &brioche.cache_client.layers {
+        if layer.remote && brioche.offline {
+            skipped_remote = true;
+            continue;
+        }
+
+        let chunk_cache = if layer.remote {
+            brioche.chunk_cache.clone()
+        } else {
+            None
+        };
+        let result = super::archive::fetch_blobs_from_chunks(
+            brioche.clone(),
+            layer.store.clone(),
+            chunk_cache,
+            permit,
+            job_id,
+            fetch.clone(),
+        )
+        .await;
+        match result {
+            Ok(()) => {
+                remove_lazy_blobs(brioche, &fetch.blob_hashes()).await?;
+                return Ok(());
+            }
+            Err(error) => {
+                tracing::debug!("failed to fetch blobs from cache: {error:#}");
+                last_error = Some(error);
+            }
+        }
+    }
+
+    if skipped_remote {
+        crate::network::ensure_online(brioche, || {
+            let blob_hashes = fetch.blob_hashes();
+            format!("blobs {} from cache", blob_hashes.iter().join_with(", "))
+        })?;
+    }
+
+    match last_error {
+        Some(error) => Err(error),
+        None => anyhow::bail!("tried to fetch blobs from cache, but no cache is configured"),
+    }
+}
+
+/// Get the size of a blob that was loaded from the cache lazily, without
+/// fetching it. Returns `None` if the blob isn't waiting to be fetched.
+pub async fn lazy_blob_size(brioche: &Brioche, blob_hash: BlobHash) -> anyhow::Result<Option<u64>> {
+    let lazy_blobs = load_lazy_blobs(brioche, [blob_hash]).await?;
+    let size = lazy_blobs.into_iter().next().map(|(_, parts)| {
+        parts
+            .iter()
+            .map(|part| part.range.end.saturating_sub(part.range.start))
+            .sum()
+    });
+    Ok(size)
+}
+
+async fn load_lazy_blobs(
+    brioche: &Brioche,
+    blob_hashes: impl IntoIterator<Item = BlobHash>,
+) -> anyhow::Result<Vec<(BlobHash, Vec<LazyBlobPart>)>> {
+    let mut lazy_blobs = vec![];
+
+    let mut db_conn = brioche.db_conn.lock().await;
+    let mut db_transaction = db_conn.begin().await?;
+    for blob_hash in blob_hashes {
+        let blob_hash_value = blob_hash.to_string();
+        let result = sqlx::query!(
+            r#"
+                SELECT parts_json FROM lazy_blobs WHERE blob_hash = ?
+            "#,
+            blob_hash_value,
+        )
+        .fetch_optional(&mut *db_transaction)
+        .await?;
+
+        if let Some(row) = result {
+            let parts = serde_json::from_str(&row.parts_json)?;
+            lazy_blobs.push((blob_hash, parts));
+        }
+    }
+    db_transaction.commit().await?;
+    drop(db_conn);
+
+    Ok(lazy_blobs)
+}
+
+async fn remove_lazy_blobs(brioche: &Brioche, blob_hashes: &[BlobHash]) -> anyhow::Result<()> {
+    let mut db_conn = brioche.db_conn.lock().await;
+    let mut db_transaction = db_conn.begin().await?;
+    for blob_hash in blob_hashes {
+        let blob_hash_value = blob_hash.to_string();
+        sqlx::query!(
+            r#"
+                DELETE FROM lazy_blobs WHERE blob_hash = ?
+            "#,
+            blob_hash_value,
+        )
+        .execute(&mut *db_transaction)
+        .await?;
+    }
+    db_transaction.commit().await?;
+    drop(db_conn);
+
+    Ok(())
+}
+
+fn add_fetch_job(brioche: &Brioche, fetches: &[BlobsFetch]) -> anyhow::Result<JobId> {
+    let total_data = fetches.iter().map(BlobsFetch::length).sum();
+    let total_blobs = fetches
+        .iter()
+        .map(|fetch| fetch.blob_hashes().len())
+        .sum::<usize>()
+        .try_into()?;
+
+    let job_id = brioche.reporter.add_job(NewJob::CacheFetch {
+        kind: CacheFetchKind::Bake,
+        downloaded_data: None,
+        total_data: Some(total_data),
+        downloaded_blobs: None,
+        total_blobs: Some(total_blobs),
+        started_at: std::time::Instant::now(),
+    });
+    Ok(job_id)
+}
+
+fn finish_fetch_job(brioche: &Brioche, job_id: JobId) {
+    brioche.reporter.update_job(
+        job_id,
+        UpdateJob::CacheFetchFinish {
+            finished_at: std::time::Instant::now(),
+        },
+    );
+}
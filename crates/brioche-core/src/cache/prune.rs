@@ -0,0 +1,400 @@
+//! Remove objects from a cache that are no longer needed.
+//!
+//! Bakes and projects are kept based on retention rules. Everything they
+//! reference is kept too: the artifact each one points to, the chunks
+//! each kept artifact is made from, and the dictionaries those chunks are
+//! compressed with. Anything left over gets deleted.
+
+use std::collections::{HashMap, HashSet};
+
+use futures::{StreamExt as _, TryStreamExt as _};
+use sqlx::Acquire as _;
+
+use crate::{Brioche, project::ProjectHash, recipe::RecipeHash};
+
+use super::{CachedBakeOutput, CachedProjectSource};
+
+/// By default, objects newer than this are never deleted. Artifacts get
+/// written before the bakes or projects that point to them, so this avoids
+/// deleting an artifact that's still being saved.
+const DEFAULT_MIN_OBJECT_AGE: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+const MAX_CONCURRENT_READS: usize = 100;
+
+#[derive(Debug, Clone, Default)]
+pub struct PruneOptions {
+    /// Keep bakes and projects written within this duration.
+    pub max_age: Option<std::time::Duration>,
+
+    /// Keep the most recently written projects, plus every bake reachable
+    /// from them. Reachable bakes are found from the local database, so
+    /// this only knows about projects baked on this machine.
+    pub keep_projects: Option<usize>,
+
+    /// Never delete objects written within this duration, regardless of
+    /// the other rules. Defaults to one hour.
+    pub min_age: Option<std::time::Duration>,
+
+    /// Find objects to delete without deleting them.
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PruneResults {
+    pub kept_objects: u64,
+    pub deleted_bakes: u64,
+    pub deleted_projects: u64,
+    pub deleted_artifacts: u64,
+    pub deleted_chunks: u64,
+    pub deleted_dictionaries: u64,
+    pub deleted_bytes: u64,
+
+    /// Projects kept by `keep_projects` that have no bakes recorded in the
+    /// local database, so none of their bakes were kept.
+    pub projects_without_bakes: u64,
+}
+
+impl PruneResults {
+    pub fn deleted_objects(&self) -> u64 {
+        self.deleted_bakes
+            + self.deleted_projects
+            + self.deleted_artifacts
+            + self.deleted_chunks
+            + self.deleted_dictionaries
+    }
+}
+
+/// Delete objects from the writable cache that aren't kept by any of the
+/// retention rules. Deletes are limited by the cache's
+/// `max_concurrent_operations`.
+pub async fn prune(brioche: &Brioche, options: &PruneOptions) -> anyhow::Result<PruneResults> {
+    anyhow::ensure!(
+        options.max_age.is_some() || options.keep_projects.is_some(),
+        "no retention rules given, so every bake and project would be deleted"
+    );
+
+    let min_age = Some(options.min_age.unwrap_or(DEFAULT_MIN_OBJECT_AGE));
+
+    let layer = super::writable_layer(brioche, || "prune cache".to_string())?;
+    let store = &layer.store;
+
+    // Find which projects to keep
+    let projects = list_objects(store, "projects").await?;
+    let recent_projects = match options.keep_projects {
+        Some(keep_projects) => {
+            let mut projects = projects.iter().collect::<Vec<_>>();
+            projects.sort_by_key(|(_, meta)| std::cmp::Reverse(meta.last_modified));
+            projects
+                .into_iter()
+                .take(keep_projects)
+                .map(|(key, _)| key.clone())
+                .collect()
+        }
+        None => HashSet::new(),
+    };
+    let kept_projects = projects
+        .iter()
+        .filter(|(key, meta)| {
+            recent_projects.contains(*key)
+                || is_newer_than(meta, options.max_age)
+                || is_newer_than(meta, min_age)
+        })
+        .map(|(key, _)| key.clone())
+        .collect::<HashSet<_>>();
+
+    // Find which bakes to keep: recent bakes, and bakes reachable from
+    // recent projects
+    let mut reachable_bakes = HashSet::new();
+    let mut projects_without_bakes = 0;
+    for project_hash in &recent_projects {
+        let Ok(project_hash) = project_hash.parse::<ProjectHash>() else {
+            continue;
+        };
+
+        let bakes = project_bakes(brioche, project_hash).await?;
+        if bakes.is_empty() {
+            // Either the project wasn't baked on this machine or it has
+            // nothing to bake (e.g. a library). Either way, its bakes can
+            // only be kept by the other rules
+            tracing::warn!(
+                %project_hash,
+                "kept project has no bakes in the local database, so its bakes won't be kept"
+            );
+            projects_without_bakes += 1;
+        }
+        reachable_bakes.extend(bakes);
+    }
+
+    let bakes = list_objects(store, "bakes").await?;
+    let kept_bakes = bakes
+        .iter()
+        .filter(|(key, meta)| {
+            reachable_bakes.contains(*key)
+                || is_newer_than(meta, options.max_age)
+                || is_newer_than(meta, min_age)
+        })
+        .map(|(key, _)| key.clone())
+        .collect::<HashSet<_>>();
+
+    // Find the artifacts the kept bakes and projects point to
+    let bake_artifacts = read_json_objects::<CachedBakeOutput>(
+        store,
+        kept_bakes.iter().map(|key| &bakes[key].location),
+    )
+    .await?
+    .into_iter()
+    .map(|bake| bake.output_hash);
+    let project_artifacts = read_json_objects::<CachedProjectSource>(
+        store,
+        kept_projects.iter().map(|key| &projects[key].location),
+    )
+    .await?
+    .into_iter()
+    .map(|project| project.artifact_hash);
+    let referenced_artifacts = bake_artifacts
+        .chain(project_artifacts)
+        .map(artifact_key)
+        .collect::<HashSet<_>>();
+
+    let artifacts = list_objects(store, "artifacts").await?;
+    let kept_artifacts = artifacts
+        .iter()
+        .filter(|(key, meta)| referenced_artifacts.contains(*key) || is_newer_than(meta, min_age))
+        .map(|(key, _)| key.clone())
+        .collect::<HashSet<_>>();
+
+    // Find the chunks the kept artifacts are made from, and the
+    // dictionaries those chunks are compressed with
+    let kept_artifact_chunks = futures::stream::iter(&kept_artifacts)
+        .map(|key| read_artifact_chunks(store, &artifacts[key].location))
+        .buffer_unordered(MAX_CONCURRENT_READS)
+        .try_collect::<Vec<_>>()
+        .await?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    let referenced_chunks = kept_artifact_chunks
+        .iter()
+        .map(|chunk| chunk.filename())
+        .collect::<HashSet<_>>();
+    let referenced_dictionaries = kept_artifact_chunks
+        .iter()
+        .filter_map(|chunk| chunk.dictionary)
+        .map(dictionary_key)
+        .collect::<HashSet<_>>();
+
+    let chunks = list_objects(store, "chunks").await?;
+    let kept_chunks = chunks
+        .iter()
+        .filter(|(key, meta)| referenced_chunks.contains(*key) || is_newer_than(meta, min_age))
+        .map(|(key, _)| key.clone())
+        .collect::<HashSet<_>>();
+
+    let dictionaries = list_objects(store, "dictionaries").await?;
+    let kept_dictionaries = dictionaries
+        .iter()
+        .filter(|(key, meta)| {
+            referenced_dictionaries.contains(*key) || is_newer_than(meta, min_age)
+        })
+        .map(|(key, _)| key.clone())
+        .collect::<HashSet<_>>();
+
+    // Delete in order from bakes and projects down to chunks and
+    // dictionaries, so a bake never points to an artifact that's already
+    // been deleted
+    let mut results = PruneResults {
+        projects_without_bakes,
+        ..Default::default()
+    };
+    let object_kinds = [
+        (&bakes, &kept_bakes, &mut results.deleted_bakes),
+        (&projects, &kept_projects, &mut results.deleted_projects),
+        (&artifacts, &kept_artifacts, &mut results.deleted_artifacts),
+        (&chunks, &kept_chunks, &mut results.deleted_chunks),
+        (
+            &dictionaries,
+            &kept_dictionaries,
+            &mut results.deleted_dictionaries,
+        ),
+    ];
+    for (objects, kept, num_deleted) in object_kinds {
+        let deleted = delete_unkept(store, objects, kept, options.dry_run).await?;
+        *num_deleted = deleted.objects;
+        results.deleted_bytes += deleted.bytes;
+        results.kept_objects += u64::try_from(kept.len())?;
+    }
+
+    Ok(results)
+}
+
+/// List all objects under a prefix in the cache, keyed by the first path
+/// component after the prefix (e.g. the recipe hash for `bakes/`).
+async fn list_objects(
+    store: &std::sync::Arc<dyn object_store::ObjectStore>,
+    prefix: &str,
+) -> anyhow::Result<HashMap<String, object_store::ObjectMeta>> {
+    let prefix_path = object_store::path::Path::from(prefix);
+    let objects = store
+        .list(Some(&prefix_path))
+        .try_filter_map(|meta| async move {
+            let key = object_key(&meta.location, prefix);
+            Ok(key.map(|key| (key, meta)))
+        })
+        .try_collect()
+        .await?;
+    Ok(objects)
+}
+
+fn object_key(path: &object_store::path::Path, prefix: &str) -> Option<String> {
+    let mut parts = path.parts();
+    let first = parts.next()?;
+    if first.as_ref() != prefix {
+        return None;
+    }
+
+    let key = parts.next()?;
+    Some(key.as_ref().to_string())
+}
+
+fn artifact_key(artifact_hash: RecipeHash) -> String {
+    format!("{artifact_hash}.bar.zst")
+}
+
+fn dictionary_key(dictionary_hash: blake3::Hash) -> String {
+    format!("{dictionary_hash}.zdict")
+}
+
+fn is_newer_than(meta: &object_store::ObjectMeta, max_age: Option<std::time::Duration>) -> bool {
+    let Some(max_age) = max_age else {
+        return false;
+    };
+
+    let now = jiff::Timestamp::now().as_second();
+    let age = now.saturating_sub(meta.last_modified.timestamp());
+    let max_age = i64::try_from(max_age.as_secs()).unwrap_or(i64::MAX);
+    age < max_age
+}
+
+async fn read_json_objects<'a, T>(
+    store: &std::sync::Arc<dyn object_store::ObjectStore>,
+    paths: impl IntoIterator<Item = &'a object_store::path::Path>,
+) -> anyhow::Result<Vec<T>>
+where
+    T: serde::de::DeserializeOwned,
+{
+    futures::stream::iter(paths)
+        .map(|path| async move {
+            let object = match store.get(path).await {
+                Ok(object) => object,
+                Err(object_store::Error::NotFound { .. }) => return anyhow::Ok(None),
+                Err(error) => return Err(error.into()),
+            };
+            let bytes = object.bytes().await?;
+            let value = serde_json::from_slice(&bytes)
+                .map_err(|error| anyhow::anyhow!("failed to parse {path}: {error}"))?;
+            anyhow::Ok(Some(value))
+        })
+        .buffer_unordered(MAX_CONCURRENT_READS)
+        .try_filter_map(|value| async move { Ok(value) })
+        .try_collect()
+        .await
+}
+
+async fn read_artifact_chunks(
+    store: &std::sync::Arc<dyn object_store::ObjectStore>,
+    path: &object_store::path::Path,
+) -> anyhow::Result<Vec<super::archive::ChunkEntry>> {
+    let object = match store.get(path).await {
+        Ok(object) => object,
+        Err(object_store::Error::NotFound { .. }) => return Ok(vec![]),
+        Err(error) => return Err(error.into()),
+    };
+
+    let archive_reader_compressed = tokio_util::io::StreamReader::new(object.into_stream());
+    let mut archive_reader =
+        async_compression::tokio::bufread::ZstdDecoder::new(archive_reader_compressed);
+    let chunks = super::archive::read_artifact_archive_chunks(&mut archive_reader)
+        .await
+        .map_err(|error| anyhow::anyhow!("failed to read artifact archive {path}: {error:#}"))?;
+    Ok(chunks)
+}
+
+struct Deleted {
+    objects: u64,
+    bytes: u64,
+}
+
+/// Delete every object not in `kept`, or only log them for a dry run.
+async fn delete_unkept(
+    store: &std::sync::Arc<dyn object_store::ObjectStore>,
+    objects: &HashMap<String, object_store::ObjectMeta>,
+    kept: &HashSet<String>,
+    dry_run: bool,
+) -> anyhow::Result<Deleted> {
+    let mut deleted = Deleted {
+        objects: 0,
+        bytes: 0,
+    };
+    let mut unkept_paths = vec![];
+    for (key, meta) in objects {
+        if !kept.contains(key) {
+            deleted.objects += 1;
+            deleted.bytes += u64::try_from(meta.size)?;
+            unkept_paths.push(meta.location.clone());
+        }
+    }
+
+    if dry_run {
+        for path in &unkept_paths {
+            tracing::info!(%path, "would delete");
+        }
+        return Ok(deleted);
+    }
+
+    let paths = futures::stream::iter(unkept_paths).map(Ok).boxed();
+    store
+        .delete_stream(paths)
+        .try_for_each(|path| async move {
+            tracing::debug!(%path, "deleted");
+            Ok(())
+        })
+        .await?;
+
+    Ok(deleted)
+}
+
+/// Find the recipe hashes of every bake reachable from a project, either
+/// baked directly by one of its exports or baked as a child of one.
+async fn project_bakes(
+    brioche: &Brioche,
+    project_hash: ProjectHash,
+) -> anyhow::Result<HashSet<String>> {
+    let mut db_conn = brioche.db_conn.lock().await;
+    let mut db_transaction = db_conn.begin().await?;
+
+    let project_hash_value = project_hash.to_string();
+    let recipe_hashes = sqlx::query_scalar!(
+        r#"
+            WITH RECURSIVE project_descendent_bakes (recipe_hash) AS (
+                SELECT project_bakes.recipe_hash
+                FROM project_bakes
+                WHERE project_hash = ?
+                UNION
+                SELECT child_bakes.recipe_hash
+                FROM child_bakes
+                INNER JOIN project_descendent_bakes ON
+                    project_descendent_bakes.recipe_hash = child_bakes.parent_hash
+            )
+            SELECT recipe_hash AS "recipe_hash!: String"
+            FROM project_descendent_bakes
+        "#,
+        project_hash_value,
+    )
+    .fetch_all(&mut *db_transaction)
+    .await?;
+
+    db_transaction.commit().await?;
+    drop(db_conn);
+
+    Ok(recipe_hashes.into_iter().collect())
+}
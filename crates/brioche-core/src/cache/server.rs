@@ -0,0 +1,381 @@
+//! Serve a cache over HTTP, using the same object layout the cache client
+//! reads and writes (`bakes/`, `artifacts/`, `projects/`, and `chunks/`).
+//! Clients can use the server by setting it as an `http://` or
+//! `https://` cache URL.
+
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::Context as _;
+
+use crate::{
+    Brioche,
+    recipe::{Artifact, RecipeHash},
+    utils::http_server::{self, Request, Response, not_found},
+};
+
+#[derive(Debug, Clone)]
+pub enum CacheServerSource {
+    /// Serve a cache directory, such as one used with a `file://` cache
+    /// URL or written by `brioche vendor`.
+    Directory(PathBuf),
+
+    /// Serve bakes and artifacts from Brioche's own data directory. These
+    /// get exported on demand the first time they're requested.
+    DataDir,
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheServerOptions {
+    pub source: CacheServerSource,
+
+    /// Allow clients to write to the cache.
+    pub writable: bool,
+
+    /// If set, writes must include an `Authorization: Bearer <token>`
+    /// header with this token.
+    pub write_token: Option<String>,
+
+    /// Used to sign bakes exported from the data directory.
+    pub signing_key: Option<Arc<super::signing::SigningKey>>,
+}
+
+struct CacheServer {
+    /// Used to export local bakes. Its cache client writes to `store`.
+    brioche: Brioche,
+    store: Arc<dyn object_store::ObjectStore>,
+    export_local: bool,
+    writable: bool,
+    write_token_hash: Option<blake3::Hash>,
+}
+
+/// Serve a cache until Brioche gets cancelled.
+pub async fn serve(
+    brioche: &Brioche,
+    options: CacheServerOptions,
+    listener: tokio::net::TcpListener,
+) -> anyhow::Result<()> {
+    let (store_path, export_local) = match options.source {
+        CacheServerSource::Directory(path) => (path, false),
+        CacheServerSource::DataDir => (brioche.data_dir.join("served-cache"), true),
+    };
+    tokio::fs::create_dir_all(&store_path)
+        .await
+        .with_context(|| format!("failed to create directory {}", store_path.display()))?;
+
+    let store = object_store::local::LocalFileSystem::new_with_prefix(&store_path)
+        .with_context(|| format!("failed to use path {} as cache", store_path.display()))?
+        .with_automatic_cleanup(true);
+    let store: Arc<dyn object_store::ObjectStore> = Arc::new(store);
+
+    let mut export_brioche = brioche.clone();
+    let mut export_cache_client = super::CacheClient::from_store(store.clone(), true);
+    export_cache_client.layers[0].signing_key = options.signing_key;
+    export_brioche.cache_client = export_cache_client;
+
+    let server = Arc::new(CacheServer {
+        brioche: export_brioche,
+        store,
+        export_local,
+        writable: options.writable,
+        // Compare hashes so checking the token takes constant time
+        write_token_hash: options
+            .write_token
+            .map(|token| blake3::hash(token.as_bytes())),
+    });
+
+    http_server::serve(listener, &brioche.cancellation_token, move |request| {
+        let server = server.clone();
+        async move { handle_request(&server, request).await }
+    })
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_request(server: &CacheServer, request: Request) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    let result = route_request(server, request).await;
+    match result {
+        Ok(response) => response,
+        Err(error) => {
+            tracing::warn!(%method, %path, "cache server request failed: {error:#}");
+            http_server::status_response(
+                hyper::StatusCode::INTERNAL_SERVER_ERROR,
+                "internal server error",
+            )
+        }
+    }
+}
+
+async fn route_request(server: &CacheServer, request: Request) -> anyhow::Result<Response> {
+    let path = request.uri().path().trim_start_matches('/');
+    let Ok(path) = urlencoding::decode(path) else {
+        return Ok(not_found());
+    };
+    let Ok(path) = object_store::path::Path::parse(&*path) else {
+        return Ok(not_found());
+    };
+
+    match request.method().as_str() {
+        "GET" | "HEAD" => get_object(server, &request, &path).await,
+        "PUT" => {
+            if let Some(response) = check_writable(server, &request) {
+                return Ok(response);
+            }
+
+            put_object(server, request, &path).await
+        }
+        "MKCOL" => {
+            if let Some(response) = check_writable(server, &request) {
+                return Ok(response);
+            }
+
+            // Directories get created automatically on write
+            Ok(http_server::status_response(hyper::StatusCode::CREATED, ""))
+        }
+        _ => Ok(http_server::status_response(
+            hyper::StatusCode::METHOD_NOT_ALLOWED,
+            "method not allowed",
+        )),
+    }
+}
+
+async fn get_object(
+    server: &CacheServer,
+    request: &Request,
+    path: &object_store::path::Path,
+) -> anyhow::Result<Response> {
+    let range = match request.headers().get(hyper::header::RANGE) {
+        Some(range) => {
+            let range = range.to_str().ok().and_then(parse_range);
+            let Some(range) = range else {
+                return Ok(http_server::status_response(
+                    hyper::StatusCode::RANGE_NOT_SATISFIABLE,
+                    "invalid range",
+                ));
+            };
+            Some(range)
+        }
+        None => None,
+    };
+    let is_head = request.method() == hyper::Method::HEAD;
+    let options = object_store::GetOptions {
+        range: range.clone(),
+        head: is_head,
+        ..Default::default()
+    };
+
+    let mut result = server.store.get_opts(path, options.clone()).await;
+    if matches!(result, Err(object_store::Error::NotFound { .. }))
+        && server.export_local
+        && export_local_object(server, path).await?
+    {
+        result = server.store.get_opts(path, options).await;
+    }
+
+    let result = match result {
+        Ok(result) => result,
+        Err(object_store::Error::NotFound { .. }) => {
+            return Ok(not_found());
+        }
+        Err(error) => {
+            return Err(error.into());
+        }
+    };
+
+    let meta = result.meta.clone();
+    let result_range = result.range.clone();
+    let body = if is_head {
+        hyper::body::Bytes::new()
+    } else {
+        result.bytes().await?
+    };
+
+    let last_modified = jiff::Timestamp::from_second(meta.last_modified.timestamp())?
+        .strftime("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string();
+    let mut response = hyper::Response::builder()
+        .header(hyper::header::CONTENT_TYPE, "application/octet-stream")
+        .header(hyper::header::ACCEPT_RANGES, "bytes")
+        .header(hyper::header::LAST_MODIFIED, last_modified)
+        .header(hyper::header::CONTENT_LENGTH, result_range.len());
+    if let Some(e_tag) = &meta.e_tag {
+        response = response.header(hyper::header::ETAG, e_tag);
+    }
+    if range.is_some() {
+        response = response.status(hyper::StatusCode::PARTIAL_CONTENT).header(
+            hyper::header::CONTENT_RANGE,
+            format!(
+                "bytes {}-{}/{}",
+                result_range.start,
+                result_range.end.saturating_sub(1),
+                meta.size
+            ),
+        );
+    }
+
+    let response = response.body(http_server::full_body(body))?;
+    Ok(response)
+}
+
+async fn put_object(
+    server: &CacheServer,
+    request: Request,
+    path: &object_store::path::Path,
+) -> anyhow::Result<Response> {
+    // `If-None-Match: *` means the client only wants to create new objects
+    let if_none_match = request.headers().get(hyper::header::IF_NONE_MATCH);
+    let mode = if if_none_match.is_some_and(|value| value == "*") {
+        object_store::PutMode::Create
+    } else {
+        object_store::PutMode::Overwrite
+    };
+
+    let body = http_server::read_body(request).await?;
+    let result = server
+        .store
+        .put_opts(
+            path,
+            body.into(),
+            object_store::PutOptions {
+                mode,
+                ..Default::default()
+            },
+        )
+        .await;
+    match result {
+        Ok(_) => Ok(http_server::status_response(hyper::StatusCode::CREATED, "")),
+        Err(object_store::Error::AlreadyExists { .. }) => Ok(http_server::status_response(
+            hyper::StatusCode::PRECONDITION_FAILED,
+            "already exists",
+        )),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Returns an error response if the request isn't allowed to write.
+fn check_writable(server: &CacheServer, request: &Request) -> Option<Response> {
+    if !server.writable {
+        return Some(http_server::status_response(
+            hyper::StatusCode::METHOD_NOT_ALLOWED,
+            "cache is read-only",
+        ));
+    }
+
+    let Some(write_token_hash) = &server.write_token_hash else {
+        return None;
+    };
+
+    let token = request
+        .headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let is_authorized =
+        token.is_some_and(|token| blake3::hash(token.as_bytes()) == *write_token_hash);
+    if is_authorized {
+        None
+    } else {
+        let mut response =
+            http_server::status_response(hyper::StatusCode::UNAUTHORIZED, "unauthorized");
+        response.headers_mut().insert(
+            hyper::header::WWW_AUTHENTICATE,
+            hyper::header::HeaderValue::from_static("Bearer"),
+        );
+        Some(response)
+    }
+}
+
+/// Write a bake or artifact from the local database into the store, if
+/// it exists locally. Returns true if the object was exported.
+async fn export_local_object(
+    server: &CacheServer,
+    path: &object_store::path::Path,
+) -> anyhow::Result<bool> {
+    let brioche = &server.brioche;
+    let parts = path.parts().collect::<Vec<_>>();
+    let parts = parts.iter().map(AsRef::as_ref).collect::<Vec<&str>>();
+
+    match parts[..] {
+        ["bakes", input_hash, "output.json"] => {
+            let Ok(input_hash) = input_hash.parse::<RecipeHash>() else {
+                return Ok(false);
+            };
+            let Some(artifact) = crate::bake::get_local_bake(brioche, input_hash).await? else {
+                return Ok(false);
+            };
+
+            let artifact_hash = artifact.hash();
+            super::save_artifact(brioche, artifact).await?;
+            super::save_bake(brioche, input_hash, artifact_hash).await?;
+            Ok(true)
+        }
+        ["artifacts", artifact_filename] => {
+            let artifact_hash = artifact_filename
+                .strip_suffix(".bar.zst")
+                .and_then(|hash| hash.parse::<RecipeHash>().ok());
+            let Some(artifact_hash) = artifact_hash else {
+                return Ok(false);
+            };
+            let local_recipes = crate::references::local_recipes(brioche, [artifact_hash]).await?;
+            if !local_recipes.contains(&artifact_hash) {
+                return Ok(false);
+            }
+
+            let recipe = crate::recipe::get_recipe(brioche, artifact_hash).await?;
+            let Ok(artifact) = Artifact::try_from(recipe) else {
+                return Ok(false);
+            };
+
+            super::save_artifact(brioche, artifact).await?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Parse a single range from a `Range` header, e.g. `bytes=0-99`,
+/// `bytes=100-`, or `bytes=-100`.
+fn parse_range(range: &str) -> Option<object_store::GetRange> {
+    let range = range.strip_prefix("bytes=")?;
+    let (start, end) = range.split_once('-')?;
+
+    match (start, end) {
+        ("", suffix) => Some(object_store::GetRange::Suffix(suffix.parse().ok()?)),
+        (start, "") => Some(object_store::GetRange::Offset(start.parse().ok()?)),
+        (start, end) => {
+            let start: usize = start.parse().ok()?;
+            let end: usize = end.parse().ok()?;
+            if end < start {
+                return None;
+            }
+            Some(object_store::GetRange::Bounded(start..end + 1))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_range;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(
+            parse_range("bytes=0-99"),
+            Some(object_store::GetRange::Bounded(0..100))
+        );
+        assert_eq!(
+            parse_range("bytes=100-"),
+            Some(object_store::GetRange::Offset(100))
+        );
+        assert_eq!(
+            parse_range("bytes=-100"),
+            Some(object_store::GetRange::Suffix(100))
+        );
+        assert_eq!(parse_range("bytes=10-5"), None);
+        assert_eq!(parse_range("bytes=0-10,20-30"), None);
+        assert_eq!(parse_range("items=0-10"), None);
+    }
+}
@@ -0,0 +1,172 @@
+//! Ed25519 signatures for cache entries that can't be verified by their
+//! content hash alone, like bake outputs and project sources.
+
+use std::path::Path;
+
+use anyhow::Context as _;
+use ring::signature::KeyPair as _;
+
+const PUBLIC_KEY_PREFIX: &str = "ed25519:";
+const SIGNING_KEY_PREFIX: &str = "ed25519-secret:";
+
+/// A private key used to sign cache entries.
+pub struct SigningKey {
+    key_pair: ring::signature::Ed25519KeyPair,
+}
+
+impl SigningKey {
+    /// Generate a new random key. Returns the key along with its
+    /// encoded form, which can be saved and loaded with [`SigningKey::decode`].
+    pub fn generate() -> anyhow::Result<(Self, String)> {
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = ring::signature::Ed25519KeyPair::generate_pkcs8(&rng)
+            .map_err(|_| anyhow::anyhow!("failed to generate signing key"))?;
+        let encoded = format!("{SIGNING_KEY_PREFIX}{}", hex::encode(pkcs8.as_ref()));
+        let key = Self::decode(&encoded)?;
+        Ok((key, encoded))
+    }
+
+    pub fn decode(encoded: &str) -> anyhow::Result<Self> {
+        let pkcs8 = encoded
+            .trim()
+            .strip_prefix(SIGNING_KEY_PREFIX)
+            .context("invalid signing key: expected an ed25519 key")?;
+        let pkcs8 = hex::decode(pkcs8).context("invalid signing key")?;
+        let key_pair = ring::signature::Ed25519KeyPair::from_pkcs8(&pkcs8)
+            .map_err(|error| anyhow::anyhow!("invalid signing key: {error}"))?;
+        Ok(Self { key_pair })
+    }
+
+    pub async fn load(path: &Path) -> anyhow::Result<Self> {
+        let encoded = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("failed to read signing key from {}", path.display()))?;
+        Self::decode(&encoded)
+            .with_context(|| format!("failed to load signing key from {}", path.display()))
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        let public_key = self.key_pair.public_key().as_ref();
+        let public_key = public_key
+            .try_into()
+            .expect("ed25519 public key should be 32 bytes");
+        PublicKey(public_key)
+    }
+
+    pub fn sign(&self, message: &[u8]) -> CacheSignature {
+        let signature = self.key_pair.sign(message);
+        CacheSignature {
+            key: self.public_key(),
+            signature: hex::encode(signature.as_ref()),
+        }
+    }
+}
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Only show the public key, so the private key doesn't end up in logs
+        f.debug_struct("SigningKey")
+            .field("public_key", &self.public_key())
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde_with::SerializeDisplay,
+    serde_with::DeserializeFromStr,
+)]
+pub struct PublicKey([u8; 32]);
+
+impl PublicKey {
+    /// Returns true if `signature` is a valid signature of `message`
+    /// made by this key.
+    pub fn verify(&self, message: &[u8], signature: &CacheSignature) -> bool {
+        if signature.key != *self {
+            return false;
+        }
+
+        let Ok(signature) = hex::decode(&signature.signature) else {
+            return false;
+        };
+        let public_key = ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, self.0);
+        public_key.verify(message, &signature).is_ok()
+    }
+}
+
+impl std::fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{PUBLIC_KEY_PREFIX}{}", hex::encode(self.0))
+    }
+}
+
+impl std::str::FromStr for PublicKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = s
+            .strip_prefix(PUBLIC_KEY_PREFIX)
+            .with_context(|| format!("invalid public key {s:?}: expected an ed25519 key"))?;
+        let mut bytes = [0; 32];
+        hex::decode_to_slice(key, &mut bytes)
+            .with_context(|| format!("invalid public key {s:?}"))?;
+        Ok(Self(bytes))
+    }
+}
+
+/// A signature stored alongside a cache entry.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheSignature {
+    pub key: PublicKey,
+    pub signature: String,
+}
+
+/// Returns true if `signature` is a valid signature of `message` from
+/// any of the trusted keys.
+pub fn is_trusted(
+    trusted_keys: &[PublicKey],
+    message: &[u8],
+    signature: Option<&CacheSignature>,
+) -> bool {
+    let Some(signature) = signature else {
+        return false;
+    };
+
+    trusted_keys
+        .iter()
+        .any(|key| key.verify(message, signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PublicKey, SigningKey, is_trusted};
+
+    #[test]
+    fn test_sign_and_verify() {
+        let (key, encoded) = SigningKey::generate().unwrap();
+        let decoded = SigningKey::decode(&encoded).unwrap();
+        assert_eq!(key.public_key(), decoded.public_key());
+
+        let public_key = key.public_key();
+        let parsed: PublicKey = public_key.to_string().parse().unwrap();
+        assert_eq!(public_key, parsed);
+
+        let signature = key.sign(b"hello");
+        assert!(is_trusted(&[public_key], b"hello", Some(&signature)));
+        assert!(!is_trusted(&[public_key], b"goodbye", Some(&signature)));
+        assert!(!is_trusted(&[public_key], b"hello", None));
+
+        let (other_key, _) = SigningKey::generate().unwrap();
+        assert!(!is_trusted(
+            &[other_key.public_key()],
+            b"hello",
+            Some(&signature)
+        ));
+    }
+}
@@ -1,4 +1,7 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
 use anyhow::Context as _;
 use tokio::io::AsyncReadExt as _;
@@ -33,6 +36,207 @@ pub struct BriocheConfig {
     pub sandbox: SandboxConfig,
 
     pub cache: Option<CacheConfig>,
+
+    /// Additional caches to read from. Caches are tried in order of
+    /// priority, and at most one of `cache` and `caches` can be writable.
+    #[serde(default)]
+    pub caches: Vec<CacheConfig>,
+
+    #[serde(default)]
+    pub chunk_cache: ChunkCacheConfig,
+
+    /// Fetch every file of an artifact from the cache up front. By
+    /// default, a file's contents only get fetched once they're needed.
+    #[serde(default)]
+    pub eager_cache_fetch: bool,
+
+    #[serde(default)]
+    pub download: DownloadConfig,
+
+    #[serde(default)]
+    pub network: NetworkConfig,
+
+    #[serde(default)]
+    pub processes: ProcessConfig,
+
+    pub remote_execution: Option<RemoteExecutionConfig>,
+
+    /// Disable network access. Anything that isn't available locally
+    /// will fail instead of being fetched.
+    #[serde(default)]
+    pub offline: bool,
+}
+
+/// Run processes on a remote worker (started with `brioche worker`)
+/// instead of locally.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RemoteExecutionConfig {
+    pub url: url::Url,
+}
+
+/// Settings for how many processes run at once.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ProcessConfig {
+    /// The maximum number of jobs to run at once. Each process uses one
+    /// job unless it declares otherwise. Defaults to the number of CPUs,
+    /// limited by available memory.
+    pub max_jobs: Option<u32>,
+
+    /// How much memory to budget for each job, in MiB, when picking the
+    /// default for `max_jobs`.
+    #[serde(default = "default_process_memory_per_job_mib")]
+    pub memory_per_job_mib: u64,
+}
+
+impl Default for ProcessConfig {
+    fn default() -> Self {
+        Self {
+            max_jobs: None,
+            memory_per_job_mib: default_process_memory_per_job_mib(),
+        }
+    }
+}
+
+fn default_process_memory_per_job_mib() -> u64 {
+    1024
+}
+
+/// Settings for the local cache of chunks fetched from remote caches.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ChunkCacheConfig {
+    /// The maximum size of the chunk cache, in MiB. Set to 0 to disable
+    /// the chunk cache.
+    #[serde(default = "default_chunk_cache_max_size_mib")]
+    pub max_size_mib: u64,
+}
+
+impl Default for ChunkCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_size_mib: default_chunk_cache_max_size_mib(),
+        }
+    }
+}
+
+fn default_chunk_cache_max_size_mib() -> u64 {
+    10 * 1024
+}
+
+/// Network settings used for downloads, git fetches, the registry, and
+/// the cache.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct NetworkConfig {
+    /// Path to a PEM file containing extra CA certificates to trust, in
+    /// addition to the built-in root certificates.
+    pub ca_certificates: Option<PathBuf>,
+
+    /// A proxy to use for all HTTP and HTTPS requests.
+    pub proxy: Option<url::Url>,
+
+    /// Hosts that should be connected to directly instead of through
+    /// the proxy.
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+
+    /// A client certificate to present for TLS connections. Used for
+    /// downloads and the registry. Remote caches and git repositories over
+    /// HTTP can't present client certificates, so they connect without one
+    /// unless their host is listed in `hosts`.
+    pub client_certificate: Option<ClientCertificateConfig>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ClientCertificateConfig {
+    /// Path to a PEM file containing the certificate chain.
+    pub certificate: PathBuf,
+
+    /// Path to a PEM file containing the private key.
+    pub key: PathBuf,
+
+    /// Hosts that require the client certificate. Remote caches and git
+    /// repositories over HTTP on one of these hosts fail with an error,
+    /// since they can't present the certificate. Entries starting with
+    /// `.` also match subdomains.
+    #[serde(default)]
+    pub hosts: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DownloadConfig {
+    /// Content-addressed mirrors to try before the original URL. Files
+    /// are fetched from `<mirror>/<algorithm>/<hex>`, e.g.
+    /// `https://mirror.example.com/sha256/<hex>`.
+    #[serde(default)]
+    pub content_mirrors: Vec<url::Url>,
+
+    /// The maximum number of times to try each download before giving up.
+    /// Interrupted downloads are resumed where possible.
+    #[serde(default = "default_download_max_attempts")]
+    pub max_attempts: u32,
+
+    /// Rules for rewriting URLs before downloading files or fetching git
+    /// repositories. The first rule with a matching prefix is used.
+    /// Rewrites don't affect recipe hashes or the lockfile.
+    #[serde(default)]
+    pub rewrites: Vec<UrlRewriteRule>,
+
+    /// Credentials to use when downloading from specific hosts. These take
+    /// precedence over credentials from the `.netrc` file.
+    #[serde(default)]
+    pub credentials: Vec<DownloadCredentials>,
+
+    /// Path to a `.netrc` file with credentials for downloads. Defaults
+    /// to `$NETRC`, or `~/.netrc` if not set.
+    pub netrc: Option<PathBuf>,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            content_mirrors: vec![],
+            max_attempts: default_download_max_attempts(),
+            rewrites: vec![],
+            credentials: vec![],
+            netrc: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UrlRewriteRule {
+    pub prefix: String,
+    pub replacement: String,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct DownloadCredentials {
+    /// The host the credentials are sent to, e.g. `files.example.com`.
+    pub host: String,
+
+    /// Username for HTTP basic auth.
+    pub username: Option<String>,
+
+    /// Password for HTTP basic auth.
+    pub password: Option<String>,
+
+    /// Token for HTTP bearer auth, used instead of a username and password.
+    pub token: Option<String>,
+}
+
+impl std::fmt::Debug for DownloadCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Avoid leaking secrets into logs
+        f.debug_struct("DownloadCredentials")
+            .field("host", &self.host)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+fn default_download_max_attempts() -> u32 {
+    5
 }
 
 #[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
@@ -81,8 +285,96 @@ pub struct CacheConfig {
     pub read_only: bool,
 
     pub allow_http: Option<bool>,
+
+    /// Caches with a higher priority get tried first.
+    #[serde(default)]
+    pub priority: i32,
+
+    /// Public keys (from `brioche cache keygen`) trusted to sign bakes
+    /// and projects in this cache. When set, unsigned entries or entries
+    /// signed by other keys are ignored.
+    #[serde(default)]
+    pub trusted_keys: Vec<crate::cache::signing::PublicKey>,
+
+    /// Path to a private key used to sign bakes and projects written to
+    /// this cache.
+    pub signing_key_path: Option<PathBuf>,
+
+    /// Credentials sent with every request to an `http://` or `https://`
+    /// cache.
+    pub auth: Option<CacheAuthConfig>,
+
+    /// Extra headers sent with every request to an `http://` or
+    /// `https://` cache, such as an API key for an auth proxy.
+    #[serde(default)]
+    pub headers: BTreeMap<String, SecretSource>,
+
+    /// How artifacts and chunks written to this cache get compressed.
+    /// Readers detect the settings from each artifact, so this only
+    /// affects writes.
+    #[serde(default)]
+    pub compression: CacheCompressionConfig,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct CacheCompressionConfig {
+    /// The zstd compression level. Defaults to zstd's default level.
+    pub level: Option<i32>,
+
+    /// Enable zstd's long-distance matching.
+    #[serde(default)]
+    pub long_distance_matching: bool,
+
+    /// Path to a zstd dictionary (e.g. from `zstd --train`) used to
+    /// compress chunks. The dictionary gets uploaded to the cache so
+    /// readers can use it too.
+    pub dictionary_path: Option<PathBuf>,
+
+    /// Target chunk sizes for content-defined chunking, in bytes.
+    pub min_chunk_size: Option<u32>,
+    pub avg_chunk_size: Option<u32>,
+    pub max_chunk_size: Option<u32>,
 }
 
 fn default_cache_max_concurrent_operations() -> usize {
     200
 }
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CacheAuthConfig {
+    Bearer {
+        token: SecretSource,
+    },
+    Basic {
+        username: String,
+        password: SecretSource,
+    },
+}
+
+/// A secret read from an environment variable or a file, so it doesn't
+/// need to be stored in the config itself.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretSource {
+    Env(String),
+    File(PathBuf),
+}
+
+impl SecretSource {
+    pub async fn read(&self) -> anyhow::Result<String> {
+        match self {
+            Self::Env(name) => {
+                std::env::var(name).with_context(|| format!("failed to read secret from ${name}"))
+            }
+            Self::File(path) => {
+                let secret = tokio::fs::read_to_string(path)
+                    .await
+                    .with_context(|| format!("failed to read secret from {}", path.display()))?;
+
+                // Ignore the trailing newline most editors add
+                Ok(secret.trim_end_matches(['\r', '\n']).to_string())
+            }
+        }
+    }
+}
@@ -1,19 +1,25 @@
 use anyhow::Context as _;
 use futures::TryStreamExt as _;
-use tokio_util::compat::FuturesAsyncReadCompatExt as _;
+use tokio::io::{AsyncSeekExt as _, AsyncWriteExt as _};
 
 use crate::{
     Brioche,
     reporter::job::{NewJob, UpdateJob},
 };
 
-#[expect(clippy::cast_possible_truncation)]
+mod netrc;
+
+const INITIAL_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+const MAX_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
 #[tracing::instrument(skip(brioche, expected_hash))]
 pub async fn download(
     brioche: &Brioche,
     url: &url::Url,
     expected_hash: Option<crate::Hash>,
 ) -> anyhow::Result<crate::blob::BlobHash> {
+    crate::network::ensure_online(brioche, || format!("download {url}"))?;
+
     // Acquire a permit to save the blob
     let mut save_blob_permit = crate::blob::get_save_blob_permit().await?;
 
@@ -22,15 +28,198 @@ pub async fn download(
     let _permit = brioche.download_semaphore.acquire().await?;
     tracing::debug!("acquired download semaphore permit");
 
+    let url = &rewrite_url(brioche, url)?;
     tracing::debug!(%url, "starting download");
 
+    let auth = download_auth(brioche, url).await?;
+
     let job_id = brioche.reporter.add_job(NewJob::Download {
         url: url.clone(),
         started_at: std::time::Instant::now(),
     });
 
-    let response = brioche.download_client.get(url.clone()).send().await?;
-    let response = response.error_for_status()?;
+    // Download into a temporary file first, so an interrupted download
+    // can be resumed from where it left off
+    let temp_dir = brioche.data_dir.join("downloads-temp");
+    tokio::fs::create_dir_all(&temp_dir).await?;
+    let temp_path = temp_dir.join(ulid::Ulid::new().to_string());
+
+    let result = async {
+        download_to_file(brioche, url, auth.as_ref(), &temp_path, job_id).await?;
+
+        let save_blob_options = crate::blob::SaveBlobOptions::new()
+            .expected_hash(expected_hash)
+            .remove_input(true);
+        let blob_hash = crate::blob::save_blob_from_file(
+            brioche,
+            &mut save_blob_permit,
+            &temp_path,
+            save_blob_options,
+            &mut Vec::new(),
+        )
+        .await
+        .context("failed to save blob")?;
+
+        anyhow::Ok(blob_hash)
+    }
+    .await;
+
+    // Clean up the partial download if it wasn't saved
+    if tokio::fs::try_exists(&temp_path).await? {
+        tokio::fs::remove_file(&temp_path).await?;
+    }
+
+    let blob_hash = result?;
+
+    brioche.reporter.update_job(
+        job_id,
+        UpdateJob::Download {
+            progress_percent: Some(100),
+            finished_at: Some(std::time::Instant::now()),
+        },
+    );
+
+    Ok(blob_hash)
+}
+
+/// Download a URL to a file, retrying with exponential backoff on
+/// transient errors. Retries use a `Range` request to resume from the end
+/// of the partially-downloaded file when the server supports it.
+async fn download_to_file(
+    brioche: &Brioche,
+    url: &url::Url,
+    auth: Option<&DownloadAuth>,
+    path: &std::path::Path,
+    job_id: crate::reporter::JobId,
+) -> anyhow::Result<()> {
+    let max_attempts = brioche.download_config.max_attempts.max(1);
+
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .with_context(|| format!("failed to create file {}", path.display()))?;
+    let mut downloaded_bytes = 0;
+    let mut retry_delay = INITIAL_RETRY_DELAY;
+
+    for attempt in 1.. {
+        let result =
+            download_attempt(brioche, url, auth, &mut file, &mut downloaded_bytes, job_id).await;
+        match result {
+            Ok(()) => {
+                break;
+            }
+            Err(DownloadAttemptError::Fatal(error)) => {
+                return Err(error);
+            }
+            Err(DownloadAttemptError::Retryable(error)) if attempt >= max_attempts => {
+                return Err(error.context(format!(
+                    "download failed after {attempt} attempt{s}",
+                    s = if attempt == 1 { "" } else { "s" }
+                )));
+            }
+            Err(DownloadAttemptError::Retryable(error)) => {
+                tracing::warn!(
+                    %url,
+                    attempt,
+                    downloaded_bytes,
+                    "download failed, retrying in {retry_delay:?}: {error:#}"
+                );
+                tokio::time::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+
+                if downloaded_bytes > 0 {
+                    brioche.reporter.update_job(
+                        job_id,
+                        UpdateJob::DownloadResume {
+                            resumed_bytes: downloaded_bytes,
+                            attempt: attempt + 1,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    file.flush()
+        .await
+        .context("failed to flush downloaded file")?;
+
+    Ok(())
+}
+
+enum DownloadAttemptError {
+    Retryable(anyhow::Error),
+    Fatal(anyhow::Error),
+}
+
+#[expect(clippy::cast_possible_truncation)]
+async fn download_attempt(
+    brioche: &Brioche,
+    url: &url::Url,
+    auth: Option<&DownloadAuth>,
+    file: &mut tokio::fs::File,
+    downloaded_bytes: &mut u64,
+    job_id: crate::reporter::JobId,
+) -> Result<(), DownloadAttemptError> {
+    let mut request = brioche.download_client.get(url.clone());
+    match auth {
+        Some(DownloadAuth::Basic { username, password }) => {
+            request = request.basic_auth(username, password.as_ref());
+        }
+        Some(DownloadAuth::Bearer { token }) => {
+            request = request.bearer_auth(token);
+        }
+        None => {}
+    }
+    if *downloaded_bytes > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={downloaded_bytes}-"));
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|error| DownloadAttemptError::Retryable(error.into()))?;
+
+    let status = response.status();
+    if status.is_server_error()
+        || status == reqwest::StatusCode::REQUEST_TIMEOUT
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+    {
+        return Err(DownloadAttemptError::Retryable(anyhow::anyhow!(
+            "server returned status {status}"
+        )));
+    }
+
+    let response = response
+        .error_for_status()
+        .map_err(|error| DownloadAttemptError::Fatal(error.into()))?;
+
+    if *downloaded_bytes > 0 {
+        let resumed_at = if response.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+            response
+                .headers()
+                .get(reqwest::header::CONTENT_RANGE)
+                .and_then(|content_range| content_range.to_str().ok())
+                .and_then(content_range_start)
+        } else {
+            None
+        };
+
+        if resumed_at != Some(*downloaded_bytes) {
+            // The server doesn't support resuming from where we left off,
+            // so start over from the beginning
+            tracing::debug!(%url, "server did not resume download, restarting");
+            restart_file(file)
+                .await
+                .map_err(DownloadAttemptError::Fatal)?;
+            *downloaded_bytes = 0;
+
+            if response.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+                return Err(DownloadAttemptError::Retryable(anyhow::anyhow!(
+                    "server returned an unexpected range"
+                )));
+            }
+        }
+    }
 
     let content_length = response.content_length().or_else(|| {
         let content_length = response.headers().get(reqwest::header::CONTENT_LENGTH)?;
@@ -41,54 +230,220 @@ pub async fn download(
             Some(content_length)
         }
     });
+    let total_length = content_length.map(|content_length| content_length + *downloaded_bytes);
+
+    let mut download_stream = response.bytes_stream();
+    while let Some(chunk) = download_stream
+        .try_next()
+        .await
+        .map_err(|error| DownloadAttemptError::Retryable(error.into()))?
+    {
+        file.write_all(&chunk)
+            .await
+            .context("failed to write downloaded file")
+            .map_err(DownloadAttemptError::Fatal)?;
+        *downloaded_bytes += chunk.len() as u64;
+
+        if let Some(total_length) = total_length {
+            let progress_percent = (*downloaded_bytes as f64 / total_length as f64) * 100.0;
+            let progress_percent = progress_percent.round().min(99.0) as u8;
+            brioche.reporter.update_job(
+                job_id,
+                UpdateJob::Download {
+                    progress_percent: Some(progress_percent),
+                    finished_at: None,
+                },
+            );
+        }
+    }
+
+    if let Some(total_length) = total_length {
+        if *downloaded_bytes < total_length {
+            return Err(DownloadAttemptError::Retryable(anyhow::anyhow!(
+                "connection closed after {downloaded_bytes} of {total_length} bytes"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+async fn restart_file(file: &mut tokio::fs::File) -> anyhow::Result<()> {
+    file.set_len(0)
+        .await
+        .context("failed to truncate downloaded file")?;
+    file.seek(std::io::SeekFrom::Start(0))
+        .await
+        .context("failed to seek downloaded file")?;
+    Ok(())
+}
+
+enum DownloadAuth {
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    Bearer {
+        token: String,
+    },
+}
 
-    let mut download_stream = response
-        .bytes_stream()
-        .map_err(|e| futures::io::Error::new(futures::io::ErrorKind::Other, e))
-        .into_async_read()
-        .compat();
-    let download_stream = std::pin::pin!(download_stream);
-
-    let save_blob_options = crate::blob::SaveBlobOptions::new()
-        .expected_hash(expected_hash)
-        .on_progress(|bytes_read| {
-            if let Some(content_length) = content_length {
-                let progress_percent = (bytes_read as f64 / content_length as f64) * 100.0;
-                let progress_percent = progress_percent.round().min(99.0) as u8;
-                brioche.reporter.update_job(
-                    job_id,
-                    UpdateJob::Download {
-                        progress_percent: Some(progress_percent),
-                        finished_at: None,
-                    },
+/// Find the credentials to use for a download, either from the config
+/// or from the `.netrc` file.
+async fn download_auth(brioche: &Brioche, url: &url::Url) -> anyhow::Result<Option<DownloadAuth>> {
+    let Some(host) = url.host_str() else {
+        return Ok(None);
+    };
+
+    let credentials = brioche
+        .download_config
+        .credentials
+        .iter()
+        .find(|credentials| credentials.host == host);
+    if let Some(credentials) = credentials {
+        let auth = match credentials {
+            crate::config::DownloadCredentials {
+                token: Some(token),
+                username: None,
+                password: None,
+                ..
+            } => DownloadAuth::Bearer {
+                token: token.clone(),
+            },
+            crate::config::DownloadCredentials {
+                token: None,
+                username: Some(username),
+                password,
+                ..
+            } => DownloadAuth::Basic {
+                username: username.clone(),
+                password: password.clone(),
+            },
+            _ => {
+                anyhow::bail!(
+                    "invalid download credentials for {host}: expected either a token or a username and password"
                 );
             }
+        };
+        return Ok(Some(auth));
+    }
 
-            Ok(())
-        });
+    let netrc_path = match &brioche.download_config.netrc {
+        Some(netrc_path) => Some(netrc_path.clone()),
+        None => std::env::var_os("NETRC")
+            .map(std::path::PathBuf::from)
+            .or_else(|| directories::BaseDirs::new().map(|dirs| dirs.home_dir().join(".netrc"))),
+    };
+    let Some(netrc_path) = netrc_path else {
+        return Ok(None);
+    };
 
-    let blob_hash = crate::blob::save_blob_from_reader(
-        brioche,
-        &mut save_blob_permit,
-        download_stream,
-        save_blob_options,
-        &mut Vec::new(),
-    )
-    .await
-    .context("failed to save blob")?;
+    let netrc = match tokio::fs::read_to_string(&netrc_path).await {
+        Ok(netrc) => netrc,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(None);
+        }
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read {}", netrc_path.display()));
+        }
+    };
 
-    brioche.reporter.update_job(
-        job_id,
-        UpdateJob::Download {
-            progress_percent: Some(100),
-            finished_at: Some(std::time::Instant::now()),
-        },
+    let auth = netrc::find_entry(&netrc, host).and_then(|entry| {
+        Some(DownloadAuth::Basic {
+            username: entry.login?,
+            password: entry.password,
+        })
+    });
+    Ok(auth)
+}
+
+/// Get the starting offset from a `Content-Range` header, e.g.
+/// `bytes 100-199/200`.
+fn content_range_start(content_range: &str) -> Option<u64> {
+    let range = content_range.strip_prefix("bytes ")?;
+    let (start, _) = range.split_once('-')?;
+    start.trim().parse().ok()
+}
+
+/// Download a file with a known hash, trying each configured content mirror
+/// first, then `url`, then each of `mirrors` in order. The first download
+/// that succeeds and matches the expected hash is returned.
+pub async fn download_with_mirrors(
+    brioche: &Brioche,
+    url: &url::Url,
+    mirrors: &[url::Url],
+    expected_hash: &crate::Hash,
+) -> anyhow::Result<crate::blob::BlobHash> {
+    crate::network::ensure_online(brioche, || format!("download {url}"))?;
+
+    let content_mirror_urls = brioche
+        .download_config
+        .content_mirrors
+        .iter()
+        .map(|mirror| content_mirror_url(mirror, expected_hash))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let candidate_urls = content_mirror_urls
+        .iter()
+        .chain(std::iter::once(url))
+        .chain(mirrors);
+
+    let mut errors = vec![];
+    for candidate_url in candidate_urls {
+        match download(brioche, candidate_url, Some(expected_hash.clone())).await {
+            Ok(blob_hash) => return Ok(blob_hash),
+            Err(error) => {
+                tracing::warn!(url = %candidate_url, "download failed: {error:#}");
+                errors.push(format!("{candidate_url}: {error:#}"));
+            }
+        }
+    }
+
+    anyhow::bail!(
+        "failed to download {url} with hash {expected_hash}:\n{}",
+        errors.join("\n")
     );
+}
 
-    Ok(blob_hash)
+/// Build the URL for a file within a content-addressed mirror, in the
+/// form `<mirror>/<algorithm>/<hex>`.
+fn content_mirror_url(mirror: &url::Url, hash: &crate::Hash) -> anyhow::Result<url::Url> {
+    let mut url = mirror.clone();
+    url.path_segments_mut()
+        .map_err(|()| anyhow::anyhow!("invalid content mirror URL: {mirror}"))?
+        .pop_if_empty()
+        .push(hash.algorithm())
+        .push(&hex::encode(hash.value()));
+    Ok(url)
 }
 
-pub async fn fetch_git_commit_for_ref(repository: &url::Url, ref_: &str) -> anyhow::Result<String> {
+/// Apply the first matching URL rewrite rule from the config, if any.
+pub fn rewrite_url(brioche: &Brioche, url: &url::Url) -> anyhow::Result<url::Url> {
+    let rule = brioche
+        .download_config
+        .rewrites
+        .iter()
+        .find(|rule| url.as_str().starts_with(&rule.prefix));
+    let Some(rule) = rule else {
+        return Ok(url.clone());
+    };
+
+    let rewritten = format!("{}{}", rule.replacement, &url.as_str()[rule.prefix.len()..]);
+    let rewritten = rewritten
+        .parse()
+        .with_context(|| format!("invalid URL {rewritten:?} after rewriting {url}"))?;
+    tracing::debug!(%url, %rewritten, "rewrote URL");
+
+    Ok(rewritten)
+}
+
+pub async fn fetch_git_commit_for_ref(
+    brioche: &Brioche,
+    repository: &url::Url,
+    ref_: &str,
+) -> anyhow::Result<String> {
+    crate::network::ensure_online(brioche, || format!("git ref {ref_:?} from {repository}"))?;
+
+    let repository = &rewrite_url(brioche, repository)?;
     let (tx, rx) = tokio::sync::oneshot::channel::<anyhow::Result<_>>();
 
     // gix uses a blocking client, so spawn a separate thread to fetch
@@ -97,6 +452,7 @@ pub async fn fetch_git_commit_for_ref(repository: &url::Url, ref_: &str) -> anyh
             .as_str()
             .try_into()
             .with_context(|| format!("failed to parse git repository URL: {repository}"))?;
+        let http_options = brioche.network_options.gix_http_options(&repository)?;
         move || {
             // Connect to the repository by URL
             let transport = gix::protocol::transport::connect(repository, Default::default());
@@ -107,6 +463,10 @@ pub async fn fetch_git_commit_for_ref(repository: &url::Url, ref_: &str) -> anyh
                     return;
                 }
             };
+            if let Err(error) = transport.configure(&http_options) {
+                let _ = tx.send(Err(anyhow::anyhow!(error)));
+                return;
+            }
 
             // Perform a handshake to get the remote's capabilities.
             // Authentication is disabled
@@ -210,3 +570,84 @@ pub async fn fetch_git_commit_for_ref(repository: &url::Url, ref_: &str) -> anyh
     let commit = object_id.to_string();
     Ok(commit)
 }
+
+/// Fetch a commit from a git repository into a new bare repository at
+/// `path`, along with all of the objects reachable from the commit.
+pub async fn fetch_git_commit(
+    brioche: &Brioche,
+    repository: &url::Url,
+    commit: gix::ObjectId,
+    path: &std::path::Path,
+) -> anyhow::Result<()> {
+    crate::network::ensure_online(brioche, || format!("git commit {commit} from {repository}"))?;
+
+    let repository = &rewrite_url(brioche, repository)?;
+    let (tx, rx) = tokio::sync::oneshot::channel::<anyhow::Result<()>>();
+
+    // gix uses a blocking client, so spawn a separate thread to fetch
+    std::thread::spawn({
+        let repository: gix::Url = repository
+            .as_str()
+            .try_into()
+            .with_context(|| format!("failed to parse git repository URL: {repository}"))?;
+        let path = path.to_owned();
+        let http_options = brioche.network_options.gix_http_options(&repository)?;
+        move || {
+            let result = fetch_git_commit_blocking(&repository, commit, &path, &http_options);
+            let _ = tx.send(result);
+        }
+    });
+
+    rx.await?
+}
+
+fn fetch_git_commit_blocking(
+    repository: &gix::Url,
+    commit: gix::ObjectId,
+    path: &std::path::Path,
+    http_options: &gix::protocol::transport::client::http::Options,
+) -> anyhow::Result<()> {
+    let repo = gix::init_bare(path)?;
+
+    // Try to fetch only the commit first. Not every server allows fetching
+    // a commit directly, so fall back to fetching all branches and tags
+    let commit_refspec = commit.to_string();
+    let refspec_attempts: [&[&str]; 2] = [
+        &[&commit_refspec],
+        &[
+            "+refs/heads/*:refs/remotes/origin/*",
+            "+refs/tags/*:refs/tags/*",
+        ],
+    ];
+
+    for refspecs in refspec_attempts {
+        let result = (|| {
+            let remote = repo
+                .remote_at(repository.clone())?
+                .with_refspecs(refspecs.iter().copied(), gix::remote::Direction::Fetch)?;
+            let connection = remote
+                .connect(gix::remote::Direction::Fetch)?
+                .with_transport_options(Box::new(http_options.clone()));
+            connection
+                .prepare_fetch(gix::progress::Discard, Default::default())?
+                .receive(
+                    gix::progress::Discard,
+                    &std::sync::atomic::AtomicBool::new(false),
+                )?;
+            anyhow::Ok(())
+        })();
+
+        if let Err(error) = result {
+            tracing::debug!(?refspecs, "failed to fetch from git repository: {error:#}");
+        }
+
+        if repo.has_object(commit) {
+            return Ok(());
+        }
+    }
+
+    anyhow::bail!(
+        "commit {commit} not found in git repository {}",
+        repository.to_bstring()
+    );
+}
@@ -0,0 +1,143 @@
+/// Credentials for a single machine from a `.netrc` file.
+#[derive(PartialEq, Eq)]
+pub struct NetrcEntry {
+    pub login: Option<String>,
+    pub password: Option<String>,
+}
+
+impl std::fmt::Debug for NetrcEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NetrcEntry")
+            .field("login", &self.login)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+/// Find the credentials for `host` in the contents of a `.netrc` file.
+/// Falls back to the `default` entry if no machine matches.
+pub fn find_entry(netrc: &str, host: &str) -> Option<NetrcEntry> {
+    let mut tokens = netrc.split_whitespace();
+    let mut default_entry = None;
+
+    while let Some(token) = tokens.next() {
+        let is_match = match token {
+            "machine" => tokens.next() == Some(host),
+            "default" => false,
+            "macdef" => {
+                // Macro definitions continue until an empty line, which
+                // gets lost when splitting on whitespace. Macros are rare
+                // enough that we just stop parsing here
+                break;
+            }
+            "login" | "password" | "account" => {
+                // Skip the value, which could be any token, including
+                // one of the keywords above
+                tokens.next();
+                continue;
+            }
+            _ => continue,
+        };
+
+        let entry = parse_entry_fields(&mut tokens.clone());
+        if is_match {
+            return Some(entry);
+        } else if token == "default" {
+            default_entry = Some(entry);
+        }
+    }
+
+    default_entry
+}
+
+fn parse_entry_fields<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> NetrcEntry {
+    let mut entry = NetrcEntry {
+        login: None,
+        password: None,
+    };
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "login" => entry.login = tokens.next().map(ToString::to_string),
+            "password" => entry.password = tokens.next().map(ToString::to_string),
+            "account" => {
+                tokens.next();
+            }
+            _ => break,
+        }
+    }
+
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NetrcEntry, find_entry};
+
+    fn entry(login: &str, password: &str) -> NetrcEntry {
+        NetrcEntry {
+            login: Some(login.to_string()),
+            password: Some(password.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_netrc_find_entry() {
+        let netrc = "
+            machine example.com login alice password hunter2
+            machine other.example.com
+                login bob
+                account ignored
+                password secret
+        ";
+
+        assert_eq!(
+            find_entry(netrc, "example.com"),
+            Some(entry("alice", "hunter2"))
+        );
+        assert_eq!(
+            find_entry(netrc, "other.example.com"),
+            Some(entry("bob", "secret"))
+        );
+        assert_eq!(find_entry(netrc, "missing.example.com"), None);
+    }
+
+    #[test]
+    fn test_netrc_default_entry() {
+        let netrc = "
+            default login anonymous password guest
+            machine example.com login alice password hunter2
+        ";
+
+        assert_eq!(
+            find_entry(netrc, "example.com"),
+            Some(entry("alice", "hunter2"))
+        );
+        assert_eq!(
+            find_entry(netrc, "other.example.com"),
+            Some(entry("anonymous", "guest"))
+        );
+    }
+
+    #[test]
+    fn test_netrc_keyword_values() {
+        let netrc = "
+            machine example.com login default password machine
+            machine other.example.com login macdef password account
+            default login anonymous password guest
+        ";
+
+        assert_eq!(
+            find_entry(netrc, "example.com"),
+            Some(entry("default", "machine"))
+        );
+        assert_eq!(
+            find_entry(netrc, "other.example.com"),
+            Some(entry("macdef", "account"))
+        );
+        assert_eq!(
+            find_entry(netrc, "missing.example.com"),
+            Some(entry("anonymous", "guest"))
+        );
+    }
+}
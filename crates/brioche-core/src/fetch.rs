@@ -0,0 +1,221 @@
+//! Fetch everything needed to bake a recipe ahead of time, so it can be
+//! baked later without network access. Nothing gets baked that would need
+//! to run a process: bakes are loaded from the cache when possible, and
+//! downloads and git checkouts get fetched, but any process that isn't in
+//! the cache is only reported.
+
+use std::collections::HashSet;
+
+use futures::{StreamExt as _, TryStreamExt as _};
+
+use crate::{
+    Brioche,
+    blob::BlobHash,
+    recipe::{Artifact, Recipe, RecipeHash, WithMeta},
+};
+
+const MAX_CONCURRENT_FETCHES: usize = 25;
+
+#[derive(Debug, Default)]
+pub struct FetchResults {
+    /// Bakes that were already baked locally or were loaded from the cache.
+    pub num_cached_bakes: u64,
+
+    /// Downloads and git checkouts that were fetched.
+    pub num_downloads: u64,
+
+    /// Processes that weren't found locally or in the cache, so they'll
+    /// need to be baked.
+    pub unfetched: Vec<UnfetchedRecipe>,
+}
+
+#[derive(Debug)]
+pub struct UnfetchedRecipe {
+    pub recipe_hash: RecipeHash,
+    pub description: String,
+}
+
+/// The result of fetching a single recipe.
+enum Fetched {
+    /// The recipe was baked (or is already an artifact), so only its
+    /// artifact's blobs are needed.
+    Artifact {
+        artifact: Artifact,
+        from_cache: bool,
+        downloaded: bool,
+    },
+
+    /// The recipe couldn't be fetched directly, so its inputs need to be
+    /// fetched instead.
+    Inputs {
+        inputs: Vec<Recipe>,
+        blobs: Vec<BlobHash>,
+        unfetched: Option<UnfetchedRecipe>,
+    },
+}
+
+/// Fetch every bake, download, and blob needed to bake a recipe.
+pub async fn fetch(brioche: &Brioche, recipe: &Recipe) -> anyhow::Result<FetchResults> {
+    crate::network::ensure_online(brioche, || format!("fetch recipe {}", recipe.hash()))?;
+
+    let mut results = FetchResults::default();
+    let mut visited = HashSet::new();
+    let mut artifacts = vec![];
+    let mut blobs = HashSet::new();
+
+    // Walk the recipe one level at a time, fetching each level concurrently
+    let mut level = vec![recipe.clone()];
+    while !level.is_empty() {
+        level.retain(|recipe| visited.insert(recipe.hash()));
+
+        let fetched = futures::stream::iter(level)
+            .map(|recipe| fetch_recipe(brioche, recipe))
+            .buffer_unordered(MAX_CONCURRENT_FETCHES)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        level = vec![];
+        for fetched in fetched {
+            match fetched {
+                Fetched::Artifact {
+                    artifact,
+                    from_cache,
+                    downloaded,
+                } => {
+                    artifacts.push(artifact);
+                    results.num_cached_bakes += u64::from(from_cache);
+                    results.num_downloads += u64::from(downloaded);
+                }
+                Fetched::Inputs {
+                    inputs,
+                    blobs: input_blobs,
+                    unfetched,
+                } => {
+                    level.extend(inputs);
+                    blobs.extend(input_blobs);
+                    results.unfetched.extend(unfetched);
+                }
+            }
+        }
+    }
+
+    // Fetch the contents of every artifact, including any that were
+    // loaded from the cache lazily
+    crate::references::descendent_artifact_blobs(brioche, artifacts, &mut blobs).await?;
+    crate::registry::fetch_blobs(brioche.clone(), blobs).await?;
+
+    Ok(results)
+}
+
+async fn fetch_recipe(brioche: &Brioche, recipe: Recipe) -> anyhow::Result<Fetched> {
+    let recipe_hash = recipe.hash();
+
+    if let Ok(artifact) = Artifact::try_from(recipe.clone()) {
+        return Ok(Fetched::Artifact {
+            artifact,
+            from_cache: false,
+            downloaded: false,
+        });
+    }
+
+    if let Some(artifact) = crate::bake::get_local_bake(brioche, recipe_hash).await? {
+        return Ok(Fetched::Artifact {
+            artifact,
+            from_cache: true,
+            downloaded: false,
+        });
+    }
+
+    if recipe.is_expensive_to_bake() {
+        if let Some(artifact) = load_bake_from_cache(brioche, &recipe).await? {
+            return Ok(Fetched::Artifact {
+                artifact,
+                from_cache: true,
+                downloaded: false,
+            });
+        }
+    }
+
+    match recipe {
+        Recipe::Download(_) | Recipe::GitCheckout(_) => {
+            // Fetching these doesn't run any processes, so bake them
+            let artifact = crate::bake::bake(
+                brioche,
+                WithMeta::without_meta(recipe),
+                &crate::bake::BakeScope::Anonymous,
+            )
+            .await?;
+            Ok(Fetched::Artifact {
+                artifact: artifact.value,
+                from_cache: false,
+                downloaded: true,
+            })
+        }
+        Recipe::Proxy(proxy) => {
+            let inner = proxy.inner(brioche).await?;
+            Ok(Fetched::Inputs {
+                inputs: vec![inner],
+                blobs: vec![],
+                unfetched: None,
+            })
+        }
+        Recipe::Process(_) | Recipe::CompleteProcess(_) => Ok(Fetched::Inputs {
+            inputs: inputs(&recipe),
+            blobs: crate::references::referenced_blobs(&recipe),
+            unfetched: Some(UnfetchedRecipe {
+                recipe_hash,
+                description: format!("process {recipe_hash}"),
+            }),
+        }),
+        _ => Ok(Fetched::Inputs {
+            inputs: inputs(&recipe),
+            blobs: crate::references::referenced_blobs(&recipe),
+            unfetched: None,
+        }),
+    }
+}
+
+fn inputs(recipe: &Recipe) -> Vec<Recipe> {
+    crate::references::inline_recipes(recipe)
+        .into_iter()
+        .cloned()
+        .collect()
+}
+
+/// Load a bake and its full artifact from the cache, then save the bake
+/// locally so it can be used offline.
+async fn load_bake_from_cache(
+    brioche: &Brioche,
+    recipe: &Recipe,
+) -> anyhow::Result<Option<Artifact>> {
+    let recipe_hash = recipe.hash();
+    if !brioche.cache_client.is_available(brioche) {
+        return Ok(None);
+    }
+
+    let Some(artifact_hash) = crate::cache::load_bake(brioche, recipe_hash).await? else {
+        return Ok(None);
+    };
+    let Some(artifact) = crate::cache::load_artifact(
+        brioche,
+        artifact_hash,
+        crate::reporter::job::CacheFetchKind::Bake,
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let input_json = serde_json::to_string(recipe)?;
+    let output_json = serde_json::to_string(&artifact)?;
+    crate::bake::save_bake_result(
+        brioche,
+        recipe_hash,
+        &input_json,
+        artifact.hash(),
+        &output_json,
+    )
+    .await?;
+
+    Ok(Some(artifact))
+}
@@ -1,4 +1,4 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
 
 use anyhow::Context as _;
 use config::BriocheConfig;
@@ -16,8 +16,10 @@ pub mod cache;
 pub mod config;
 pub mod download;
 pub mod encoding;
+pub mod fetch;
 pub mod fs_utils;
 pub mod input;
+pub mod network;
 pub mod object_store_utils;
 pub mod output;
 pub mod platform;
@@ -27,14 +29,15 @@ pub mod publish;
 pub mod recipe;
 pub mod references;
 pub mod registry;
+pub mod remote_execution;
 pub mod reporter;
 pub mod sandbox;
 pub mod script;
 pub mod sync;
 pub mod utils;
+pub mod vendor;
 pub mod vfs;
 
-const MAX_CONCURRENT_PROCESSES: usize = 20;
 const MAX_CONCURRENT_DOWNLOADS: usize = 20;
 
 const DEFAULT_REGISTRY_URL: &str = "https://registry.brioche.dev/";
@@ -72,18 +75,34 @@ pub struct Brioche {
 
     pub active_bakes: Arc<RwLock<bake::ActiveBakes>>,
 
-    pub process_semaphore: Arc<tokio::sync::Semaphore>,
+    pub process_scheduler: Arc<bake::ProcessScheduler>,
 
     pub download_semaphore: Arc<tokio::sync::Semaphore>,
 
     pub download_client: reqwest_middleware::ClientWithMiddleware,
 
+    pub download_config: config::DownloadConfig,
+
+    pub network_options: Arc<network::NetworkOptions>,
+
     pub registry_client: registry::RegistryClient,
 
     pub cache_client: cache::CacheClient,
 
+    /// Local cache of chunks fetched from remote caches. `None` if the
+    /// chunk cache is disabled.
+    pub chunk_cache: Option<Arc<cache::chunk_cache::ChunkCache>>,
+
+    /// When set, processes get sent to a remote worker instead of being
+    /// run locally.
+    pub remote_execution: Option<remote_execution::RemoteExecutionClient>,
+
     pub sandbox_config: config::SandboxConfig,
 
+    /// When set, anything that needs network access fails with an
+    /// [`network::OfflineError`] instead.
+    pub offline: bool,
+
     sandbox_backend: Arc<tokio::sync::OnceCell<sandbox::SandboxBackend>>,
 
     cancellation_token: tokio_util::sync::CancellationToken,
@@ -111,6 +130,7 @@ pub struct BriocheBuilder {
     reporter: Reporter,
     registry_client: Option<registry::RegistryClient>,
     cache_client: Option<cache::CacheClient>,
+    remote_execution: Option<Option<remote_execution::RemoteExecutionClient>>,
     vfs: vfs::Vfs,
     config: Option<BriocheConfig>,
     data_dir: Option<PathBuf>,
@@ -118,6 +138,7 @@ pub struct BriocheBuilder {
     self_exec_processes: bool,
     keep_temps: bool,
     sync: bool,
+    offline: Option<bool>,
 }
 
 impl BriocheBuilder {
@@ -126,6 +147,7 @@ impl BriocheBuilder {
             reporter,
             registry_client: None,
             cache_client: None,
+            remote_execution: None,
             vfs: vfs::Vfs::immutable(),
             config: None,
             data_dir: None,
@@ -133,6 +155,7 @@ impl BriocheBuilder {
             self_exec_processes: true,
             keep_temps: false,
             sync: false,
+            offline: None,
         }
     }
 
@@ -156,6 +179,16 @@ impl BriocheBuilder {
         self
     }
 
+    /// Override the remote execution worker from the config. Pass `None`
+    /// to always run processes locally.
+    pub fn remote_execution(
+        mut self,
+        remote_execution: Option<remote_execution::RemoteExecutionClient>,
+    ) -> Self {
+        self.remote_execution = Some(remote_execution);
+        self
+    }
+
     pub fn sandbox_backend(mut self, sandbox_backend: SandboxBackend) -> Self {
         self.sandbox_backend = Some(sandbox_backend);
         self
@@ -181,6 +214,11 @@ impl BriocheBuilder {
         self
     }
 
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = Some(offline);
+        self
+    }
+
     pub async fn build(self) -> anyhow::Result<Brioche> {
         let dirs = directories::ProjectDirs::from("dev", "brioche", "brioche")
             .context("failed to get Brioche directories (is $HOME set?)")?;
@@ -218,36 +256,50 @@ impl BriocheBuilder {
 
         tracing::debug!("finished running database migrations");
 
-        let download_retry_policy = reqwest_retry::policies::ExponentialBackoff::builder()
-            .retry_bounds(
-                std::time::Duration::from_secs(1),
-                std::time::Duration::from_secs(30),
-            )
-            .build_with_max_retries(5);
-        let download_retry_middleware =
-            reqwest_retry::RetryTransientMiddleware::new_with_policy(download_retry_policy);
-        let download_client = reqwest::Client::builder().user_agent(USER_AGENT).build()?;
-        let download_client = reqwest_middleware::ClientBuilder::new(download_client)
-            .with(download_retry_middleware)
-            .build();
-
-        let registry_client = self.registry_client.unwrap_or_else(|| {
-            let registry_password = std::env::var("BRIOCHE_REGISTRY_PASSWORD").ok();
-            let registry_auth = match registry_password {
-                Some(password) => registry::RegistryAuthentication::Admin { password },
-                None => registry::RegistryAuthentication::Anonymous,
-            };
-            let registry_url = config.registry_url.clone().unwrap_or_else(|| {
-                DEFAULT_REGISTRY_URL
-                    .parse()
-                    .expect("failed to parse default registry URL")
-            });
-            registry::RegistryClient::new(registry_url, registry_auth)
-        });
+        // Downloads handle retries themselves (see `download::download`), so
+        // they can resume partial downloads
+        let network_options = network::NetworkOptions::load(&config.network)
+            .await
+            .context("failed to load network config")?;
+
+        let download_client = network_options
+            .apply_to_reqwest(reqwest::Client::builder().user_agent(USER_AGENT))?
+            .build()?;
+        let download_client = reqwest_middleware::ClientBuilder::new(download_client).build();
+
+        let offline = match self.offline {
+            Some(offline) => offline,
+            None => match std::env::var_os("BRIOCHE_OFFLINE") {
+                Some(value) if value.to_str() == Some("true") => true,
+                Some(value) if value.to_str() == Some("false") => false,
+                Some(value) => {
+                    anyhow::bail!("invalid value for $BRIOCHE_OFFLINE: {value:?}");
+                }
+                None => config.offline,
+            },
+        };
+
+        let registry_client = match self.registry_client {
+            _ if offline => registry::RegistryClient::Offline,
+            Some(registry_client) => registry_client,
+            None => {
+                let registry_password = std::env::var("BRIOCHE_REGISTRY_PASSWORD").ok();
+                let registry_auth = match registry_password {
+                    Some(password) => registry::RegistryAuthentication::Admin { password },
+                    None => registry::RegistryAuthentication::Anonymous,
+                };
+                let registry_url = config.registry_url.clone().unwrap_or_else(|| {
+                    DEFAULT_REGISTRY_URL
+                        .parse()
+                        .expect("failed to parse default registry URL")
+                });
+                registry::RegistryClient::new(registry_url, registry_auth, &network_options)?
+            }
+        };
         let cache_client = match self.cache_client {
             Some(cache_client) => cache_client,
             None => {
-                let cache_config = match std::env::var_os("BRIOCHE_CACHE_URL") {
+                let cache_configs = match std::env::var_os("BRIOCHE_CACHE_URL") {
                     Some(url) => {
                         let url = url.to_str().ok_or_else(|| {
                             anyhow::anyhow!("invalid URL for $BRIOCHE_CACHE_URL: {url:?}")
@@ -285,19 +337,78 @@ impl BriocheBuilder {
                             }
                             None => None,
                         };
-                        Some(config::CacheConfig {
+                        let trusted_keys = match std::env::var_os("BRIOCHE_CACHE_TRUSTED_KEYS") {
+                            Some(value) => {
+                                let value = value.to_str().ok_or_else(|| {
+                                    anyhow::anyhow!(
+                                        "invalid value for $BRIOCHE_CACHE_TRUSTED_KEYS: {value:?}"
+                                    )
+                                })?;
+                                value
+                                    .split(',')
+                                    .map(str::trim)
+                                    .filter(|key| !key.is_empty())
+                                    .map(str::parse)
+                                    .collect::<anyhow::Result<Vec<_>>>()
+                                    .context("invalid value for $BRIOCHE_CACHE_TRUSTED_KEYS")?
+                            }
+                            None => vec![],
+                        };
+                        let signing_key_path =
+                            std::env::var_os("BRIOCHE_CACHE_SIGNING_KEY_PATH").map(PathBuf::from);
+                        let auth = std::env::var_os("BRIOCHE_CACHE_AUTH_TOKEN").map(|_| {
+                            config::CacheAuthConfig::Bearer {
+                                token: config::SecretSource::Env(
+                                    "BRIOCHE_CACHE_AUTH_TOKEN".to_string(),
+                                ),
+                            }
+                        });
+                        vec![config::CacheConfig {
                             url,
                             max_concurrent_operations,
                             read_only,
                             allow_http,
-                        })
+                            priority: 0,
+                            trusted_keys,
+                            signing_key_path,
+                            auth,
+                            headers: BTreeMap::new(),
+                            compression: config::CacheCompressionConfig::default(),
+                        }]
                     }
-                    None => config.cache.clone(),
+                    None => config.cache.iter().chain(&config.caches).cloned().collect(),
                 };
-                cache::cache_client_from_config_or_default(cache_config.as_ref()).await?
+                let mut cache_client =
+                    cache::cache_client_from_configs(&cache_configs, &network_options).await?;
+                if config.eager_cache_fetch {
+                    cache_client.lazy_fetch = false;
+                }
+                cache_client
             }
         };
 
+        let chunk_cache = match config.chunk_cache.max_size_mib {
+            0 => None,
+            max_size_mib => Some(Arc::new(cache::chunk_cache::ChunkCache::new(
+                data_dir.join("chunk-cache"),
+                max_size_mib.saturating_mul(1024 * 1024),
+            ))),
+        };
+
+        let remote_execution = match self.remote_execution {
+            Some(remote_execution) => remote_execution,
+            None => config
+                .remote_execution
+                .as_ref()
+                .map(|remote_execution| {
+                    remote_execution::RemoteExecutionClient::new(
+                        remote_execution.url.clone(),
+                        &network_options,
+                    )
+                })
+                .transpose()?,
+        };
+
         let (sync_tx, mut sync_rx) = tokio::sync::mpsc::channel(1000);
 
         // Start a task that listens for sync messages and syncs to the
@@ -339,6 +450,10 @@ impl BriocheBuilder {
             .instrument(tracing::Span::current()),
         );
 
+        let max_process_jobs = config.processes.max_jobs.unwrap_or_else(|| {
+            bake::scheduler::default_max_jobs(config.processes.memory_per_job_mib)
+        });
+
         let cancellation_token = tokio_util::sync::CancellationToken::new();
         let task_tracker = tokio_util::task::TaskTracker::new();
 
@@ -352,12 +467,17 @@ impl BriocheBuilder {
             sync_tx: Arc::new(sync_tx),
             cached_recipes: Arc::new(RwLock::new(bake::CachedRecipes::default())),
             active_bakes: Arc::new(RwLock::new(bake::ActiveBakes::default())),
-            process_semaphore: Arc::new(tokio::sync::Semaphore::new(MAX_CONCURRENT_PROCESSES)),
+            process_scheduler: Arc::new(bake::ProcessScheduler::new(max_process_jobs)),
             download_semaphore: Arc::new(tokio::sync::Semaphore::new(MAX_CONCURRENT_DOWNLOADS)),
             download_client,
+            download_config: config.download.clone(),
+            network_options: Arc::new(network_options),
             registry_client,
             cache_client,
+            chunk_cache,
+            remote_execution,
             sandbox_config: config.sandbox.clone(),
+            offline,
             sandbox_backend: Arc::new(tokio::sync::OnceCell::new_with(self.sandbox_backend)),
             cancellation_token,
             task_tracker,
@@ -383,7 +503,9 @@ pub enum SyncMessage {
 pub struct RuntimeFiles;
 
 #[serde_with::serde_as]
-#[derive(Debug, Clone, Hash, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Hash, serde::Serialize, serde::Deserialize, PartialEq, Eq, PartialOrd, Ord,
+)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum Hash {
@@ -391,18 +513,68 @@ pub enum Hash {
         #[serde_as(as = "serde_with::hex::Hex")]
         value: Vec<u8>,
     },
+    Sha512 {
+        #[serde_as(as = "serde_with::hex::Hex")]
+        value: Vec<u8>,
+    },
+    Blake3 {
+        #[serde_as(as = "serde_with::hex::Hex")]
+        value: Vec<u8>,
+    },
 }
 
 impl std::fmt::Display for Hash {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.algorithm(), hex::encode(self.value()))
+    }
+}
+
+impl std::str::FromStr for Hash {
+    type Err = anyhow::Error;
+
+    /// Parse a hash in the same format used by [`std::fmt::Display`],
+    /// e.g. `sha256:<hex>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, value) = s
+            .split_once(':')
+            .with_context(|| format!("invalid hash {s:?}, expected <algorithm>:<hex>"))?;
+        let value = hex::decode(value).with_context(|| format!("invalid hex in hash {s:?}"))?;
+
+        let (hash, expected_len) = match algorithm {
+            "sha256" => (Hash::Sha256 { value }, 32),
+            "sha512" => (Hash::Sha512 { value }, 64),
+            "blake3" => (Hash::Blake3 { value }, blake3::OUT_LEN),
+            _ => anyhow::bail!("unsupported hash algorithm {algorithm:?} in hash {s:?}"),
+        };
+        anyhow::ensure!(
+            hash.value().len() == expected_len,
+            "invalid {algorithm} hash {s:?}: expected {expected_len} bytes"
+        );
+
+        Ok(hash)
+    }
+}
+
+impl Hash {
+    pub const fn algorithm(&self) -> &'static str {
         match self {
-            Hash::Sha256 { value } => write!(f, "sha256:{}", hex::encode(value)),
+            Hash::Sha256 { .. } => "sha256",
+            Hash::Sha512 { .. } => "sha512",
+            Hash::Blake3 { .. } => "blake3",
+        }
+    }
+
+    pub fn value(&self) -> &[u8] {
+        match self {
+            Hash::Sha256 { value } | Hash::Sha512 { value } | Hash::Blake3 { value } => value,
         }
     }
 }
 
 pub enum Hasher {
     Sha256(sha2::Sha256),
+    Sha512(sha2::Sha512),
+    Blake3(Box<blake3::Hasher>),
 }
 
 impl Hasher {
@@ -410,15 +582,29 @@ impl Hasher {
         Self::Sha256(sha2::Sha256::new())
     }
 
+    pub fn new_sha512() -> Self {
+        Self::Sha512(sha2::Sha512::new())
+    }
+
+    pub fn new_blake3() -> Self {
+        Self::Blake3(Box::new(blake3::Hasher::new()))
+    }
+
     pub fn for_hash(hash: &Hash) -> Self {
         match hash {
-            Hash::Sha256 { .. } => Self::Sha256(sha2::Sha256::new()),
+            Hash::Sha256 { .. } => Self::new_sha256(),
+            Hash::Sha512 { .. } => Self::new_sha512(),
+            Hash::Blake3 { .. } => Self::new_blake3(),
         }
     }
 
     pub fn update(&mut self, bytes: &[u8]) {
         match self {
             Self::Sha256(hasher) => hasher.update(bytes),
+            Self::Sha512(hasher) => hasher.update(bytes),
+            Self::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
         }
     }
 
@@ -430,6 +616,18 @@ impl Hasher {
                     value: hash.as_slice().to_vec(),
                 })
             }
+            Self::Sha512(hasher) => {
+                let hash = hasher.finalize();
+                Ok(Hash::Sha512 {
+                    value: hash.as_slice().to_vec(),
+                })
+            }
+            Self::Blake3(hasher) => {
+                let hash = hasher.finalize();
+                Ok(Hash::Blake3 {
+                    value: hash.as_bytes().to_vec(),
+                })
+            }
         }
     }
 }
@@ -0,0 +1,241 @@
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+
+use crate::config::NetworkConfig;
+
+/// Network settings applied to every client Brioche uses to talk to the
+/// outside world: the download client, the registry client, the cache's
+/// object store, and git transports.
+#[derive(Clone, Default)]
+pub struct NetworkOptions {
+    ca_certificates_path: Option<PathBuf>,
+    ca_certificates: Vec<String>,
+    proxy: Option<url::Url>,
+    no_proxy: Vec<String>,
+    client_identity: Option<Vec<u8>>,
+    client_identity_hosts: Vec<String>,
+}
+
+impl NetworkOptions {
+    pub async fn load(config: &NetworkConfig) -> anyhow::Result<Self> {
+        let ca_certificates = match &config.ca_certificates {
+            Some(path) => {
+                let pem = tokio::fs::read_to_string(path).await.with_context(|| {
+                    format!("failed to read CA certificates from {}", path.display())
+                })?;
+                let certificates = split_pem_certificates(&pem);
+                anyhow::ensure!(
+                    !certificates.is_empty(),
+                    "no CA certificates found in {}",
+                    path.display()
+                );
+                certificates
+            }
+            None => vec![],
+        };
+
+        let client_identity = match &config.client_certificate {
+            Some(client_certificate) => {
+                let mut identity = tokio::fs::read(&client_certificate.certificate)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "failed to read client certificate from {}",
+                            client_certificate.certificate.display()
+                        )
+                    })?;
+                identity.push(b'\n');
+                let key = tokio::fs::read(&client_certificate.key)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "failed to read client key from {}",
+                            client_certificate.key.display()
+                        )
+                    })?;
+                identity.extend_from_slice(&key);
+
+                // Validate the identity up front, so a bad certificate
+                // is reported on startup instead of on the first request
+                reqwest::Identity::from_pem(&identity)
+                    .context("invalid client certificate or key")?;
+
+                Some(identity)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            ca_certificates_path: config.ca_certificates.clone(),
+            ca_certificates,
+            proxy: config.proxy.clone(),
+            no_proxy: config.no_proxy.clone(),
+            client_identity,
+            client_identity_hosts: config
+                .client_certificate
+                .as_ref()
+                .map(|client_certificate| client_certificate.hosts.clone())
+                .unwrap_or_default(),
+        })
+    }
+
+    pub fn apply_to_reqwest(
+        &self,
+        mut builder: reqwest::ClientBuilder,
+    ) -> anyhow::Result<reqwest::ClientBuilder> {
+        for certificate in &self.ca_certificates {
+            let certificate = reqwest::Certificate::from_pem(certificate.as_bytes())
+                .context("invalid CA certificate")?;
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        if let Some(proxy) = &self.proxy {
+            let no_proxy = reqwest::NoProxy::from_string(&self.no_proxy.join(","));
+            let proxy = reqwest::Proxy::all(proxy.as_str())
+                .with_context(|| format!("invalid proxy URL {proxy}"))?
+                .no_proxy(no_proxy);
+            builder = builder.proxy(proxy);
+        }
+
+        if let Some(client_identity) = &self.client_identity {
+            let identity = reqwest::Identity::from_pem(client_identity)
+                .context("invalid client certificate or key")?;
+            builder = builder.identity(identity);
+        }
+
+        Ok(builder)
+    }
+
+    pub fn apply_to_object_store(
+        &self,
+        url: &url::Url,
+        mut options: object_store::ClientOptions,
+    ) -> anyhow::Result<object_store::ClientOptions> {
+        for certificate in &self.ca_certificates {
+            let certificate = object_store::Certificate::from_pem(certificate.as_bytes())
+                .context("invalid CA certificate")?;
+            options = options.with_root_certificate(certificate);
+        }
+
+        if let Some(proxy) = &self.proxy {
+            options = options.with_proxy_url(proxy.as_str());
+            if !self.no_proxy.is_empty() {
+                options = options.with_proxy_excludes(self.no_proxy.join(","));
+            }
+        }
+
+        // The object store client can't present client certificates, so
+        // fail instead of connecting without one to a host that needs it
+        anyhow::ensure!(
+            !self.requires_client_identity(url.host_str()),
+            "client certificates are not supported for remote caches, but `network.client_certificate` is required for {url}"
+        );
+
+        Ok(options)
+    }
+
+    /// Get the HTTP options for a git transport. Returns an error for HTTP
+    /// repositories on a host that requires the client certificate, since
+    /// gix's HTTP transport doesn't support client certificates.
+    pub fn gix_http_options(
+        &self,
+        repository: &gix::Url,
+    ) -> anyhow::Result<gix::protocol::transport::client::http::Options> {
+        let is_http = matches!(
+            repository.scheme,
+            gix::url::Scheme::Http | gix::url::Scheme::Https
+        );
+        anyhow::ensure!(
+            !(is_http && self.requires_client_identity(repository.host())),
+            "client certificates are not supported for git repositories over HTTP, but `network.client_certificate` is required for {}",
+            repository.to_bstring()
+        );
+
+        Ok(gix::protocol::transport::client::http::Options {
+            proxy: self.proxy.as_ref().map(ToString::to_string),
+            no_proxy: (!self.no_proxy.is_empty()).then(|| self.no_proxy.join(",")),
+            ssl_ca_info: self.ca_certificates_path.clone(),
+            ..Default::default()
+        })
+    }
+
+    /// Returns true if a client certificate is configured and `host` is
+    /// listed as requiring it.
+    fn requires_client_identity(&self, host: Option<&str>) -> bool {
+        let Some(host) = host else {
+            return false;
+        };
+        if self.client_identity.is_none() {
+            return false;
+        }
+
+        self.client_identity_hosts.iter().any(|pattern| {
+            if let Some(suffix) = pattern.strip_prefix('.') {
+                host.eq_ignore_ascii_case(suffix)
+                    || host
+                        .to_ascii_lowercase()
+                        .ends_with(&pattern.to_ascii_lowercase())
+            } else {
+                host.eq_ignore_ascii_case(pattern)
+            }
+        })
+    }
+}
+
+/// Split a PEM bundle into each of its certificates.
+fn split_pem_certificates(pem: &str) -> Vec<String> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+
+    let mut certificates = vec![];
+    let mut remaining = pem;
+    while let Some(start) = remaining.find(BEGIN) {
+        let Some(end) = remaining[start..].find(END) else {
+            break;
+        };
+        let end = start + end + END.len();
+        certificates.push(format!("{}\n", &remaining[start..end]));
+        remaining = &remaining[end..];
+    }
+
+    certificates
+}
+
+/// Returned when something needs network access while Brioche is running
+/// in offline mode. Each entry describes one network input that was needed.
+#[derive(Debug, Clone, thiserror::Error)]
+pub struct OfflineError {
+    pub needed: Vec<String>,
+}
+
+impl std::fmt::Display for OfflineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.needed[..] {
+            [needed] => write!(f, "offline: {needed}"),
+            needed => {
+                write!(f, "offline: {} network inputs needed:", needed.len())?;
+                for needed in needed {
+                    write!(f, "\n- {needed}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Return an [`OfflineError`] if Brioche is running in offline mode.
+/// `needed` describes what the network was needed for.
+pub fn ensure_online(
+    brioche: &crate::Brioche,
+    needed: impl FnOnce() -> String,
+) -> anyhow::Result<()> {
+    if brioche.offline {
+        return Err(OfflineError {
+            needed: vec![needed()],
+        }
+        .into());
+    }
+
+    Ok(())
+}
@@ -213,3 +213,32 @@ pub fn apply_s3_config(
 
     builder
 }
+
+/// Split a bucket URL like `s3://bucket/prefix` into its bucket (or
+/// container) name and the prefix within the bucket.
+pub fn bucket_and_prefix(url: &url::Url) -> anyhow::Result<(&str, String)> {
+    let bucket = url
+        .host_str()
+        .filter(|bucket| !bucket.is_empty())
+        .with_context(|| format!("{}:// URL must include a bucket name", url.scheme()))?;
+    let prefix = url.path().trim_start_matches('/').to_string();
+    Ok((bucket, prefix))
+}
+
+/// Create a builder for a Google Cloud Storage bucket. Credentials are
+/// found the same way as other Google Cloud tools: from the `GOOGLE_*`
+/// environment variables, then application default credentials (from
+/// `gcloud auth application-default login`), then the instance metadata
+/// server.
+pub fn gcs_builder(bucket: &str) -> object_store::gcp::GoogleCloudStorageBuilder {
+    object_store::gcp::GoogleCloudStorageBuilder::from_env().with_bucket_name(bucket)
+}
+
+/// Create a builder for an Azure Blob Storage container. The storage
+/// account and credentials are read from the `AZURE_*` environment
+/// variables (such as `AZURE_STORAGE_ACCOUNT_NAME`), falling back to a
+/// managed identity. Setting `AZURE_STORAGE_USE_EMULATOR=true` uses a
+/// local Azurite emulator instead.
+pub fn azure_builder(container: &str) -> object_store::azure::MicrosoftAzureBuilder {
+    object_store::azure::MicrosoftAzureBuilder::from_env().with_container_name(container)
+}
@@ -713,13 +713,14 @@ async fn load_project_inner(
         .map(|module| (module.project_subpath.clone(), module.file_id))
         .collect();
     let mut statics = HashMap::new();
+    let mut offline_needed = vec![];
     for module in project_analysis.local_modules.values() {
         let mut module_statics = BTreeMap::new();
         for static_ in &module.statics {
             // Only resolve the static if we need a fully valid project
             match validation {
                 ProjectValidation::Standard => {
-                    let recipe_hash = resolve_static(
+                    let result = resolve_static(
                         brioche,
                         &path,
                         module,
@@ -728,7 +729,20 @@ async fn load_project_inner(
                         lockfile.as_ref(),
                         &mut new_lockfile,
                     )
-                    .await?;
+                    .await;
+
+                    // When offline, keep going so every missing network
+                    // input gets reported together
+                    let recipe_hash = match result {
+                        Ok(recipe_hash) => recipe_hash,
+                        Err(error) => match error.downcast_ref::<crate::network::OfflineError>() {
+                            Some(offline_error) => {
+                                offline_needed.extend(offline_error.needed.iter().cloned());
+                                continue;
+                            }
+                            None => return Err(error),
+                        },
+                    };
                     module_statics.insert(static_.clone(), Some(recipe_hash));
                 }
                 ProjectValidation::Minimal => {
@@ -742,6 +756,13 @@ async fn load_project_inner(
         }
     }
 
+    if !offline_needed.is_empty() {
+        return Err(crate::network::OfflineError {
+            needed: offline_needed,
+        }
+        .into());
+    }
+
     let project = Project {
         definition: project_analysis.definition,
         dependencies,
@@ -1303,8 +1324,23 @@ async fn resolve_static(
 
             Ok(StaticOutput::RecipeHash(recipe_hash))
         }
-        StaticQuery::Download { url } => {
-            let current_download_hash = lockfile.and_then(|lockfile| lockfile.downloads.get(url));
+        StaticQuery::Download { url, hash } => {
+            let lockfile_download_hash = lockfile.and_then(|lockfile| lockfile.downloads.get(url));
+
+            // An explicit hash takes precedence over the lockfile, but the
+            // lockfile still needs to match when it can't be updated
+            let current_download_hash = match hash {
+                Some(hash) => {
+                    if locking == ProjectLocking::Locked {
+                        anyhow::ensure!(
+                            lockfile_download_hash == Some(hash),
+                            "hash for download '{url}' in lockfile does not match {hash}"
+                        );
+                    }
+                    Some(hash)
+                }
+                None => lockfile_download_hash,
+            };
 
             let download_hash: crate::Hash;
             let blob_hash: Option<crate::blob::BlobHash>;
@@ -1354,6 +1390,7 @@ async fn resolve_static(
             let download_recipe = crate::recipe::Recipe::Download(crate::recipe::DownloadRecipe {
                 hash: download_hash.clone(),
                 url: url.clone(),
+                mirrors: vec![],
             });
             let download_recipe_hash = download_recipe.hash();
 
@@ -1409,7 +1446,7 @@ async fn resolve_static(
                 (Some(commit), _) => commit.clone(),
                 (None, ProjectLocking::Unlocked) => {
                     // Fetch the current commit hash of the git ref from the repo
-                    crate::download::fetch_git_commit_for_ref(repository, ref_)
+                    crate::download::fetch_git_commit_for_ref(brioche, repository, ref_)
                         .await
                         .with_context(|| {
                             format!("failed to fetch ref '{ref_}' from git repo '{repository}'")
@@ -1770,7 +1807,7 @@ fn project_lockfile(project: &Project) -> Lockfile {
             StaticQuery::Include(_) | StaticQuery::Glob { .. } => {
                 continue;
             }
-            StaticQuery::Download { url } => {
+            StaticQuery::Download { url, .. } => {
                 let Some(StaticOutput::Kind(StaticOutputKind::Download { hash })) = output else {
                     continue;
                 };
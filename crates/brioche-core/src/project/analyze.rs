@@ -41,8 +41,17 @@ pub enum ImportAnalysis {
 #[serde(rename_all = "snake_case")]
 pub enum StaticQuery {
     Include(StaticInclude),
-    Glob { patterns: Vec<String> },
-    Download { url: url::Url },
+    Glob {
+        patterns: Vec<String>,
+    },
+    Download {
+        url: url::Url,
+
+        /// The expected hash of the download, if one was passed explicitly.
+        /// Otherwise, the hash is recorded in the lockfile.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        hash: Option<crate::Hash>,
+    },
     GitRef(GitRefOptions),
 }
 
@@ -54,12 +63,16 @@ impl StaticQuery {
         let recipe_hash = match output {
             StaticOutput::RecipeHash(hash) => Some(*hash),
             StaticOutput::Kind(StaticOutputKind::Download { hash }) => {
-                let Self::Download { url: download_url } = self else {
+                let Self::Download {
+                    url: download_url, ..
+                } = self
+                else {
                     anyhow::bail!("expected download query");
                 };
                 let recipe = crate::recipe::Recipe::Download(crate::recipe::DownloadRecipe {
                     url: download_url.clone(),
                     hash: hash.clone(),
+                    mirrors: vec![],
                 });
                 Some(recipe.hash())
             }
@@ -73,6 +86,13 @@ impl StaticQuery {
     }
 }
 
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DownloadOptions {
+    /// A hash in the form `<algorithm>:<hex>`, such as `sha512:...`
+    hash: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
 pub struct GitRefOptions {
     pub repository: url::Url,
@@ -543,32 +563,42 @@ where
                 "download" => {
                     // Get the arguments
                     let args = call_expr.arguments()?.args();
-                    let args = args
-                        .iter()
-                        .map(|arg| arg_to_string_literal(arg, env))
-                        .map(|arg| {
-                            arg.with_context(|| {
-                                format!("{location}: invalid arg to Brioche.download")
-                            })
-                        })
-                        .collect::<anyhow::Result<Vec<_>>>()?;
-
-                    // Ensure there's exactly one argument
-                    let url = match &*args {
-                        [url] => url.clone(),
-                        _ => {
-                            anyhow::bail!(
-                                "{location}: Brioche.download() must take exactly one argument",
-                            );
-                        }
+                    let mut args = args.iter();
+
+                    // Ensure there's a URL argument plus optional options
+                    let (Some(url), options, None) = (args.next(), args.next(), args.next())
+                    else {
+                        anyhow::bail!(
+                            "{location}: Brioche.download() must take one or two arguments",
+                        );
                     };
 
                     // Parse the URL
+                    let url = arg_to_string_literal(url, env)
+                        .with_context(|| format!("{location}: invalid arg to Brioche.download"))?;
                     let url = url.parse().with_context(|| {
                         format!("{location}: invalid URL for Brioche.download")
                     })?;
 
-                    Ok(Some(StaticQuery::Download { url }))
+                    // Parse the options
+                    let hash = match options {
+                        Some(options) => {
+                            let options = arg_to_json(options, env).with_context(|| {
+                                format!("{location}: invalid arg to Brioche.download")
+                            })?;
+                            let options: DownloadOptions = serde_json::from_value(options)
+                                .with_context(|| {
+                                    format!("{location}: invalid options for Brioche.download, expected an object with the key `hash`")
+                                })?;
+                            let hash = options.hash.parse().with_context(|| {
+                                format!("{location}: invalid hash for Brioche.download")
+                            })?;
+                            Some(hash)
+                        }
+                        None => None,
+                    };
+
+                    Ok(Some(StaticQuery::Download { url, hash }))
                 }
                 "gitRef" => {
                     // Get the arguments
@@ -110,6 +110,29 @@ pub enum Recipe {
     Sync {
         recipe: Box<WithMeta<Recipe>>,
     },
+    #[serde(rename_all = "camelCase")]
+    Substitute(Substitute),
+    #[serde(rename_all = "camelCase")]
+    GitCheckout(GitCheckout),
+}
+
+thread_local! {
+    static SERIALIZING_FOR_HASH: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Returns true while a recipe is being serialized to compute its hash.
+/// Fields that only affect how a recipe is baked (but not its output)
+/// are skipped when this is set.
+fn is_serializing_for_hash() -> bool {
+    SERIALIZING_FOR_HASH.get()
+}
+
+fn skip_serializing_mirrors(mirrors: &[url::Url]) -> bool {
+    mirrors.is_empty() || is_serializing_for_hash()
+}
+
+fn skip_serializing_jobs(jobs: &Option<u32>) -> bool {
+    jobs.is_none() || is_serializing_for_hash()
 }
 
 impl Recipe {
@@ -125,7 +148,7 @@ impl Recipe {
             }
         }
 
-        let hash = RecipeHash::from_serializable(self)?;
+        let hash = RecipeHash::from_serializable_for_hash(self)?;
         {
             let mut hashes_writer = hashes
                 .write()
@@ -149,7 +172,8 @@ impl Recipe {
             Recipe::Download(_)
             | Recipe::Process(_)
             | Recipe::CompleteProcess(_)
-            | Recipe::Sync { .. } => true,
+            | Recipe::Sync { .. }
+            | Recipe::GitCheckout(_) => true,
             Recipe::File { .. }
             | Recipe::Directory(_)
             | Recipe::Symlink { .. }
@@ -165,7 +189,8 @@ impl Recipe {
             | Recipe::SetPermissions { .. }
             | Recipe::CollectReferences { .. }
             | Recipe::AttachResources { .. }
-            | Recipe::Proxy(_) => false,
+            | Recipe::Proxy(_)
+            | Recipe::Substitute(_) => false,
         }
     }
 }
@@ -489,8 +514,14 @@ impl CreateDirectory {
 pub struct DownloadRecipe {
     pub url: url::Url,
     pub hash: Hash,
+
+    /// Additional URLs to try in order if downloading from `url` fails.
+    /// These don't affect the recipe's hash.
+    #[serde(default, skip_serializing_if = "skip_serializing_mirrors")]
+    pub mirrors: Vec<url::Url>,
 }
 
+#[serde_with::serde_as]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Unarchive {
@@ -498,6 +529,50 @@ pub struct Unarchive {
     pub archive: ArchiveFormat,
     #[serde(default)]
     pub compression: CompressionFormat,
+
+    /// Remove this many leading path components from each archive entry.
+    /// Entries with fewer components are skipped.
+    #[serde(default, skip_serializing_if = "crate::utils::is_default")]
+    pub strip_components: u32,
+
+    /// Only extract entries matching one of these glob patterns (after
+    /// stripping components). Matching directories are extracted with all
+    /// of their contents. When empty, all entries are extracted.
+    #[serde_as(as = "BTreeSet<TickEncoded>")]
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub include: BTreeSet<BString>,
+}
+
+/// Replace literal placeholders within a file's contents. Each placeholder
+/// is replaced by its rendered template, where templates may only contain
+/// literals and inputs. Inputs are attached as resources of the file and
+/// render as their path within the resource directory. Other paths aren't
+/// allowed, since they would make the output depend on the machine it was
+/// baked on.
+#[serde_with::serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Substitute {
+    pub file: Box<WithMeta<Recipe>>,
+
+    #[serde_as(as = "BTreeMap<TickEncoded, _>")]
+    pub replacements: BTreeMap<BString, ProcessTemplate>,
+}
+
+/// Check out the tree of a commit from a git repository. The result is a
+/// directory containing the commit's files, without a `.git` directory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitCheckout {
+    pub repository: url::Url,
+
+    /// The full hex-encoded ID of the commit to check out.
+    pub commit: String,
+
+    /// Recursively check out submodules at the commits recorded in the
+    /// tree. When disabled, submodules are left as empty directories.
+    #[serde(default, skip_serializing_if = "crate::utils::is_default")]
+    pub submodules: bool,
 }
 
 #[serde_with::serde_as]
@@ -530,6 +605,11 @@ pub struct ProcessRecipe {
 
     #[serde(default, skip_serializing_if = "crate::utils::is_default")]
     pub networking: bool,
+
+    /// How many jobs (roughly, CPU cores) the process expects to use.
+    /// Only used for scheduling, so it's excluded from the recipe's hash.
+    #[serde(default, skip_serializing_if = "skip_serializing_jobs")]
+    pub jobs: Option<u32>,
 }
 
 #[serde_with::serde_as]
@@ -560,6 +640,11 @@ pub struct CompleteProcessRecipe {
 
     #[serde(default, skip_serializing_if = "crate::utils::is_default")]
     pub networking: bool,
+
+    /// How many jobs (roughly, CPU cores) the process expects to use.
+    /// Only used for scheduling, so it's excluded from the recipe's hash.
+    #[serde(default, skip_serializing_if = "skip_serializing_jobs")]
+    pub jobs: Option<u32>,
 }
 
 impl TryFrom<ProcessRecipe> for CompleteProcessRecipe {
@@ -576,6 +661,7 @@ impl TryFrom<ProcessRecipe> for CompleteProcessRecipe {
             platform,
             is_unsafe,
             networking,
+            jobs,
         } = recipe;
 
         anyhow::ensure!(
@@ -606,6 +692,7 @@ impl TryFrom<ProcessRecipe> for CompleteProcessRecipe {
             platform,
             is_unsafe,
             networking,
+            jobs,
         })
     }
 }
@@ -1058,7 +1145,9 @@ impl TryFrom<Recipe> for Artifact {
             | Recipe::SetPermissions { .. }
             | Recipe::CollectReferences { .. }
             | Recipe::AttachResources { .. }
-            | Recipe::Proxy { .. } => Err(RecipeIncomplete),
+            | Recipe::Proxy { .. }
+            | Recipe::Substitute(_)
+            | Recipe::GitCheckout(_) => Err(RecipeIncomplete),
         }
     }
 }
@@ -1157,6 +1246,19 @@ impl RecipeHash {
         Ok(Self(hash))
     }
 
+    /// Hash a recipe, skipping fields that don't affect its output (see
+    /// [`is_serializing_for_hash`]). Nested recipes are serialized the
+    /// same way, so these fields are skipped at every level.
+    fn from_serializable_for_hash<V>(value: &V) -> anyhow::Result<Self>
+    where
+        V: serde::Serialize,
+    {
+        let previous = SERIALIZING_FOR_HASH.replace(true);
+        let result = Self::from_serializable(value);
+        SERIALIZING_FOR_HASH.set(previous);
+        result
+    }
+
     pub fn as_bytes(&self) -> &[u8; blake3::OUT_LEN] {
         self.0.as_bytes()
     }
@@ -1374,6 +1476,11 @@ impl TryFrom<ProcessTemplateComponent> for CompleteProcessTemplateComponent {
 pub enum ArchiveFormat {
     Tar,
     Zip,
+    Ar,
+
+    /// A cpio archive, in either the "newc" (SVR4) or the portable "odc"
+    /// format. The format is detected from each entry's header.
+    Cpio,
 }
 
 #[derive(
@@ -1383,24 +1490,63 @@ pub enum ArchiveFormat {
 pub enum CompressionFormat {
     #[default]
     None,
+    Brotli,
     Bzip2,
     Gzip,
+    Lz4,
+    Lzip,
     Xz,
     Zstd,
 }
 
 impl CompressionFormat {
+    /// Wrap `input` with an async decoder. Returns an error for formats
+    /// that can only be decoded synchronously, in which case
+    /// [`Self::decompress_blocking`] should be used instead.
     pub fn decompress(
         &self,
         input: impl tokio::io::AsyncBufRead + Unpin + Send + 'static,
-    ) -> Box<dyn tokio::io::AsyncRead + Unpin + Send> {
-        match self {
+    ) -> anyhow::Result<Box<dyn tokio::io::AsyncRead + Unpin + Send>> {
+        let decoder: Box<dyn tokio::io::AsyncRead + Unpin + Send> = match self {
             Self::None => Box::new(input),
+            Self::Brotli => Box::new(async_compression::tokio::bufread::BrotliDecoder::new(input)),
             Self::Bzip2 => Box::new(async_compression::tokio::bufread::BzDecoder::new(input)),
             Self::Gzip => Box::new(async_compression::tokio::bufread::GzipDecoder::new(input)),
             Self::Xz => Box::new(async_compression::tokio::bufread::XzDecoder::new(input)),
             Self::Zstd => Box::new(async_compression::tokio::bufread::ZstdDecoder::new(input)),
-        }
+            Self::Lz4 | Self::Lzip => {
+                anyhow::bail!("{self:?} compression can only be decompressed synchronously");
+            }
+        };
+        Ok(decoder)
+    }
+
+    /// Wrap `input` with a synchronous decoder. This supports every
+    /// compression format, but the returned reader must only be used from
+    /// a blocking context (e.g. within [`tokio::task::spawn_blocking`]).
+    pub fn decompress_blocking(
+        &self,
+        input: impl tokio::io::AsyncBufRead + Unpin + Send + 'static,
+    ) -> anyhow::Result<Box<dyn std::io::Read + Send>> {
+        let decoder: Box<dyn std::io::Read + Send> = match self {
+            Self::Lz4 => {
+                let input = tokio_util::io::SyncIoBridge::new(input);
+                Box::new(lz4_flex::frame::FrameDecoder::new(input))
+            }
+            Self::Lzip => {
+                let input = std::io::BufReader::new(tokio_util::io::SyncIoBridge::new(input));
+                let stream = liblzma::stream::Stream::new_lzip_decoder(
+                    u64::MAX,
+                    liblzma::stream::CONCATENATED,
+                )?;
+                Box::new(liblzma::bufread::XzDecoder::new_stream(input, stream))
+            }
+            Self::None | Self::Brotli | Self::Bzip2 | Self::Gzip | Self::Xz | Self::Zstd => {
+                let decoder = self.decompress(input)?;
+                Box::new(tokio_util::io::SyncIoBridge::new(decoder))
+            }
+        };
+        Ok(decoder)
     }
 }
 
@@ -13,7 +13,7 @@ use crate::{
     project::{Project, ProjectHash, Projects},
     recipe::{
         Artifact, CompleteProcessRecipe, CompleteProcessTemplateComponent, ProcessRecipe,
-        ProcessTemplateComponent, Recipe, RecipeHash,
+        ProcessTemplate, ProcessTemplateComponent, Recipe, RecipeHash, Substitute,
     },
 };
 
@@ -134,7 +134,9 @@ pub fn referenced_blobs(recipe: &Recipe) -> Vec<BlobHash> {
         | Recipe::Proxy(_)
         | Recipe::CollectReferences { .. }
         | Recipe::AttachResources { .. }
-        | Recipe::Sync { .. } => vec![],
+        | Recipe::Sync { .. }
+        | Recipe::Substitute(_)
+        | Recipe::GitCheckout(_) => vec![],
     }
 }
 
@@ -160,6 +162,7 @@ pub fn referenced_recipes(recipe: &Recipe) -> Vec<RecipeHash> {
                 platform: _,
                 is_unsafe: _,
                 networking: _,
+                jobs: _,
             } = process;
 
             let templates = [command].into_iter().chain(args).chain(env.values());
@@ -199,6 +202,7 @@ pub fn referenced_recipes(recipe: &Recipe) -> Vec<RecipeHash> {
                 platform: _,
                 is_unsafe: _,
                 networking: _,
+                jobs: _,
             } = process;
 
             let work_dir = Recipe::from(work_dir.clone());
@@ -267,6 +271,115 @@ pub fn referenced_recipes(recipe: &Recipe) -> Vec<RecipeHash> {
         Recipe::CollectReferences { recipe } => referenced_recipes(recipe),
         Recipe::AttachResources { recipe } => referenced_recipes(recipe),
         Recipe::Sync { recipe } => referenced_recipes(recipe),
+        Recipe::Substitute(substitute) => {
+            let Substitute { file, replacements } = substitute;
+
+            referenced_recipes(file)
+                .into_iter()
+                .chain(
+                    replacements
+                        .values()
+                        .flat_map(|template| &template.components)
+                        .flat_map(|component| match component {
+                            ProcessTemplateComponent::Input { recipe } => {
+                                referenced_recipes(recipe)
+                            }
+                            ProcessTemplateComponent::Literal { .. }
+                            | ProcessTemplateComponent::OutputPath
+                            | ProcessTemplateComponent::ResourceDir
+                            | ProcessTemplateComponent::InputResourceDirs
+                            | ProcessTemplateComponent::HomeDir
+                            | ProcessTemplateComponent::WorkDir
+                            | ProcessTemplateComponent::TempDir => vec![],
+                        }),
+                )
+                .collect()
+        }
+        Recipe::GitCheckout(_) => vec![],
+    }
+}
+
+/// Returns the recipes nested directly within a recipe. Unlike
+/// [`referenced_recipes`], this doesn't recurse, and doesn't include
+/// recipes referenced by hash (e.g. proxies or directory entries).
+pub fn inline_recipes(recipe: &Recipe) -> Vec<&Recipe> {
+    fn template_recipes<'a>(
+        templates: impl IntoIterator<Item = &'a ProcessTemplate>,
+    ) -> impl Iterator<Item = &'a Recipe> {
+        templates
+            .into_iter()
+            .flat_map(|template| &template.components)
+            .filter_map(|component| match component {
+                ProcessTemplateComponent::Input { recipe } => Some(&recipe.value),
+                ProcessTemplateComponent::Literal { .. }
+                | ProcessTemplateComponent::OutputPath
+                | ProcessTemplateComponent::ResourceDir
+                | ProcessTemplateComponent::InputResourceDirs
+                | ProcessTemplateComponent::HomeDir
+                | ProcessTemplateComponent::WorkDir
+                | ProcessTemplateComponent::TempDir => None,
+            })
+    }
+
+    match recipe {
+        Recipe::File { resources, .. } | Recipe::CreateFile { resources, .. } => {
+            vec![&resources.value]
+        }
+        Recipe::Directory(_)
+        | Recipe::Symlink { .. }
+        | Recipe::Download(_)
+        | Recipe::CompleteProcess(_)
+        | Recipe::Proxy(_)
+        | Recipe::GitCheckout(_) => vec![],
+        Recipe::Unarchive(unarchive) => vec![&unarchive.file.value],
+        Recipe::Process(process) => {
+            let ProcessRecipe {
+                command,
+                args,
+                env,
+                dependencies,
+                work_dir,
+                output_scaffold,
+                platform: _,
+                is_unsafe: _,
+                networking: _,
+                jobs: _,
+            } = process;
+
+            template_recipes([command].into_iter().chain(args).chain(env.values()))
+                .chain(dependencies.iter().map(|dep| &dep.value))
+                .chain([&work_dir.value])
+                .chain(output_scaffold.iter().map(|recipe| &recipe.value))
+                .collect()
+        }
+        Recipe::CreateDirectory(directory) => directory
+            .entries
+            .values()
+            .map(|entry| &entry.value)
+            .collect(),
+        Recipe::Merge { directories } => directories.iter().map(|dir| &dir.value).collect(),
+        Recipe::Insert {
+            directory, recipe, ..
+        } => [&directory.value]
+            .into_iter()
+            .chain(recipe.iter().map(|recipe| &recipe.value))
+            .collect(),
+        Recipe::Cast { recipe, .. }
+        | Recipe::CollectReferences { recipe }
+        | Recipe::AttachResources { recipe }
+        | Recipe::Sync { recipe } => vec![&recipe.value],
+        Recipe::Peel { directory, .. }
+        | Recipe::Get { directory, .. }
+        | Recipe::Glob { directory, .. } => vec![&directory.value],
+        Recipe::SetPermissions { file, .. } => vec![&file.value],
+        Recipe::Substitute(substitute) => {
+            let Substitute { file, replacements } = substitute;
+
+            [&file.value]
+                .into_iter()
+                .chain(template_recipes(replacements.values()))
+                .collect()
+        }
     }
 }
 
@@ -315,8 +428,8 @@ pub async fn descendent_project_bakes(
 
     // Find all recipes baked by the project (either directly in the
     // `project_resolves` table or indirectly in the `child_resolves` table),
-    // then filter it down to only `complete_process`, `download`, and `sync`
-    // recipes.
+    // then filter it down to only `complete_process`, `download`, `sync`,
+    // and `git_checkout` recipes.
     let project_hash_value = project_hash.to_string();
     let project_descendent_bakes = sqlx::query!(
         r#"
@@ -346,7 +459,8 @@ pub async fn descendent_project_bakes(
                 'process',
                 'complete_process',
                 'download',
-                'sync'
+                'sync',
+                'git_checkout'
             );
         "#,
         project_hash_value,
@@ -358,34 +472,12 @@ pub async fn descendent_project_bakes(
     let project_descendent_bakes = project_descendent_bakes
         .into_iter()
         .map(|record| {
-            let input_hash = record
-                .input_hash
-                .parse()
-                .context("invalid recipe hash from database")?;
-            let output_hash = record
-                .output_hash
-                .parse()
-                .context("invalid recipe hash from database")?;
-            let input: Recipe = serde_json::from_str(&record.input_json)
-                .context("invalid recipe JSON from database")?;
-            let output: Recipe = serde_json::from_str(&record.output_json)
-                .context("invalid recipe JSON from database")?;
-            let output: Artifact = output
-                .try_into()
-                .map_err(|_| anyhow::anyhow!("output recipe is not complete"))?;
-
-            anyhow::ensure!(
-                input.hash() == input_hash,
-                "expected input hash to be {input_hash}, but was {}",
-                input.hash()
-            );
-            anyhow::ensure!(
-                output.hash() == output_hash,
-                "expected output hash to be {output_hash}, but was {}",
-                output.hash()
-            );
-
-            Ok((input, output))
+            parse_bake(
+                &record.input_hash,
+                &record.input_json,
+                &record.output_hash,
+                &record.output_json,
+            )
         })
         .collect::<anyhow::Result<Vec<_>>>()?;
 
@@ -394,6 +486,95 @@ pub async fn descendent_project_bakes(
     Ok(project_descendent_bakes)
 }
 
+/// Returns every bake saved in the local database within `max_age`, only
+/// including the same kinds of recipes as [`descendent_project_bakes`].
+/// A bake is dated from the first time it was baked locally.
+pub async fn recent_bakes(
+    brioche: &Brioche,
+    max_age: std::time::Duration,
+) -> anyhow::Result<Vec<(Recipe, Artifact)>> {
+    let mut db_conn = brioche.db_conn.lock().await;
+    let mut db_transaction = db_conn.begin().await?;
+
+    let max_age_modifier = format!("-{} seconds", max_age.as_secs());
+    let recent_bakes = sqlx::query!(
+        r#"
+            SELECT
+                input_recipes.recipe_hash AS input_hash,
+                input_recipes.recipe_json AS input_json,
+                output_artifacts.recipe_hash AS output_hash,
+                output_artifacts.recipe_json AS output_json
+            FROM bakes
+            INNER JOIN recipes AS input_recipes ON
+                input_recipes.recipe_hash = bakes.input_hash
+            INNER JOIN recipes AS output_artifacts ON
+                output_artifacts.recipe_hash = bakes.output_hash
+            WHERE
+                bakes.created_at >= datetime('now', ?)
+                AND input_recipes.recipe_json->>'type' IN (
+                    'process',
+                    'complete_process',
+                    'download',
+                    'sync',
+                    'git_checkout'
+                );
+        "#,
+        max_age_modifier,
+    )
+    .fetch_all(&mut *db_transaction)
+    .await?;
+
+    let recent_bakes = recent_bakes
+        .into_iter()
+        .map(|record| {
+            parse_bake(
+                &record.input_hash,
+                &record.input_json,
+                &record.output_hash,
+                &record.output_json,
+            )
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    db_transaction.commit().await?;
+
+    Ok(recent_bakes)
+}
+
+fn parse_bake(
+    input_hash: &str,
+    input_json: &str,
+    output_hash: &str,
+    output_json: &str,
+) -> anyhow::Result<(Recipe, Artifact)> {
+    let input_hash: RecipeHash = input_hash
+        .parse()
+        .context("invalid recipe hash from database")?;
+    let output_hash: RecipeHash = output_hash
+        .parse()
+        .context("invalid recipe hash from database")?;
+    let input: Recipe =
+        serde_json::from_str(input_json).context("invalid recipe JSON from database")?;
+    let output: Recipe =
+        serde_json::from_str(output_json).context("invalid recipe JSON from database")?;
+    let output: Artifact = output
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("output recipe is not complete"))?;
+
+    anyhow::ensure!(
+        input.hash() == input_hash,
+        "expected input hash to be {input_hash}, but was {}",
+        input.hash()
+    );
+    anyhow::ensure!(
+        output.hash() == output_hash,
+        "expected output hash to be {output_hash}, but was {}",
+        output.hash()
+    );
+
+    Ok((input, output))
+}
+
 pub async fn local_recipes(
     brioche: &Brioche,
     recipes: impl IntoIterator<Item = RecipeHash>,
@@ -27,10 +27,15 @@ pub enum RegistryClient {
         auth: RegistryAuthentication,
     },
     Disabled,
+    Offline,
 }
 
 impl RegistryClient {
-    pub fn new(url: url::Url, auth: RegistryAuthentication) -> Self {
+    pub fn new(
+        url: url::Url,
+        auth: RegistryAuthentication,
+        network_options: &crate::network::NetworkOptions,
+    ) -> anyhow::Result<Self> {
         let retry_policy = reqwest_retry::policies::ExponentialBackoff::builder()
             .retry_bounds(
                 std::time::Duration::from_millis(500),
@@ -43,14 +48,16 @@ impl RegistryClient {
         let client = reqwest::Client::builder()
             .user_agent(crate::USER_AGENT)
             .connect_timeout(CONNECT_TIMEOUT)
-            .read_timeout(READ_TIMEOUT)
+            .read_timeout(READ_TIMEOUT);
+        let client = network_options
+            .apply_to_reqwest(client)?
             .build()
-            .expect("failed to build reqwest client");
+            .context("failed to build registry client")?;
         let client = reqwest_middleware::ClientBuilder::new(client)
             .with(retry_middleware)
             .build();
 
-        Self::new_with_client(client, url, auth)
+        Ok(Self::new_with_client(client, url, auth))
     }
 
     pub fn new_with_client(
@@ -80,8 +87,17 @@ impl RegistryClient {
         method: reqwest::Method,
         path: &str,
     ) -> anyhow::Result<reqwest_middleware::RequestBuilder> {
-        let Self::Enabled { client, url, auth } = self else {
-            return Err(anyhow::anyhow!("registry client is disabled"));
+        let (client, url, auth) = match self {
+            Self::Enabled { client, url, auth } => (client, url, auth),
+            Self::Disabled => {
+                return Err(anyhow::anyhow!("registry client is disabled"));
+            }
+            Self::Offline => {
+                return Err(crate::network::OfflineError {
+                    needed: vec![format!("registry request {method} /{path}")],
+                }
+                .into());
+            }
         };
         let endpoint_url = url.join(path).context("failed to construct registry URL")?;
         let request = client
@@ -501,6 +517,10 @@ pub async fn fetch_blobs(brioche: Brioche, blobs: HashSet<BlobHash>) -> anyhow::
         return Ok(());
     }
 
+    // Fetch blobs from artifacts that were loaded from the cache lazily
+    // all at once, so blobs that share chunks are fetched together
+    crate::cache::lazy_blobs::fetch_lazy_blobs(&brioche, unknown_blobs.iter().copied()).await?;
+
     futures::stream::iter(unknown_blobs)
         .map(Ok)
         .try_for_each_concurrent(25, |blob| {
@@ -0,0 +1,446 @@
+//! Run processes on a remote `brioche worker` instead of locally.
+//!
+//! The protocol is a handful of JSON endpoints over HTTP:
+//!
+//! 1. `POST /v0/inputs`: The client sends the recipes referenced by the
+//!    process, along with the blobs it needs. The worker tries to fetch
+//!    any blobs it's missing from its own cache, then replies with the
+//!    blobs it still needs.
+//! 2. `PUT /v0/blobs/{blob_hash}`: The client uploads each missing blob.
+//! 3. `POST /v0/processes`: The worker runs the process in its sandbox
+//!    (using the client's priority for it), then replies with the output artifact (plus its recipes and blobs)
+//!    or the error message, along with an ID for the process event log.
+//! 4. `GET /v0/blobs/{blob_hash}`: The client downloads any output blobs
+//!    it doesn't have yet.
+//! 5. `GET /v0/events/{events_id}`: The client downloads the process
+//!    event log, then removes it from the worker with `DELETE`.
+
+use std::{path::Path, sync::Arc};
+
+use anyhow::Context as _;
+use futures::{StreamExt as _, TryStreamExt as _};
+use tokio::io::AsyncWriteExt as _;
+
+use crate::{
+    Brioche,
+    blob::BlobHash,
+    recipe::{
+        Artifact, CompleteProcessRecipe, CompleteProcessTemplateComponent, Meta, Recipe, RecipeHash,
+    },
+    references::RecipeReferences,
+    reporter::job::{NewJob, ProcessStatus, UpdateJob},
+};
+
+pub mod worker;
+
+const MAX_CONCURRENT_BLOB_TRANSFERS: usize = 25;
+
+#[derive(Debug, Clone)]
+pub struct RemoteExecutionClient {
+    client: reqwest::Client,
+    url: url::Url,
+}
+
+impl RemoteExecutionClient {
+    pub fn new(
+        url: url::Url,
+        network_options: &crate::network::NetworkOptions,
+    ) -> anyhow::Result<Self> {
+        // No read timeout, since processes can run for a long time
+        let client = reqwest::Client::builder()
+            .user_agent(crate::USER_AGENT)
+            .connect_timeout(std::time::Duration::from_secs(10));
+        let client = network_options.apply_to_reqwest(client)?.build()?;
+
+        Ok(Self { client, url })
+    }
+
+    fn request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+    ) -> anyhow::Result<reqwest::RequestBuilder> {
+        let endpoint_url = self
+            .url
+            .join(path)
+            .with_context(|| format!("failed to build URL for remote execution path {path}"))?;
+        Ok(self.client.request(method, endpoint_url))
+    }
+
+    pub async fn prepare_inputs(
+        &self,
+        request: &PrepareInputsRequest,
+    ) -> anyhow::Result<PrepareInputsResponse> {
+        let response = self
+            .request(reqwest::Method::POST, "v0/inputs")?
+            .json(request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(response)
+    }
+
+    /// Upload a blob to the worker, streaming it from `blob_path`.
+    pub async fn send_blob(&self, blob_hash: BlobHash, blob_path: &Path) -> anyhow::Result<()> {
+        let file = tokio::fs::File::open(blob_path)
+            .await
+            .with_context(|| format!("failed to open blob {blob_hash}"))?;
+        let length = file.metadata().await?.len();
+        let body = reqwest::Body::wrap_stream(tokio_util::io::ReaderStream::new(file));
+
+        self.request(reqwest::Method::PUT, &format!("v0/blobs/{blob_hash}"))?
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .header(reqwest::header::CONTENT_LENGTH, length)
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Download a blob from the worker, streaming it into the local blob
+    /// store.
+    pub async fn save_blob(&self, brioche: &Brioche, blob_hash: BlobHash) -> anyhow::Result<()> {
+        let response = self
+            .request(reqwest::Method::GET, &format!("v0/blobs/{blob_hash}"))?
+            .send()
+            .await?
+            .error_for_status()?;
+        let response_stream = response.bytes_stream().map_err(std::io::Error::other);
+        let response_reader = tokio_util::io::StreamReader::new(response_stream);
+
+        let mut permit = crate::blob::get_save_blob_permit().await?;
+        crate::blob::save_blob_from_reader(
+            brioche,
+            &mut permit,
+            response_reader,
+            crate::blob::SaveBlobOptions::new().expected_blob_hash(Some(blob_hash)),
+            &mut vec![],
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn run_process(
+        &self,
+        request: &RunProcessRequest,
+    ) -> anyhow::Result<RunProcessResponse> {
+        let response = self
+            .request(reqwest::Method::POST, "v0/processes")?
+            .json(request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(response)
+    }
+
+    /// Download a process event log from the worker, streaming it to
+    /// `path`.
+    pub async fn save_events(&self, events_id: &str, path: &Path) -> anyhow::Result<()> {
+        let response = self
+            .request(reqwest::Method::GET, &format!("v0/events/{events_id}"))?
+            .send()
+            .await?
+            .error_for_status()?;
+        let response_stream = response.bytes_stream().map_err(std::io::Error::other);
+        let mut response_reader = tokio_util::io::StreamReader::new(response_stream);
+
+        let mut events_file = tokio::fs::File::create(path)
+            .await
+            .with_context(|| format!("failed to create {}", path.display()))?;
+        tokio::io::copy(&mut response_reader, &mut events_file).await?;
+        events_file.flush().await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_events(&self, events_id: &str) -> anyhow::Result<()> {
+        self.request(reqwest::Method::DELETE, &format!("v0/events/{events_id}"))?
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrepareInputsRequest {
+    /// The artifacts used as inputs to the process. The worker can try
+    /// loading these from its cache if it's missing any blobs.
+    pub artifacts: Vec<RecipeHash>,
+    pub recipes: Vec<Recipe>,
+    pub blobs: Vec<BlobHash>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrepareInputsResponse {
+    pub missing_blobs: Vec<BlobHash>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunProcessRequest {
+    pub process: CompleteProcessRecipe,
+    pub meta: Meta,
+
+    /// The process's priority from the client's plan, so the worker runs
+    /// processes on the client's critical path first.
+    #[serde(default)]
+    pub priority: Option<u64>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunProcessResponse {
+    pub result: RunProcessResult,
+
+    /// The ID of the process event log on the worker, if the process
+    /// got far enough to start writing one.
+    pub events_id: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum RunProcessResult {
+    #[serde(rename_all = "camelCase")]
+    Success {
+        artifact: Artifact,
+        recipes: Vec<Recipe>,
+        blobs: Vec<BlobHash>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Failed { message: String },
+}
+
+/// Collect the recipes and blobs needed to describe some artifacts. Used
+/// for both sides of a remote process: the client sends the process
+/// inputs, and the worker sends back the output.
+pub async fn artifact_references(
+    brioche: &Brioche,
+    artifacts: impl IntoIterator<Item = Artifact>,
+) -> anyhow::Result<RecipeReferences> {
+    let mut references = RecipeReferences::default();
+    let mut recipe_hashes = vec![];
+    for artifact in artifacts {
+        let recipe = Recipe::from(artifact);
+        references
+            .blobs
+            .extend(crate::references::referenced_blobs(&recipe));
+        recipe_hashes.extend(crate::references::referenced_recipes(&recipe));
+    }
+
+    crate::references::recipe_references(brioche, &mut references, recipe_hashes).await?;
+    Ok(references)
+}
+
+/// Returns the artifacts a complete process uses as inputs.
+pub fn process_input_artifacts(process: &CompleteProcessRecipe) -> Vec<Artifact> {
+    let templates = [&process.command]
+        .into_iter()
+        .chain(&process.args)
+        .chain(process.env.values());
+    let template_artifacts = templates
+        .flat_map(|template| &template.components)
+        .filter_map(|component| match component {
+            CompleteProcessTemplateComponent::Input { artifact } => Some(artifact.value.clone()),
+            CompleteProcessTemplateComponent::Literal { .. }
+            | CompleteProcessTemplateComponent::OutputPath
+            | CompleteProcessTemplateComponent::ResourceDir
+            | CompleteProcessTemplateComponent::InputResourceDirs
+            | CompleteProcessTemplateComponent::HomeDir
+            | CompleteProcessTemplateComponent::WorkDir
+            | CompleteProcessTemplateComponent::TempDir => None,
+        });
+
+    [Artifact::Directory(process.work_dir.clone())]
+        .into_iter()
+        .chain(
+            process
+                .output_scaffold
+                .iter()
+                .map(|artifact| (**artifact).clone()),
+        )
+        .chain(template_artifacts)
+        .collect()
+}
+
+#[tracing::instrument(skip_all, fields(url = %remote_execution.url))]
+pub async fn bake_process_remote(
+    brioche: &Brioche,
+    remote_execution: &RemoteExecutionClient,
+    meta: &Arc<Meta>,
+    process: CompleteProcessRecipe,
+) -> anyhow::Result<Artifact> {
+    crate::network::ensure_online(brioche, || {
+        format!("remote execution on {}", remote_execution.url)
+    })?;
+
+    let created_at = std::time::Instant::now();
+    let mut job_status = ProcessStatus::Preparing { created_at };
+    let job_id = brioche.reporter.add_job(NewJob::Process {
+        status: job_status.clone(),
+    });
+
+    // Send the inputs to the worker
+
+    let input_artifacts = process_input_artifacts(&process);
+    let input_artifact_hashes = input_artifacts.iter().map(Artifact::hash).collect();
+    let input_references = artifact_references(brioche, input_artifacts).await?;
+    let prepare_response = remote_execution
+        .prepare_inputs(&PrepareInputsRequest {
+            artifacts: input_artifact_hashes,
+            recipes: input_references.recipes.into_values().collect(),
+            blobs: input_references.blobs.into_iter().collect(),
+        })
+        .await
+        .context("failed to send process inputs to worker")?;
+
+    futures::stream::iter(prepare_response.missing_blobs)
+        .map(|blob_hash| async move {
+            let blob_path = {
+                let mut permit = crate::blob::get_save_blob_permit().await?;
+                crate::blob::blob_path(brioche, &mut permit, blob_hash).await?
+            };
+            remote_execution
+                .send_blob(blob_hash, &blob_path)
+                .await
+                .with_context(|| format!("failed to send blob {blob_hash} to worker"))?;
+            anyhow::Ok(())
+        })
+        .buffer_unordered(MAX_CONCURRENT_BLOB_TRANSFERS)
+        .try_collect::<()>()
+        .await?;
+
+    // Run the process
+
+    job_status.to_running(std::time::Instant::now(), None)?;
+    brioche.reporter.update_job(
+        job_id,
+        UpdateJob::ProcessUpdateStatus {
+            status: job_status.clone(),
+        },
+    );
+
+    let hash = Recipe::CompleteProcess(process.clone()).hash();
+    let duration_key = crate::bake::scheduler::complete_process_duration_key(&process);
+    let priority = brioche.process_scheduler.priority(hash);
+    let started_at = std::time::Instant::now();
+    let response = remote_execution
+        .run_process(&RunProcessRequest {
+            process,
+            meta: (**meta).clone(),
+            priority: Some(priority),
+        })
+        .await
+        .context("failed to run process on worker")?;
+    let run_duration = started_at.elapsed();
+
+    // Save the event log locally, so `brioche jobs logs` works the same
+    // way as it does for local processes
+
+    let events_path = match &response.events_id {
+        Some(events_id) => {
+            let bake_dir = brioche
+                .data_dir
+                .join("process-temp")
+                .join(ulid::Ulid::new().to_string());
+            tokio::fs::create_dir_all(&bake_dir).await?;
+            let events_path = bake_dir.join("events.bin.zst");
+
+            remote_execution
+                .save_events(events_id, &events_path)
+                .await
+                .context("failed to get process events from worker")?;
+            remote_execution
+                .delete_events(events_id)
+                .await
+                .unwrap_or_else(|error| {
+                    tracing::warn!("failed to delete process events from worker: {error:#}");
+                });
+
+            Some((bake_dir, events_path))
+        }
+        None => None,
+    };
+
+    let (artifact, recipes, blobs) = match response.result {
+        RunProcessResult::Success {
+            artifact,
+            recipes,
+            blobs,
+        } => (artifact, recipes, blobs),
+        RunProcessResult::Failed { message } => {
+            let error = anyhow::anyhow!("{message}");
+            return match events_path {
+                Some((_, events_path)) => Err(error).with_context(|| {
+                    format!(
+                        "process {hash} failed on worker, view full output by running `brioche jobs logs {}`",
+                        events_path.display(),
+                    )
+                }),
+                None => Err(error).with_context(|| format!("process {hash} failed on worker")),
+            };
+        }
+    };
+
+    // Record how long the process took, so later plans can prioritize it
+    // the same way as a local process. This includes time spent waiting
+    // for the worker, which is part of the critical path too
+    crate::bake::scheduler::save_process_duration(brioche, &duration_key, run_duration)
+        .await
+        .unwrap_or_else(|error| {
+            tracing::warn!("failed to save process duration: {error:#}");
+        });
+
+    job_status.to_ran(std::time::Instant::now())?;
+    brioche.reporter.update_job(
+        job_id,
+        UpdateJob::ProcessUpdateStatus {
+            status: job_status.clone(),
+        },
+    );
+
+    // Fetch the outputs from the worker
+
+    crate::recipe::save_recipes(brioche, &recipes).await?;
+    futures::stream::iter(blobs)
+        .map(|blob_hash| async move {
+            let local_path = crate::blob::local_blob_path(brioche, blob_hash);
+            if tokio::fs::try_exists(&local_path).await? {
+                return anyhow::Ok(());
+            }
+
+            remote_execution
+                .save_blob(brioche, blob_hash)
+                .await
+                .with_context(|| format!("failed to get blob {blob_hash} from worker"))?;
+            anyhow::Ok(())
+        })
+        .buffer_unordered(MAX_CONCURRENT_BLOB_TRANSFERS)
+        .try_collect::<()>()
+        .await?;
+
+    if let Some((bake_dir, _)) = events_path {
+        if !brioche.keep_temps {
+            crate::fs_utils::try_remove(&bake_dir).await?;
+        }
+    }
+
+    job_status.to_finalized(std::time::Instant::now())?;
+    brioche.reporter.update_job(
+        job_id,
+        UpdateJob::ProcessUpdateStatus {
+            status: job_status.clone(),
+        },
+    );
+
+    Ok(artifact)
+}
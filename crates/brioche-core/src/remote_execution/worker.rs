@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use anyhow::Context as _;
+
+use crate::{
+    Brioche,
+    blob::BlobHash,
+    recipe::RecipeHash,
+    reporter::job::CacheFetchKind,
+    utils::http_server::{self, Request, Response, json_response, not_found, read_json},
+};
+
+use super::{
+    PrepareInputsRequest, PrepareInputsResponse, RunProcessRequest, RunProcessResponse,
+    RunProcessResult,
+};
+
+/// Serve remote execution requests until Brioche gets cancelled. The
+/// worker doesn't do any authentication, so it should only be reachable
+/// from trusted machines.
+pub async fn serve(brioche: Brioche, listener: tokio::net::TcpListener) -> anyhow::Result<()> {
+    let events_dir = worker_events_dir(&brioche);
+    tokio::fs::create_dir_all(&events_dir)
+        .await
+        .with_context(|| format!("failed to create directory {}", events_dir.display()))?;
+
+    let cancellation_token = brioche.cancellation_token.clone();
+    http_server::serve(listener, &cancellation_token, move |request| {
+        let brioche = brioche.clone();
+        async move { handle_request(&brioche, request).await }
+    })
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_request(brioche: &Brioche, request: Request) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    let result = route_request(brioche, request).await;
+    match result {
+        Ok(response) => response,
+        Err(error) => {
+            tracing::warn!(%method, %path, "worker request failed: {error:#}");
+            http_server::status_response(
+                hyper::StatusCode::INTERNAL_SERVER_ERROR,
+                format!("{error:#}"),
+            )
+        }
+    }
+}
+
+async fn route_request(brioche: &Brioche, request: Request) -> anyhow::Result<Response> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let segments = path.trim_start_matches('/').split('/').collect::<Vec<_>>();
+
+    match (&method, &segments[..]) {
+        (&hyper::Method::POST, ["v0", "inputs"]) => {
+            let request = read_json(request).await?;
+            let response = prepare_inputs(brioche, request).await?;
+            json_response(&response)
+        }
+        (&hyper::Method::PUT, ["v0", "blobs", blob_hash]) => {
+            let Ok(blob_hash) = blob_hash.parse::<BlobHash>() else {
+                return Ok(not_found());
+            };
+            let content = http_server::body_reader(request);
+
+            let mut permit = crate::blob::get_save_blob_permit().await?;
+            crate::blob::save_blob_from_reader(
+                brioche,
+                &mut permit,
+                content,
+                crate::blob::SaveBlobOptions::new().expected_blob_hash(Some(blob_hash)),
+                &mut vec![],
+            )
+            .await?;
+
+            Ok(http_server::status_response(hyper::StatusCode::OK, ""))
+        }
+        (&hyper::Method::GET, ["v0", "blobs", blob_hash]) => {
+            let Ok(blob_hash) = blob_hash.parse::<BlobHash>() else {
+                return Ok(not_found());
+            };
+            let blob_path = crate::blob::local_blob_path(brioche, blob_hash);
+            http_server::file_response(&blob_path).await
+        }
+        (&hyper::Method::POST, ["v0", "processes"]) => {
+            let request = read_json(request).await?;
+            let response = run_process(brioche, request).await?;
+            json_response(&response)
+        }
+        (&hyper::Method::GET, ["v0", "events", events_id]) => {
+            let Some(events_path) = worker_events_path(brioche, events_id) else {
+                return Ok(not_found());
+            };
+            http_server::file_response(&events_path).await
+        }
+        (&hyper::Method::DELETE, ["v0", "events", events_id]) => {
+            let Some(events_path) = worker_events_path(brioche, events_id) else {
+                return Ok(not_found());
+            };
+            crate::fs_utils::try_remove(&events_path).await?;
+            Ok(http_server::status_response(hyper::StatusCode::OK, ""))
+        }
+        _ => Ok(not_found()),
+    }
+}
+
+async fn prepare_inputs(
+    brioche: &Brioche,
+    request: PrepareInputsRequest,
+) -> anyhow::Result<PrepareInputsResponse> {
+    crate::recipe::save_recipes(brioche, &request.recipes).await?;
+
+    let mut missing_blobs = find_missing_blobs(brioche, &request.blobs).await?;
+
+    // Try to get missing inputs from the cache before asking the client
+    // to upload them
+    if !missing_blobs.is_empty() && brioche.cache_client.is_available(brioche) {
+        for &artifact_hash in &request.artifacts {
+            load_artifact_from_cache(brioche, artifact_hash).await;
+        }
+
+        missing_blobs = find_missing_blobs(brioche, &missing_blobs).await?;
+    }
+
+    Ok(PrepareInputsResponse { missing_blobs })
+}
+
+async fn load_artifact_from_cache(brioche: &Brioche, artifact_hash: RecipeHash) {
+    let result = crate::cache::load_artifact(brioche, artifact_hash, CacheFetchKind::Bake).await;
+    if let Err(error) = result {
+        tracing::warn!(%artifact_hash, "failed to load input artifact from cache: {error:#}");
+    }
+}
+
+async fn find_missing_blobs(
+    brioche: &Brioche,
+    blobs: &[BlobHash],
+) -> anyhow::Result<Vec<BlobHash>> {
+    let mut missing_blobs = vec![];
+    for &blob_hash in blobs {
+        let blob_path = crate::blob::local_blob_path(brioche, blob_hash);
+        if !tokio::fs::try_exists(&blob_path).await? {
+            missing_blobs.push(blob_hash);
+        }
+    }
+
+    Ok(missing_blobs)
+}
+
+async fn run_process(
+    brioche: &Brioche,
+    request: RunProcessRequest,
+) -> anyhow::Result<RunProcessResponse> {
+    let RunProcessRequest {
+        process,
+        meta,
+        priority,
+    } = request;
+
+    let events_id = ulid::Ulid::new().to_string();
+    let events_path = worker_events_dir(brioche).join(format!("{events_id}.bin.zst"));
+
+    let result = crate::bake::bake_process_local(
+        brioche,
+        &Arc::new(meta),
+        process,
+        Some(&events_path),
+        priority,
+    )
+    .await;
+
+    let events_id = if tokio::fs::try_exists(&events_path).await? {
+        Some(events_id)
+    } else {
+        None
+    };
+
+    let result = match result {
+        Ok(artifact) => {
+            let output_references = super::artifact_references(brioche, [artifact.clone()]).await?;
+            RunProcessResult::Success {
+                artifact,
+                recipes: output_references.recipes.into_values().collect(),
+                blobs: output_references.blobs.into_iter().collect(),
+            }
+        }
+        Err(error) => RunProcessResult::Failed {
+            message: format!("{error:#}"),
+        },
+    };
+
+    Ok(RunProcessResponse { result, events_id })
+}
+
+fn worker_events_dir(brioche: &Brioche) -> std::path::PathBuf {
+    brioche.data_dir.join("worker-events")
+}
+
+fn worker_events_path(brioche: &Brioche, events_id: &str) -> Option<std::path::PathBuf> {
+    // Only accept IDs we could have generated, so the path can't escape
+    // the events directory
+    let events_id = events_id.parse::<ulid::Ulid>().ok()?;
+    Some(worker_events_dir(brioche).join(format!("{events_id}.bin.zst")))
+}
@@ -278,6 +278,17 @@ impl ConsoleReporter {
                     } => {
                         eprintln!("Fetching project from cache");
                     }
+                    NewJob::CacheSync {
+                        total_bakes,
+                        total_artifacts,
+                        started_at: _,
+                    } => {
+                        eprintln!(
+                            "Syncing {total_bakes} bake{s} and {total_artifacts} artifact{s2} to cache",
+                            s = if *total_bakes == 1 { "" } else { "s" },
+                            s2 = if *total_artifacts == 1 { "" } else { "s" },
+                        );
+                    }
                 }
 
                 let new_job = Job::new(job);
@@ -324,6 +335,16 @@ impl ConsoleReporter {
                             eprintln!("Finished download in {}", DisplayDuration(elapsed));
                         }
                     }
+                    UpdateJob::DownloadResume {
+                        resumed_bytes,
+                        attempt,
+                    } => {
+                        if let Job::Download { url, .. } = job {
+                            eprintln!(
+                                "Resuming download of {url} from {resumed_bytes} bytes (attempt {attempt})"
+                            );
+                        }
+                    }
                     UpdateJob::Unarchive { finished_at, .. } => {
                         if let Some(finished_at) = finished_at {
                             let elapsed = finished_at.saturating_duration_since(job.created_at());
@@ -422,12 +443,15 @@ impl ConsoleReporter {
                     }
                     UpdateJob::CacheFetchAdd { .. } => {}
                     UpdateJob::CacheFetchUpdate { .. } => {}
+                    UpdateJob::CacheFetchAddChunks { .. } => {}
                     UpdateJob::CacheFetchFinish { finished_at } => {
                         let elapsed = finished_at.saturating_duration_since(job.created_at());
 
                         let Job::CacheFetch {
                             kind,
                             downloaded_blobs,
+                            chunk_cache_hits,
+                            chunk_cache_misses,
                             ..
                         } = job
                         else {
@@ -437,13 +461,37 @@ impl ConsoleReporter {
                             crate::reporter::job::CacheFetchKind::Bake => "artifact",
                             crate::reporter::job::CacheFetchKind::Project => "project",
                         };
+                        let chunk_cache_note =
+                            chunk_cache_summary(*chunk_cache_hits, *chunk_cache_misses)
+                                .map(|summary| format!(" ({summary})"))
+                                .unwrap_or_default();
 
                         eprintln!(
-                            "Finished fetching {fetch_kind} with {downloaded_blobs} new blob{s} from cache in {}",
+                            "Finished fetching {fetch_kind} with {downloaded_blobs} new blob{s} from cache in {}{chunk_cache_note}",
                             DisplayDuration(elapsed),
                             s = if *downloaded_blobs == 1 { "" } else { "s" }
                         );
                     }
+                    UpdateJob::CacheSyncAdd { .. } => {}
+                    UpdateJob::CacheSyncFinish { finished_at } => {
+                        let elapsed = finished_at.saturating_duration_since(job.created_at());
+
+                        let Job::CacheSync {
+                            synced_bakes,
+                            synced_artifacts,
+                            ..
+                        } = job
+                        else {
+                            panic!("tried to update non-cache job {id:?} with a cache update");
+                        };
+
+                        eprintln!(
+                            "Finished syncing {synced_bakes} bake{s} and {synced_artifacts} artifact{s2} to cache in {}",
+                            DisplayDuration(elapsed),
+                            s = if *synced_bakes == 1 { "" } else { "s" },
+                            s2 = if *synced_artifacts == 1 { "" } else { "s" },
+                        );
+                    }
                 }
 
                 // This should never fail, since we would've already
@@ -697,6 +745,7 @@ impl superconsole::Component for JobComponent<'_> {
             Job::Download {
                 url,
                 progress_percent,
+                resumed_bytes,
                 started_at: _,
                 finished_at: _,
             } => {
@@ -727,7 +776,12 @@ impl superconsole::Component for JobComponent<'_> {
                     .saturating_sub(1)
                     .saturating_sub(line.len());
 
-                let truncated_url = string_with_width(url.as_str(), remaining_width, "…");
+                let download_message = if *resumed_bytes > 0 {
+                    format!("{url} (resumed at {resumed_bytes} bytes)")
+                } else {
+                    url.to_string()
+                };
+                let truncated_url = string_with_width(&download_message, remaining_width, "…");
 
                 let progress_bar_progress = f64::from(progress_percent.unwrap_or(0)) / 100.0;
 
@@ -853,6 +907,8 @@ impl superconsole::Component for JobComponent<'_> {
                 total_data,
                 downloaded_blobs,
                 total_blobs,
+                chunk_cache_hits,
+                chunk_cache_misses,
                 started_at: _,
                 finished_at: _,
             } => {
@@ -887,8 +943,12 @@ impl superconsole::Component for JobComponent<'_> {
                     super::job::CacheFetchKind::Project => "project",
                 };
                 let fetching_message = if job.is_complete() {
+                    let chunk_cache_note =
+                        chunk_cache_summary(*chunk_cache_hits, *chunk_cache_misses)
+                            .map(|summary| format!(", {summary}"))
+                            .unwrap_or_default();
                     format!(
-                        "Fetch {fetch_kind}: {downloaded_blobs} blob{s}",
+                        "Fetch {fetch_kind}: {downloaded_blobs} blob{s}{chunk_cache_note}",
                         s = if *downloaded_blobs == 1 { "" } else { "s" }
                     )
                 } else if let Some(total_blobs) = total_blobs {
@@ -910,6 +970,58 @@ impl superconsole::Component for JobComponent<'_> {
                     total_progress,
                 ));
 
+                superconsole::Lines::from_iter([line])
+            }
+            Job::CacheSync {
+                synced_bakes,
+                total_bakes,
+                synced_artifacts,
+                total_artifacts,
+                started_at: _,
+                finished_at: _,
+            } => {
+                let synced = synced_bakes + synced_artifacts;
+                let total = total_bakes + total_artifacts;
+                let total_progress = if total == 0 {
+                    1.0
+                } else {
+                    synced as f64 / total as f64
+                };
+                let total_percent = (total_progress * 100.0) as u64;
+
+                let percentage_span = superconsole::Span::new_unstyled_lossy(
+                    lazy_format::lazy_format!("{total_percent:>3}%"),
+                );
+
+                let indicator = if job.is_complete() {
+                    IndicatorKind::Complete
+                } else {
+                    IndicatorKind::Spinner(job.elapsed().unwrap_or_default())
+                };
+
+                let mut line = superconsole::Line::from_iter([
+                    elapsed_span,
+                    superconsole::Span::new_unstyled_lossy(" "),
+                    indicator_span(indicator),
+                    superconsole::Span::new_unstyled_lossy(" Cache     "),
+                    percentage_span,
+                    superconsole::Span::new_unstyled_lossy(" "),
+                ]);
+
+                let syncing_message = format!(
+                    "Sync: {synced_artifacts} / {total_artifacts} artifacts, {synced_bakes} / {total_bakes} bakes"
+                );
+
+                let remaining_width = dimensions
+                    .width
+                    .saturating_sub(1)
+                    .saturating_sub(line.len());
+                line.extend(progress_bar_spans(
+                    &syncing_message,
+                    remaining_width,
+                    total_progress,
+                ));
+
                 superconsole::Lines::from_iter([line])
             }
         };
@@ -1046,6 +1158,20 @@ fn spinner(duration: std::time::Duration, speed: u128) -> &'static str {
     SPINNERS[(duration.as_millis() / speed) as usize % SPINNERS.len()]
 }
 
+/// Describe how many chunks were read from the local chunk cache, or
+/// `None` if no chunks were fetched.
+fn chunk_cache_summary(hits: u64, misses: u64) -> Option<String> {
+    let total = hits + misses;
+    if total == 0 {
+        return None;
+    }
+
+    Some(format!(
+        "{hits}/{total} chunk{s} from local chunk cache",
+        s = if total == 1 { "" } else { "s" }
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::string_with_width;
@@ -22,6 +22,11 @@ pub enum NewJob {
         total_blobs: Option<u64>,
         started_at: std::time::Instant,
     },
+    CacheSync {
+        total_bakes: u64,
+        total_artifacts: u64,
+        started_at: std::time::Instant,
+    },
 }
 
 #[derive(Debug)]
@@ -30,6 +35,10 @@ pub enum UpdateJob {
         progress_percent: Option<u8>,
         finished_at: Option<std::time::Instant>,
     },
+    DownloadResume {
+        resumed_bytes: u64,
+        attempt: u32,
+    },
     Unarchive {
         progress_percent: u8,
         finished_at: Option<std::time::Instant>,
@@ -51,9 +60,20 @@ pub enum UpdateJob {
         downloaded_blobs: Option<u64>,
         total_blobs: Option<u64>,
     },
+    CacheFetchAddChunks {
+        chunk_cache_hits: u64,
+        chunk_cache_misses: u64,
+    },
     CacheFetchFinish {
         finished_at: std::time::Instant,
     },
+    CacheSyncAdd {
+        synced_bakes: u64,
+        synced_artifacts: u64,
+    },
+    CacheSyncFinish {
+        finished_at: std::time::Instant,
+    },
 }
 
 #[derive(Debug)]
@@ -61,6 +81,7 @@ pub enum Job {
     Download {
         url: url::Url,
         progress_percent: Option<u8>,
+        resumed_bytes: u64,
         started_at: std::time::Instant,
         finished_at: Option<std::time::Instant>,
     },
@@ -79,6 +100,19 @@ pub enum Job {
         total_data: Option<u64>,
         downloaded_blobs: u64,
         total_blobs: Option<u64>,
+
+        /// Chunks read from the local chunk cache instead of the cache.
+        chunk_cache_hits: u64,
+        chunk_cache_misses: u64,
+
+        started_at: std::time::Instant,
+        finished_at: Option<std::time::Instant>,
+    },
+    CacheSync {
+        synced_bakes: u64,
+        total_bakes: u64,
+        synced_artifacts: u64,
+        total_artifacts: u64,
         started_at: std::time::Instant,
         finished_at: Option<std::time::Instant>,
     },
@@ -90,6 +124,7 @@ impl Job {
             NewJob::Download { url, started_at } => Self::Download {
                 url,
                 progress_percent: Some(0),
+                resumed_bytes: 0,
                 started_at,
                 finished_at: None,
             },
@@ -115,6 +150,20 @@ impl Job {
                 total_data,
                 downloaded_blobs: downloaded_blobs.unwrap_or(0),
                 total_blobs,
+                chunk_cache_hits: 0,
+                chunk_cache_misses: 0,
+                started_at,
+                finished_at: None,
+            },
+            NewJob::CacheSync {
+                total_bakes,
+                total_artifacts,
+                started_at,
+            } => Self::CacheSync {
+                synced_bakes: 0,
+                total_bakes,
+                synced_artifacts: 0,
+                total_artifacts,
                 started_at,
                 finished_at: None,
             },
@@ -138,6 +187,15 @@ impl Job {
                 *progress_percent = new_progress_percent;
                 *finished_at = new_finished_at;
             }
+            UpdateJob::DownloadResume {
+                resumed_bytes: new_resumed_bytes,
+                attempt: _,
+            } => {
+                let Self::Download { resumed_bytes, .. } = self else {
+                    anyhow::bail!("tried to update a non-download job with a download update");
+                };
+                *resumed_bytes = new_resumed_bytes;
+            }
             UpdateJob::Unarchive {
                 progress_percent: new_progress_percent,
                 finished_at: new_finished_at,
@@ -214,6 +272,8 @@ impl Job {
                     total_data,
                     downloaded_blobs,
                     total_blobs,
+                    chunk_cache_hits: _,
+                    chunk_cache_misses: _,
                     started_at: _,
                     finished_at: _,
                 } = self
@@ -236,6 +296,24 @@ impl Job {
                     *total_blobs = Some(new_total_blobs);
                 }
             }
+            UpdateJob::CacheFetchAddChunks {
+                chunk_cache_hits: add_chunk_cache_hits,
+                chunk_cache_misses: add_chunk_cache_misses,
+            } => {
+                let Self::CacheFetch {
+                    chunk_cache_hits,
+                    chunk_cache_misses,
+                    ..
+                } = self
+                else {
+                    anyhow::bail!(
+                        "tried to update a non-cache-fetch job with a cache-fetch update"
+                    );
+                };
+
+                *chunk_cache_hits += add_chunk_cache_hits;
+                *chunk_cache_misses += add_chunk_cache_misses;
+            }
             UpdateJob::CacheFetchFinish {
                 finished_at: new_finished_at,
             } => {
@@ -245,6 +323,8 @@ impl Job {
                     total_data,
                     downloaded_blobs,
                     total_blobs,
+                    chunk_cache_hits: _,
+                    chunk_cache_misses: _,
                     started_at: _,
                     finished_at,
                 } = self
@@ -262,6 +342,33 @@ impl Job {
                     *downloaded_blobs = *total_blobs;
                 }
 
+                *finished_at = Some(new_finished_at);
+            }
+            UpdateJob::CacheSyncAdd {
+                synced_bakes: add_synced_bakes,
+                synced_artifacts: add_synced_artifacts,
+            } => {
+                let Self::CacheSync {
+                    synced_bakes,
+                    synced_artifacts,
+                    ..
+                } = self
+                else {
+                    anyhow::bail!("tried to update a non-cache-sync job with a cache-sync update");
+                };
+
+                *synced_bakes += add_synced_bakes;
+                *synced_artifacts += add_synced_artifacts;
+            }
+            UpdateJob::CacheSyncFinish {
+                finished_at: new_finished_at,
+            } => {
+                let Self::CacheSync { finished_at, .. } = self else {
+                    anyhow::bail!(
+                        "tried to update a non-cache-sync job with a cache-sync-finish update"
+                    );
+                };
+
                 *finished_at = Some(new_finished_at);
             }
         }
@@ -273,7 +380,8 @@ impl Job {
         match self {
             Job::Download { started_at, .. }
             | Job::Unarchive { started_at, .. }
-            | Job::CacheFetch { started_at, .. } => *started_at,
+            | Job::CacheFetch { started_at, .. }
+            | Job::CacheSync { started_at, .. } => *started_at,
             Job::Process { status, .. } => status.created_at(),
         }
     }
@@ -282,7 +390,8 @@ impl Job {
         match self {
             Job::Download { started_at, .. }
             | Job::Unarchive { started_at, .. }
-            | Job::CacheFetch { started_at, .. } => Some(*started_at),
+            | Job::CacheFetch { started_at, .. }
+            | Job::CacheSync { started_at, .. } => Some(*started_at),
             Job::Process { status, .. } => status.started_at(),
         }
     }
@@ -291,7 +400,8 @@ impl Job {
         match self {
             Job::Download { finished_at, .. }
             | Job::Unarchive { finished_at, .. }
-            | Job::CacheFetch { finished_at, .. } => *finished_at,
+            | Job::CacheFetch { finished_at, .. }
+            | Job::CacheSync { finished_at, .. } => *finished_at,
             Job::Process { status, .. } => status.finished_at(),
         }
     }
@@ -300,7 +410,8 @@ impl Job {
         match self {
             Job::Download { finished_at, .. }
             | Job::Unarchive { finished_at, .. }
-            | Job::CacheFetch { finished_at, .. } => *finished_at,
+            | Job::CacheFetch { finished_at, .. }
+            | Job::CacheSync { finished_at, .. } => *finished_at,
             Job::Process { status, .. } => status.finalized_at(),
         }
     }
@@ -324,7 +435,10 @@ impl Job {
     pub fn job_type_priority(&self) -> u8 {
         match self {
             Job::Unarchive { .. } => 0,
-            Job::Download { .. } | Job::CacheFetch { .. } | Job::Process { .. } => 2,
+            Job::Download { .. }
+            | Job::CacheFetch { .. }
+            | Job::CacheSync { .. }
+            | Job::Process { .. } => 2,
         }
     }
 }
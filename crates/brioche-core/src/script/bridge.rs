@@ -254,7 +254,7 @@ impl RuntimeBridge {
                                                 .join_with(", ");
                                             anyhow::anyhow!("failed to resolve Brioche.glob({patterns}) from {specifier}, were the patterns passed in as string literals?")
                                         }
-                                        StaticQuery::Download { url } => {
+                                        StaticQuery::Download { url, .. } => {
                                             anyhow::anyhow!("failed to resolve Brioche.download({url:?}) from {specifier}, was the URL passed in as a string literal?")
                                         }
                                         StaticQuery::GitRef(GitRefOptions { repository, ref_ }) => {
@@ -285,7 +285,7 @@ impl RuntimeBridge {
                                     GetStaticResult::Recipe(recipe)
                                 }
                                 StaticOutput::Kind(StaticOutputKind::Download { hash }) => {
-                                    let StaticQuery::Download { url } = static_ else {
+                                    let StaticQuery::Download { url, .. } = static_ else {
                                         let _ = result_tx.send(Err(anyhow::anyhow!("invalid 'download' static output kind for non-download static")));
                                         return;
                                     };
@@ -293,6 +293,7 @@ impl RuntimeBridge {
                                     GetStaticResult::Recipe(Recipe::Download(DownloadRecipe {
                                         url,
                                         hash,
+                                        mirrors: vec![],
                                     }))
                                 }
                                 StaticOutput::Kind(StaticOutputKind::GitRef { commit }) => {
@@ -51,15 +51,69 @@ pub async fn sync_bakes(
         results.get_or_insert_default().merge(legacy_results);
     }
 
-    if brioche.cache_client.writable {
-        let new_results = new_sync::sync_bakes(brioche, bakes, verbose).await?;
-        results.get_or_insert_default().merge(new_results);
+    if brioche.cache_client.is_writable() {
+        let new_results =
+            new_sync::sync_bakes(brioche, bakes, &CacheSyncOptions::default(), verbose).await?;
+        results.get_or_insert_default().merge(SyncBakesResults {
+            num_new_blobs: 0,
+            num_new_recipes: new_results.num_new_artifacts,
+            num_new_bakes: new_results.num_new_bakes,
+        });
     }
 
     let results = results.context("cannot sync: cache is read-only")?;
     Ok(results)
 }
 
+/// Sync bakes and their output artifacts to the writable cache, reporting
+/// progress with a reporter job. Unlike [`sync_bakes`], this never syncs
+/// to the legacy registry.
+pub async fn sync_bakes_to_cache(
+    brioche: &Brioche,
+    bakes: Vec<(crate::recipe::Recipe, crate::recipe::Artifact)>,
+    options: &CacheSyncOptions,
+) -> anyhow::Result<CacheSyncResults> {
+    anyhow::ensure!(
+        brioche.cache_client.is_writable(),
+        "cannot sync: cache is read-only"
+    );
+
+    new_sync::sync_bakes(brioche, bakes, options, true).await
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CacheSyncOptions {
+    /// Only sync bakes of process recipes, skipping downloads and git
+    /// checkouts (which can be fetched again from their original source).
+    pub processes_only: bool,
+
+    /// Skip artifacts larger than this many bytes, along with any bakes
+    /// that output them.
+    pub max_artifact_size: Option<u64>,
+
+    /// Find what would be synced without writing to the cache.
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Default, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheSyncResults {
+    pub dry_run: bool,
+    pub num_bakes: usize,
+    pub num_new_bakes: usize,
+    pub num_artifacts: usize,
+    pub num_new_artifacts: usize,
+    pub num_skipped_bakes: usize,
+    pub skipped_artifacts: Vec<SkippedArtifact>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedArtifact {
+    pub artifact_hash: crate::recipe::RecipeHash,
+    pub size: u64,
+}
+
 #[derive(Debug, Default, Clone, Copy)]
 pub struct SyncBakesResults {
     pub num_new_blobs: usize,
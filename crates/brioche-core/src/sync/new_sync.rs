@@ -1,27 +1,107 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Context as _;
 use futures::{StreamExt as _, TryStreamExt as _};
 
-use crate::{Brioche, utils::DisplayDuration};
+use crate::{
+    Brioche,
+    recipe::{Artifact, Recipe},
+    reporter::{
+        JobId,
+        job::{NewJob, UpdateJob},
+    },
+};
 
-use super::SyncBakesResults;
+use super::{CacheSyncOptions, CacheSyncResults, SkippedArtifact};
 
-#[expect(clippy::print_stdout)]
 pub async fn sync_bakes(
     brioche: &Brioche,
-    bakes: Vec<(crate::recipe::Recipe, crate::recipe::Artifact)>,
+    bakes: Vec<(Recipe, Artifact)>,
+    options: &CacheSyncOptions,
     verbose: bool,
-) -> anyhow::Result<SyncBakesResults> {
-    let sync_start = std::time::Instant::now();
+) -> anyhow::Result<CacheSyncResults> {
+    let mut results = CacheSyncResults {
+        dry_run: options.dry_run,
+        ..Default::default()
+    };
 
-    // Sync all artifacts to cache first
-    let artifacts = bakes
-        .iter()
-        .map(|(_, artifact)| artifact)
-        .cloned()
+    // Filter out bakes by kind
+    let num_bakes = bakes.len();
+    let mut bakes = bakes
+        .into_iter()
+        .filter(|(recipe, _)| {
+            !options.processes_only
+                || matches!(recipe, Recipe::Process(_) | Recipe::CompleteProcess(_))
+        })
         .collect::<Vec<_>>();
-    let num_new_artifacts = futures::stream::iter(artifacts)
+    results.num_skipped_bakes = num_bakes - bakes.len();
+
+    let mut artifacts = HashMap::new();
+    for (_, artifact) in &bakes {
+        artifacts
+            .entry(artifact.hash())
+            .or_insert_with(|| artifact.clone());
+    }
+
+    // Filter out artifacts that are too large, along with the bakes that
+    // output them. Bakes should never point to an artifact that isn't in
+    // the cache
+    if let Some(max_artifact_size) = options.max_artifact_size {
+        let artifact_sizes = futures::stream::iter(artifacts.values().cloned())
+            .map(|artifact| async move {
+                let size = artifact_size(brioche, &artifact).await?;
+                anyhow::Ok((artifact.hash(), size))
+            })
+            .buffer_unordered(25)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut skipped_hashes = HashSet::new();
+        for (artifact_hash, size) in artifact_sizes {
+            if size > max_artifact_size {
+                artifacts.remove(&artifact_hash);
+                skipped_hashes.insert(artifact_hash);
+                results.skipped_artifacts.push(SkippedArtifact {
+                    artifact_hash,
+                    size,
+                });
+            }
+        }
+
+        let num_unskipped_bakes = bakes.len();
+        bakes.retain(|(_, artifact)| !skipped_hashes.contains(&artifact.hash()));
+        results.num_skipped_bakes += num_unskipped_bakes - bakes.len();
+    }
+
+    results.num_bakes = bakes.len();
+    results.num_artifacts = artifacts.len();
+
+    // Only report progress for verbose syncs, so syncing each bake in the
+    // background during a build doesn't add a job per bake
+    let job_id = if verbose {
+        Some(brioche.reporter.add_job(NewJob::CacheSync {
+            total_bakes: bakes.len().try_into()?,
+            total_artifacts: artifacts.len().try_into()?,
+            started_at: std::time::Instant::now(),
+        }))
+    } else {
+        None
+    };
+
+    // Sync all artifacts to cache first
+    results.num_new_artifacts = futures::stream::iter(artifacts.into_values())
         .map(|artifact| {
             let brioche = brioche.clone();
-            async move { crate::cache::save_artifact(&brioche, artifact).await }
+            let dry_run = options.dry_run;
+            async move {
+                let is_new_artifact = if dry_run {
+                    !crate::cache::writable_cache_has_artifact(&brioche, artifact.hash()).await?
+                } else {
+                    crate::cache::save_artifact(&brioche, artifact).await?
+                };
+                add_synced(&brioche, job_id, 0, 1);
+                anyhow::Ok(is_new_artifact)
+            }
         })
         .buffer_unordered(25)
         .try_fold(0, |mut sum, is_new_artifact| async move {
@@ -34,13 +114,21 @@ pub async fn sync_bakes(
         .await?;
 
     // Sync all bakes to cache
-    let num_bakes = bakes.len();
-    let num_new_bakes = futures::stream::iter(bakes)
+    results.num_new_bakes = futures::stream::iter(bakes)
         .map(|(recipe, artifact)| {
             let input_hash = recipe.hash();
             let output_hash = artifact.hash();
             let brioche = brioche.clone();
-            async move { crate::cache::save_bake(&brioche, input_hash, output_hash).await }
+            let dry_run = options.dry_run;
+            async move {
+                let is_new_bake = if dry_run {
+                    !crate::cache::writable_cache_has_bake(&brioche, input_hash).await?
+                } else {
+                    crate::cache::save_bake(&brioche, input_hash, output_hash).await?
+                };
+                add_synced(&brioche, job_id, 1, 0);
+                anyhow::Ok(is_new_bake)
+            }
         })
         .buffer_unordered(25)
         .try_fold(0, |mut sum, is_new_bake| async move {
@@ -52,16 +140,58 @@ pub async fn sync_bakes(
         })
         .await?;
 
-    if verbose {
-        println!(
-            "Finished syncing {num_new_bakes} / {num_bakes} bakes to cache in {}",
-            DisplayDuration(sync_start.elapsed())
+    if let Some(job_id) = job_id {
+        brioche.reporter.update_job(
+            job_id,
+            UpdateJob::CacheSyncFinish {
+                finished_at: std::time::Instant::now(),
+            },
         );
     }
 
-    Ok(SyncBakesResults {
-        num_new_blobs: 0,
-        num_new_bakes,
-        num_new_recipes: num_new_artifacts,
-    })
+    Ok(results)
+}
+
+fn add_synced(brioche: &Brioche, job_id: Option<JobId>, synced_bakes: u64, synced_artifacts: u64) {
+    let Some(job_id) = job_id else {
+        return;
+    };
+
+    brioche.reporter.update_job(
+        job_id,
+        UpdateJob::CacheSyncAdd {
+            synced_bakes,
+            synced_artifacts,
+        },
+    );
+}
+
+/// Get the total size of all the blobs in an artifact. Blobs that haven't
+/// been fetched yet (e.g. from an artifact loaded from the cache lazily)
+/// use the size recorded when the artifact was loaded.
+async fn artifact_size(brioche: &Brioche, artifact: &Artifact) -> anyhow::Result<u64> {
+    let mut blobs = HashSet::new();
+    crate::references::descendent_artifact_blobs(brioche, [artifact.clone()], &mut blobs).await?;
+
+    let mut size = 0;
+    for blob_hash in blobs {
+        let blob_path = crate::blob::local_blob_path(brioche, blob_hash);
+        match tokio::fs::metadata(&blob_path).await {
+            Ok(metadata) => {
+                size += metadata.len();
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                let lazy_size = crate::cache::lazy_blobs::lazy_blob_size(brioche, blob_hash)
+                    .await?
+                    .with_context(|| format!("blob {blob_hash} not found"))?;
+                size += lazy_size;
+            }
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("failed to get size of blob {blob_hash}"));
+            }
+        }
+    }
+
+    Ok(size)
 }
@@ -0,0 +1,130 @@
+//! Helpers for the small HTTP servers built into Brioche, like
+//! `brioche worker` and `brioche serve-cache`.
+
+use std::future::Future;
+
+use anyhow::Context as _;
+use futures::TryStreamExt as _;
+use http_body_util::BodyExt as _;
+use hyper::body::Bytes;
+
+pub type Request = hyper::Request<hyper::body::Incoming>;
+pub type ResponseBody = http_body_util::combinators::UnsyncBoxBody<Bytes, std::io::Error>;
+pub type Response = hyper::Response<ResponseBody>;
+
+/// Accept HTTP/1 connections from `listener` until `cancellation_token`
+/// is cancelled, calling `handler` for each request.
+pub async fn serve<F, Fut>(
+    listener: tokio::net::TcpListener,
+    cancellation_token: &tokio_util::sync::CancellationToken,
+    handler: F,
+) -> anyhow::Result<()>
+where
+    F: Fn(Request) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send + 'static,
+{
+    loop {
+        let (stream, remote_addr) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            () = cancellation_token.cancelled() => {
+                break;
+            }
+        };
+
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |request| {
+                let response = handler(request);
+                async move { Ok::<_, std::convert::Infallible>(response.await) }
+            });
+
+            let io = hyper_util::rt::TokioIo::new(stream);
+            let result = hyper::server::conn::http1::Builder::new()
+                .serve_connection(io, service)
+                .await;
+            if let Err(error) = result {
+                tracing::warn!(%remote_addr, "HTTP connection failed: {error}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+pub async fn read_body(request: Request) -> anyhow::Result<Bytes> {
+    let body = request
+        .into_body()
+        .collect()
+        .await
+        .context("failed to read request body")?;
+    Ok(body.to_bytes())
+}
+
+/// Read a request body incrementally, without buffering all of it in
+/// memory.
+pub fn body_reader(request: Request) -> impl tokio::io::AsyncBufRead + Unpin {
+    let stream = request
+        .into_body()
+        .into_data_stream()
+        .map_err(std::io::Error::other);
+    tokio_util::io::StreamReader::new(stream)
+}
+
+pub async fn read_json<T>(request: Request) -> anyhow::Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let body = read_body(request).await?;
+    let value = serde_json::from_slice(&body).context("failed to parse request body")?;
+    Ok(value)
+}
+
+pub fn json_response<T>(value: &T) -> anyhow::Result<Response>
+where
+    T: serde::Serialize,
+{
+    let body = serde_json::to_vec(value)?;
+    let response = hyper::Response::builder()
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(full_body(body))?;
+    Ok(response)
+}
+
+/// Respond with the contents of a file, streaming it instead of reading
+/// it all into memory. Responds with 404 if the file doesn't exist.
+pub async fn file_response(path: &std::path::Path) -> anyhow::Result<Response> {
+    let file = match tokio::fs::File::open(path).await {
+        Ok(file) => file,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(not_found());
+        }
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to open {}", path.display()));
+        }
+    };
+    let length = file.metadata().await?.len();
+
+    let stream = tokio_util::io::ReaderStream::new(file).map_ok(hyper::body::Frame::data);
+    let body = http_body_util::StreamBody::new(stream).boxed_unsync();
+    let response = hyper::Response::builder()
+        .header(hyper::header::CONTENT_TYPE, "application/octet-stream")
+        .header(hyper::header::CONTENT_LENGTH, length)
+        .body(body)?;
+    Ok(response)
+}
+
+pub fn full_body(content: impl Into<Bytes>) -> ResponseBody {
+    http_body_util::Full::new(content.into())
+        .map_err(|never| match never {})
+        .boxed_unsync()
+}
+
+pub fn status_response(status: hyper::StatusCode, message: impl Into<String>) -> Response {
+    let mut response = hyper::Response::new(full_body(message.into()));
+    *response.status_mut() = status;
+    response
+}
+
+pub fn not_found() -> Response {
+    status_response(hyper::StatusCode::NOT_FOUND, "not found")
+}
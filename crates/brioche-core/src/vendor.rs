@@ -0,0 +1,144 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context as _;
+use futures::{StreamExt as _, TryStreamExt as _};
+
+use crate::{
+    Brioche,
+    project::{ProjectHash, Projects},
+    recipe::Artifact,
+    references::ProjectReferences,
+};
+
+/// The name of the config snippet written alongside a vendored cache.
+pub const VENDOR_CONFIG_FILENAME: &str = "brioche-config.toml";
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct VendorResults {
+    pub num_projects: usize,
+    pub num_bakes: usize,
+}
+
+/// Bake a project export, then write everything needed to build it
+/// without network access into a `file://` cache at `cache_dir`. This
+/// includes every project it depends on, plus the bakes for any
+/// downloads, git checkouts, and processes it uses.
+pub async fn vendor_project(
+    brioche: &Brioche,
+    projects: &Projects,
+    project_hash: ProjectHash,
+    export: &str,
+    cache_dir: &Path,
+) -> anyhow::Result<VendorResults> {
+    tokio::fs::create_dir_all(cache_dir)
+        .await
+        .with_context(|| format!("failed to create directory {}", cache_dir.display()))?;
+    let cache_dir = tokio::fs::canonicalize(cache_dir).await?;
+    let cache_url = url::Url::from_directory_path(&cache_dir)
+        .map_err(|()| anyhow::anyhow!("invalid vendor path {}", cache_dir.display()))?;
+
+    // Bake the export first, so all of its downloads, git checkouts, and
+    // processes are recorded as descendents of the project
+    let recipe = crate::script::evaluate::evaluate(brioche, projects, project_hash, export).await?;
+    crate::bake::bake(
+        brioche,
+        recipe,
+        &crate::bake::BakeScope::Project {
+            project_hash,
+            export: export.to_string(),
+        },
+    )
+    .await?;
+
+    // Write to the vendor directory instead of the configured cache, and
+    // skip syncing to the registry
+    let mut vendor_brioche = brioche.clone();
+    vendor_brioche.registry_client = crate::registry::RegistryClient::disabled();
+    vendor_brioche.cache_client = crate::cache::cache_client_from_config_or_default(
+        Some(&crate::config::CacheConfig {
+            url: cache_url,
+            max_concurrent_operations: crate::cache::DEFAULT_CACHE_MAX_CONCURRENT_OPERATIONS,
+            read_only: false,
+            allow_http: None,
+            priority: 0,
+            trusted_keys: vec![],
+            signing_key_path: None,
+            auth: None,
+            headers: BTreeMap::new(),
+            compression: crate::config::CacheCompressionConfig::default(),
+        }),
+        &brioche.network_options,
+    )
+    .await?;
+
+    let bakes = crate::references::descendent_project_bakes(brioche, project_hash, export).await?;
+    let num_bakes = bakes.len();
+    crate::sync::sync_bakes(&vendor_brioche, bakes, false).await?;
+
+    // Save each project individually, so registry dependencies can be
+    // fetched by hash
+    let mut project_references = ProjectReferences::default();
+    crate::references::project_references(
+        brioche,
+        projects,
+        &mut project_references,
+        [project_hash],
+    )
+    .await?;
+    let project_hashes = project_references.projects.into_keys().collect::<Vec<_>>();
+    let num_projects = project_hashes.len();
+    futures::stream::iter(project_hashes)
+        .map(|project_hash| {
+            let vendor_brioche = vendor_brioche.clone();
+            async move {
+                let project_artifact = crate::project::artifact::create_artifact_with_projects(
+                    &vendor_brioche,
+                    projects,
+                    &[project_hash],
+                )
+                .await?;
+                let project_artifact = Artifact::Directory(project_artifact);
+                let project_artifact_hash = project_artifact.hash();
+                crate::cache::save_artifact(&vendor_brioche, project_artifact).await?;
+                crate::cache::save_project_artifact_hash(
+                    &vendor_brioche,
+                    project_hash,
+                    project_artifact_hash,
+                )
+                .await?;
+
+                anyhow::Ok(())
+            }
+        })
+        .buffer_unordered(25)
+        .try_collect::<()>()
+        .await?;
+
+    let config_path = cache_dir.join(VENDOR_CONFIG_FILENAME);
+    tokio::fs::write(&config_path, vendor_config_snippet(&cache_dir)?)
+        .await
+        .with_context(|| format!("failed to write {}", config_path.display()))?;
+
+    Ok(VendorResults {
+        num_projects,
+        num_bakes,
+    })
+}
+
+/// Returns a Brioche config that builds offline using the vendored cache
+/// at `cache_dir`.
+pub fn vendor_config_snippet(cache_dir: &Path) -> anyhow::Result<String> {
+    let cache_url = url::Url::from_directory_path(cache_dir)
+        .map_err(|()| anyhow::anyhow!("invalid vendor path {}", cache_dir.display()))?;
+
+    Ok(format!(
+        r#"# Add to your Brioche config file (e.g. ~/.config/brioche/config.toml).
+# Update the URL if this directory gets moved.
+offline = true
+
+[cache]
+url = "{cache_url}"
+read_only = true
+"#
+    ))
+}
@@ -20,6 +20,7 @@ async fn test_bake_cache_nested() -> anyhow::Result<()> {
     let hello_download = Recipe::Download(DownloadRecipe {
         hash: hello_hash.clone(),
         url: format!("{server_url}/file.txt").parse().unwrap(),
+        mirrors: vec![],
     });
 
     let hello_nested_download =
@@ -64,6 +65,7 @@ async fn test_bake_cache_unnested() -> anyhow::Result<()> {
     let hello_download = Recipe::Download(DownloadRecipe {
         hash: hello_hash.clone(),
         url: format!("{server_url}/file.txt").parse().unwrap(),
+        mirrors: vec![],
     });
 
     let hello_nested_download =
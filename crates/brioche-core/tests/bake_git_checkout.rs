@@ -0,0 +1,255 @@
+use std::path::Path;
+
+use assert_matches::assert_matches;
+use brioche_core::recipe::{GitCheckout, Recipe};
+use brioche_test_support::bake_without_meta;
+
+fn git(repo: &Path, args: &[&str]) -> String {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args([
+            "-c",
+            "user.name=Brioche",
+            "-c",
+            "user.email=brioche@example.com",
+            "-c",
+            "commit.gpgsign=false",
+        ])
+        .args(args)
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "git {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+fn init_repo(path: &Path, files: &[(&str, &str)]) -> String {
+    std::fs::create_dir_all(path).unwrap();
+    git(path, &["init", "--quiet"]);
+    write_files(path, files);
+    git(path, &["add", "--all"]);
+    git(path, &["commit", "--quiet", "--message", "Initial commit"]);
+    git(path, &["rev-parse", "HEAD"])
+}
+
+fn write_files(path: &Path, files: &[(&str, &str)]) {
+    for (file_path, content) in files {
+        let file_path = path.join(file_path);
+        std::fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+        std::fs::write(file_path, content).unwrap();
+    }
+}
+
+fn git_checkout(repository: &Path, commit: &str, submodules: bool) -> Recipe {
+    Recipe::GitCheckout(GitCheckout {
+        repository: url::Url::from_directory_path(repository).unwrap(),
+        commit: commit.to_string(),
+        submodules,
+    })
+}
+
+#[tokio::test]
+async fn test_bake_git_checkout() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+    let repos = tempfile::tempdir()?;
+    let repo = repos.path().join("repo");
+
+    let commit = init_repo(
+        &repo,
+        &[
+            ("README", "readme"),
+            ("src/main.c", "main"),
+            ("run.sh", "#!/bin/sh"),
+        ],
+    );
+    git(&repo, &["update-index", "--chmod=+x", "run.sh"]);
+    std::os::unix::fs::symlink("README", repo.join("readme"))?;
+    git(&repo, &["add", "--all"]);
+    git(&repo, &["commit", "--quiet", "--message", "Add script"]);
+    let pinned_commit = git(&repo, &["rev-parse", "HEAD"]);
+
+    // Later commits should not affect the checkout
+    write_files(&repo, &[("README", "updated")]);
+    git(
+        &repo,
+        &["commit", "--quiet", "--all", "--message", "Update"],
+    );
+
+    let readme_blob = brioche_test_support::blob(&brioche, b"readme").await;
+    let main_blob = brioche_test_support::blob(&brioche, b"main").await;
+    let run_blob = brioche_test_support::blob(&brioche, b"#!/bin/sh").await;
+
+    assert_eq!(
+        bake_without_meta(&brioche, git_checkout(&repo, &pinned_commit, false)).await?,
+        brioche_test_support::dir(
+            &brioche,
+            [
+                ("README", brioche_test_support::file(readme_blob, false)),
+                ("src/main.c", brioche_test_support::file(main_blob, false)),
+                ("run.sh", brioche_test_support::file(run_blob, true)),
+                ("readme", brioche_test_support::symlink("README")),
+            ]
+        )
+        .await,
+    );
+
+    assert_eq!(
+        bake_without_meta(&brioche, git_checkout(&repo, &commit, false)).await?,
+        brioche_test_support::dir(
+            &brioche,
+            [
+                ("README", brioche_test_support::file(readme_blob, false)),
+                ("src/main.c", brioche_test_support::file(main_blob, false)),
+                ("run.sh", brioche_test_support::file(run_blob, false)),
+            ]
+        )
+        .await,
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_git_checkout_submodules() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+    let repos = tempfile::tempdir()?;
+    let repo = repos.path().join("repo");
+    let sub_repo = repos.path().join("sub");
+
+    let sub_commit = init_repo(&sub_repo, &[("lib.c", "lib")]);
+
+    let gitmodules = "[submodule \"sub\"]\n\tpath = vendor/sub\n\turl = ../sub\n";
+    init_repo(&repo, &[("main.c", "main"), (".gitmodules", gitmodules)]);
+    git(
+        &repo,
+        &[
+            "update-index",
+            "--add",
+            "--cacheinfo",
+            &format!("160000,{sub_commit},vendor/sub"),
+        ],
+    );
+    git(&repo, &["commit", "--quiet", "--message", "Add submodule"]);
+    let commit = git(&repo, &["rev-parse", "HEAD"]);
+
+    let main_blob = brioche_test_support::blob(&brioche, b"main").await;
+    let lib_blob = brioche_test_support::blob(&brioche, b"lib").await;
+    let gitmodules_blob = brioche_test_support::blob(&brioche, gitmodules).await;
+
+    // Without submodules, the submodule is left as an empty directory
+    assert_eq!(
+        bake_without_meta(&brioche, git_checkout(&repo, &commit, false)).await?,
+        brioche_test_support::dir(
+            &brioche,
+            [
+                ("main.c", brioche_test_support::file(main_blob, false)),
+                (
+                    ".gitmodules",
+                    brioche_test_support::file(gitmodules_blob, false)
+                ),
+                ("vendor/sub", brioche_test_support::dir_empty()),
+            ]
+        )
+        .await,
+    );
+
+    assert_eq!(
+        bake_without_meta(&brioche, git_checkout(&repo, &commit, true)).await?,
+        brioche_test_support::dir(
+            &brioche,
+            [
+                ("main.c", brioche_test_support::file(main_blob, false)),
+                (
+                    ".gitmodules",
+                    brioche_test_support::file(gitmodules_blob, false)
+                ),
+                (
+                    "vendor/sub/lib.c",
+                    brioche_test_support::file(lib_blob, false)
+                ),
+            ]
+        )
+        .await,
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_git_checkout_invalid_commit() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+    let repos = tempfile::tempdir()?;
+    let repo = repos.path().join("repo");
+
+    let commit = init_repo(&repo, &[("README", "readme")]);
+
+    // Only full commit hashes are allowed
+    assert_matches!(
+        bake_without_meta(&brioche, git_checkout(&repo, &commit[..7], false)).await,
+        Err(_)
+    );
+    assert_matches!(
+        bake_without_meta(&brioche, git_checkout(&repo, "main", false)).await,
+        Err(_)
+    );
+
+    // Commits that don't exist in the repository fail
+    assert_matches!(
+        bake_without_meta(
+            &brioche,
+            git_checkout(&repo, "0123456789abcdef0123456789abcdef01234567", false)
+        )
+        .await,
+        Err(_)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_git_checkout_rewrite_url() -> anyhow::Result<()> {
+    let repos = tempfile::tempdir()?;
+    let repo = repos.path().join("repo");
+    let repo_url = url::Url::from_directory_path(&repo).unwrap();
+
+    let (brioche, _context) = brioche_test_support::brioche_test_with(|builder| {
+        builder.config(brioche_core::config::BriocheConfig {
+            download: brioche_core::config::DownloadConfig {
+                rewrites: vec![brioche_core::config::UrlRewriteRule {
+                    prefix: "https://git.example.invalid/repo".to_string(),
+                    replacement: repo_url.to_string(),
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    })
+    .await;
+
+    let commit = init_repo(&repo, &[("README", "readme")]);
+    let readme_blob = brioche_test_support::blob(&brioche, b"readme").await;
+
+    let checkout = Recipe::GitCheckout(GitCheckout {
+        repository: "https://git.example.invalid/repo".parse()?,
+        commit,
+        submodules: false,
+    });
+
+    assert_eq!(
+        bake_without_meta(&brioche, checkout).await?,
+        brioche_test_support::dir(
+            &brioche,
+            [("README", brioche_test_support::file(readme_blob, false))]
+        )
+        .await,
+    );
+
+    Ok(())
+}
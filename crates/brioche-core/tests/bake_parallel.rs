@@ -20,6 +20,7 @@ async fn test_bake_parallel_no_duplicates() -> anyhow::Result<()> {
     let hello_download = Recipe::Download(DownloadRecipe {
         hash: hello_hash,
         url: format!("{server_url}/file.txt").parse().unwrap(),
+        mirrors: vec![],
     });
 
     let download_1 = bake_without_meta(&brioche, hello_download.clone());
@@ -1,6 +1,9 @@
 #![cfg(target_os = "linux")]
 
-use std::{collections::BTreeMap, future::Future};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    future::Future,
+};
 
 use anyhow::Context as _;
 use assert_matches::assert_matches;
@@ -29,12 +32,15 @@ fn utils() -> Recipe {
     let utils_download = Recipe::Download(DownloadRecipe {
         url: "https://development-content.brioche.dev/github.com/tangramdotdev/bootstrap/2023-07-06/utils_amd64_linux.tar.zstd".parse().unwrap(),
         hash: sha256_hash("eb29ea059fcd9ca457841f5c79151721a74761a31610d694bce61a62f4de6d33"),
+        mirrors: vec![],
     });
 
     Recipe::Unarchive(Unarchive {
         file: Box::new(brioche_test_support::without_meta(utils_download)),
         archive: ArchiveFormat::Tar,
         compression: CompressionFormat::Zstd,
+        strip_components: 0,
+        include: BTreeSet::new(),
     })
 }
 
@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+
+use assert_matches::assert_matches;
+use brioche_core::recipe::{ProcessTemplate, ProcessTemplateComponent, Recipe, Substitute};
+use brioche_test_support::{bake_without_meta, tpl, without_meta};
+
+fn substitute(
+    file: Recipe,
+    replacements: impl IntoIterator<Item = (&'static str, ProcessTemplate)>,
+) -> Recipe {
+    Recipe::Substitute(Substitute {
+        file: Box::new(without_meta(file)),
+        replacements: replacements
+            .into_iter()
+            .map(|(placeholder, value)| (placeholder.into(), value))
+            .collect::<BTreeMap<_, _>>(),
+    })
+}
+
+#[tokio::test]
+async fn test_bake_substitute_literals() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let input_blob = brioche_test_support::blob(
+        &brioche,
+        b"prefix=@prefix@\nversion=@version@\n@prefix@/lib",
+    )
+    .await;
+    let expected_blob =
+        brioche_test_support::blob(&brioche, b"prefix=/usr\nversion=1.0\n/usr/lib").await;
+
+    let recipe = substitute(
+        brioche_test_support::lazy_file(input_blob, false),
+        [("@prefix@", tpl("/usr")), ("@version@", tpl("1.0"))],
+    );
+
+    assert_eq!(
+        bake_without_meta(&brioche, recipe).await?,
+        brioche_test_support::file(expected_blob, false),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_substitute_single_pass() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let input_blob = brioche_test_support::blob(&brioche, b"@a@ @b@ @version@ @ver").await;
+    let expected_blob = brioche_test_support::blob(&brioche, b"@b@ 2 1.0 v").await;
+
+    // Replaced values should not be substituted again, and the longest
+    // matching placeholder should win
+    let recipe = substitute(
+        brioche_test_support::lazy_file(input_blob, false),
+        [
+            ("@a@", tpl("@b@")),
+            ("@b@", tpl("2")),
+            ("@ver", tpl("v")),
+            ("@version@", tpl("1.0")),
+        ],
+    );
+
+    assert_eq!(
+        bake_without_meta(&brioche, recipe).await?,
+        brioche_test_support::file(expected_blob, false),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_substitute_preserves_executable_and_resources() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let input_blob = brioche_test_support::blob(&brioche, b"#!/bin/sh\necho @message@").await;
+    let expected_blob = brioche_test_support::blob(&brioche, b"#!/bin/sh\necho hello").await;
+    let resource_blob = brioche_test_support::blob(&brioche, b"resource").await;
+
+    let resources = brioche_test_support::dir_value(
+        &brioche,
+        [("foo.txt", brioche_test_support::file(resource_blob, false))],
+    )
+    .await;
+
+    let recipe = substitute(
+        Recipe::from(brioche_test_support::file_with_resources(
+            input_blob,
+            true,
+            resources.clone(),
+        )),
+        [("@message@", tpl("hello"))],
+    );
+
+    assert_eq!(
+        bake_without_meta(&brioche, recipe).await?,
+        brioche_test_support::file_with_resources(expected_blob, true, resources),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_substitute_input() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let input_blob = brioche_test_support::blob(&brioche, b"path=@path@/dep.txt").await;
+    let dep_blob = brioche_test_support::blob(&brioche, b"dep").await;
+    let dep = brioche_test_support::lazy_dir([(
+        "dep.txt",
+        brioche_test_support::lazy_file(dep_blob, false),
+    )]);
+    let dep_artifact = brioche_test_support::dir(
+        &brioche,
+        [("dep.txt", brioche_test_support::file(dep_blob, false))],
+    )
+    .await;
+
+    // Inputs are attached as resources, and render as their path within
+    // the resource directory
+    let resource_path = format!("substitute/{}", dep_artifact.hash());
+    let expected_blob =
+        brioche_test_support::blob(&brioche, format!("path={resource_path}/dep.txt")).await;
+    let expected_resources =
+        brioche_test_support::dir_value(&brioche, [(resource_path, dep_artifact)]).await;
+
+    let recipe = substitute(
+        brioche_test_support::lazy_file(input_blob, false),
+        [("@path@", brioche_test_support::template_input(dep))],
+    );
+
+    assert_eq!(
+        bake_without_meta(&brioche, recipe).await?,
+        brioche_test_support::file_with_resources(expected_blob, false, expected_resources),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_substitute_output_path_fails() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let input_blob = brioche_test_support::blob(&brioche, b"path=@path@").await;
+
+    // The output path only exists on the local machine, so it's rejected
+    // to keep the output the same on every machine
+    let recipe = substitute(
+        brioche_test_support::lazy_file(input_blob, false),
+        [(
+            "@path@",
+            ProcessTemplate {
+                components: vec![ProcessTemplateComponent::OutputPath],
+            },
+        )],
+    );
+
+    assert_matches!(bake_without_meta(&brioche, recipe).await, Err(_));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_substitute_non_file_fails() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let recipe = substitute(brioche_test_support::lazy_dir_empty(), []);
+
+    assert_matches!(bake_without_meta(&brioche, recipe).await, Err(_));
+
+    Ok(())
+}
@@ -0,0 +1,714 @@
+use std::{collections::BTreeSet, io::Write as _};
+
+use assert_matches::assert_matches;
+use brioche_core::{
+    Brioche,
+    blob::BlobHash,
+    recipe::{ArchiveFormat, CompressionFormat, Recipe, Unarchive, WithMeta},
+};
+use brioche_test_support::bake_without_meta;
+use tokio::io::AsyncReadExt as _;
+
+enum TarEntry {
+    File(&'static str, &'static [u8]),
+    Directory(&'static str),
+    Symlink(&'static str, &'static str),
+    Hardlink(&'static str, &'static str),
+}
+
+async fn tar_blob(brioche: &Brioche, entries: &[TarEntry]) -> brioche_core::blob::BlobHash {
+    let mut builder = tar::Builder::new(vec![]);
+    for entry in entries {
+        let mut header = tar::Header::new_gnu();
+        match entry {
+            TarEntry::File(path, content) => {
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_mode(0o644);
+                header.set_size(content.len() as u64);
+                builder.append_data(&mut header, path, *content).unwrap();
+            }
+            TarEntry::Directory(path) => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_size(0);
+                builder.append_data(&mut header, path, &[][..]).unwrap();
+            }
+            TarEntry::Symlink(path, target) => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_mode(0o777);
+                header.set_size(0);
+                builder.append_link(&mut header, path, target).unwrap();
+            }
+            TarEntry::Hardlink(path, target) => {
+                header.set_entry_type(tar::EntryType::Link);
+                header.set_mode(0o644);
+                header.set_size(0);
+                builder.append_link(&mut header, path, target).unwrap();
+            }
+        }
+    }
+    let archive = builder.into_inner().unwrap();
+
+    brioche_test_support::blob(brioche, archive).await
+}
+
+fn ar_archive(entries: &[(&str, &[u8], u32)]) -> Vec<u8> {
+    let mut builder = ar::Builder::new(vec![]);
+    for (path, content, mode) in entries {
+        let mut header = ar::Header::new(path.as_bytes().to_vec(), content.len() as u64);
+        header.set_mode(*mode);
+        builder.append(&header, *content).unwrap();
+    }
+    builder.into_inner().unwrap()
+}
+
+struct CpioEntry {
+    ino: u32,
+    mode: u32,
+    nlink: u32,
+    path: &'static str,
+    content: &'static [u8],
+}
+
+const fn cpio_file(ino: u32, path: &'static str, content: &'static [u8], mode: u32) -> CpioEntry {
+    CpioEntry {
+        ino,
+        mode: 0o100_000 | mode,
+        nlink: 1,
+        path,
+        content,
+    }
+}
+
+const fn cpio_dir(ino: u32, path: &'static str) -> CpioEntry {
+    CpioEntry {
+        ino,
+        mode: 0o040_755,
+        nlink: 2,
+        path,
+        content: b"",
+    }
+}
+
+const fn cpio_symlink(ino: u32, path: &'static str, target: &'static str) -> CpioEntry {
+    CpioEntry {
+        ino,
+        mode: 0o120_777,
+        nlink: 1,
+        path,
+        content: target.as_bytes(),
+    }
+}
+
+fn cpio_newc_archive(entries: &[CpioEntry]) -> Vec<u8> {
+    fn pad(archive: &mut Vec<u8>) {
+        while archive.len() % 4 != 0 {
+            archive.push(0);
+        }
+    }
+
+    let trailer = CpioEntry {
+        ino: 0,
+        mode: 0,
+        nlink: 1,
+        path: "TRAILER!!!",
+        content: b"",
+    };
+
+    let mut archive = vec![];
+    for entry in entries.iter().chain([&trailer]) {
+        let fields = [
+            entry.ino,
+            entry.mode,
+            0,
+            0,
+            entry.nlink,
+            0,
+            u32::try_from(entry.content.len()).unwrap(),
+            0,
+            0,
+            0,
+            0,
+            u32::try_from(entry.path.len() + 1).unwrap(),
+            0,
+        ];
+
+        archive.extend_from_slice(b"070701");
+        for field in fields {
+            archive.extend_from_slice(format!("{field:08X}").as_bytes());
+        }
+        archive.extend_from_slice(entry.path.as_bytes());
+        archive.push(0);
+        pad(&mut archive);
+        archive.extend_from_slice(entry.content);
+        pad(&mut archive);
+    }
+
+    archive
+}
+
+fn cpio_odc_archive(entries: &[CpioEntry]) -> Vec<u8> {
+    let trailer = CpioEntry {
+        ino: 0,
+        mode: 0,
+        nlink: 1,
+        path: "TRAILER!!!",
+        content: b"",
+    };
+
+    let mut archive = vec![];
+    for entry in entries.iter().chain([&trailer]) {
+        archive.extend_from_slice(b"070707");
+        let fields = [
+            (0, 6),
+            (entry.ino as usize, 6),
+            (entry.mode as usize, 6),
+            (0, 6),
+            (0, 6),
+            (entry.nlink as usize, 6),
+            (0, 6),
+            (0, 11),
+            (entry.path.len() + 1, 6),
+            (entry.content.len(), 11),
+        ];
+        for (field, width) in fields {
+            archive.extend_from_slice(format!("{field:0width$o}").as_bytes());
+        }
+        archive.extend_from_slice(entry.path.as_bytes());
+        archive.push(0);
+        archive.extend_from_slice(entry.content);
+    }
+
+    archive
+}
+
+/// An ar archive containing a single file `hello.txt`, compressed with
+/// lzip. Generated by encoding a raw LZMA stream and wrapping it in an
+/// lzip header and trailer, then checked with `xz --format=lzip -d`.
+const HELLO_AR_LZIP: &[u8] = &[
+    0x4c, 0x5a, 0x49, 0x50, 0x01, 0x10, 0x00, 0x10, 0x8f, 0x08, 0xa8, 0x19, 0x72, 0xd7, 0x75, 0x1f,
+    0x78, 0xd3, 0x28, 0xce, 0xd3, 0xaf, 0x98, 0xa2, 0x29, 0x3b, 0xb5, 0x40, 0x09, 0x62, 0x2d, 0x19,
+    0x99, 0x53, 0xb7, 0xaf, 0xc5, 0x34, 0xa1, 0xa8, 0xc2, 0xe5, 0xfc, 0xd3, 0xa2, 0x8a, 0xb1, 0xa1,
+    0xe4, 0xb1, 0x64, 0xbf, 0xff, 0xf3, 0x69, 0x9c, 0x00, 0x53, 0x3d, 0x32, 0x0c, 0x4a, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x4d, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+fn unarchive_tar(
+    archive_blob: brioche_core::blob::BlobHash,
+    strip_components: u32,
+    include: &[&str],
+) -> Recipe {
+    unarchive(
+        archive_blob,
+        ArchiveFormat::Tar,
+        CompressionFormat::None,
+        strip_components,
+        include,
+    )
+}
+
+fn unarchive(
+    archive_blob: brioche_core::blob::BlobHash,
+    archive: ArchiveFormat,
+    compression: CompressionFormat,
+    strip_components: u32,
+    include: &[&str],
+) -> Recipe {
+    Recipe::Unarchive(Unarchive {
+        file: Box::new(WithMeta::without_meta(brioche_test_support::lazy_file(
+            archive_blob,
+            false,
+        ))),
+        archive,
+        compression,
+        strip_components,
+        include: include
+            .iter()
+            .map(|&pattern| pattern.into())
+            .collect::<BTreeSet<_>>(),
+    })
+}
+
+async fn example_archive(brioche: &Brioche) -> brioche_core::blob::BlobHash {
+    tar_blob(
+        brioche,
+        &[
+            TarEntry::Directory("pkg-1.0/"),
+            TarEntry::File("pkg-1.0/README", b"readme"),
+            TarEntry::Directory("pkg-1.0/src/"),
+            TarEntry::File("pkg-1.0/src/main.c", b"main"),
+            TarEntry::Directory("pkg-1.0/docs/"),
+            TarEntry::File("pkg-1.0/docs/index.md", b"docs"),
+            TarEntry::Symlink("pkg-1.0/docs/readme", "../README"),
+        ],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_bake_unarchive_tar() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let archive_blob = example_archive(&brioche).await;
+    let readme_blob = brioche_test_support::blob(&brioche, b"readme").await;
+    let main_blob = brioche_test_support::blob(&brioche, b"main").await;
+    let docs_blob = brioche_test_support::blob(&brioche, b"docs").await;
+
+    assert_eq!(
+        bake_without_meta(&brioche, unarchive_tar(archive_blob, 0, &[])).await?,
+        brioche_test_support::dir(
+            &brioche,
+            [
+                (
+                    "pkg-1.0/README",
+                    brioche_test_support::file(readme_blob, false)
+                ),
+                (
+                    "pkg-1.0/src/main.c",
+                    brioche_test_support::file(main_blob, false)
+                ),
+                (
+                    "pkg-1.0/docs/index.md",
+                    brioche_test_support::file(docs_blob, false)
+                ),
+                (
+                    "pkg-1.0/docs/readme",
+                    brioche_test_support::symlink("../README")
+                ),
+            ]
+        )
+        .await,
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_unarchive_tar_strip_components() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let archive_blob = example_archive(&brioche).await;
+    let readme_blob = brioche_test_support::blob(&brioche, b"readme").await;
+    let main_blob = brioche_test_support::blob(&brioche, b"main").await;
+    let docs_blob = brioche_test_support::blob(&brioche, b"docs").await;
+
+    assert_eq!(
+        bake_without_meta(&brioche, unarchive_tar(archive_blob, 1, &[])).await?,
+        brioche_test_support::dir(
+            &brioche,
+            [
+                ("README", brioche_test_support::file(readme_blob, false)),
+                ("src/main.c", brioche_test_support::file(main_blob, false)),
+                (
+                    "docs/index.md",
+                    brioche_test_support::file(docs_blob, false)
+                ),
+                ("docs/readme", brioche_test_support::symlink("../README")),
+            ]
+        )
+        .await,
+    );
+
+    assert_eq!(
+        bake_without_meta(&brioche, unarchive_tar(archive_blob, 2, &[])).await?,
+        brioche_test_support::dir(
+            &brioche,
+            [
+                ("main.c", brioche_test_support::file(main_blob, false)),
+                ("index.md", brioche_test_support::file(docs_blob, false)),
+                ("readme", brioche_test_support::symlink("../README")),
+            ]
+        )
+        .await,
+    );
+
+    assert_eq!(
+        bake_without_meta(&brioche, unarchive_tar(archive_blob, 3, &[])).await?,
+        brioche_test_support::dir_empty(),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_unarchive_tar_include() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let archive_blob = example_archive(&brioche).await;
+    let readme_blob = brioche_test_support::blob(&brioche, b"readme").await;
+    let main_blob = brioche_test_support::blob(&brioche, b"main").await;
+    let docs_blob = brioche_test_support::blob(&brioche, b"docs").await;
+
+    // Including a directory includes all of its contents
+    assert_eq!(
+        bake_without_meta(&brioche, unarchive_tar(archive_blob, 1, &["docs"])).await?,
+        brioche_test_support::dir(
+            &brioche,
+            [
+                (
+                    "docs/index.md",
+                    brioche_test_support::file(docs_blob, false)
+                ),
+                ("docs/readme", brioche_test_support::symlink("../README")),
+            ]
+        )
+        .await,
+    );
+
+    assert_eq!(
+        bake_without_meta(
+            &brioche,
+            unarchive_tar(archive_blob, 1, &["README", "**/*.c"])
+        )
+        .await?,
+        brioche_test_support::dir(
+            &brioche,
+            [
+                ("README", brioche_test_support::file(readme_blob, false)),
+                ("src/main.c", brioche_test_support::file(main_blob, false)),
+            ]
+        )
+        .await,
+    );
+
+    // Patterns are matched after stripping components
+    assert_eq!(
+        bake_without_meta(&brioche, unarchive_tar(archive_blob, 0, &["docs"])).await?,
+        brioche_test_support::dir_empty(),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_unarchive_tar_include_hardlink_to_excluded_file() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let archive_blob = tar_blob(
+        &brioche,
+        &[
+            TarEntry::Directory("pkg-1.0/"),
+            TarEntry::Directory("pkg-1.0/lib/"),
+            TarEntry::File("pkg-1.0/lib/libfoo.so", b"libfoo"),
+            TarEntry::Directory("pkg-1.0/bin/"),
+            TarEntry::Hardlink("pkg-1.0/bin/foo", "pkg-1.0/lib/libfoo.so"),
+        ],
+    )
+    .await;
+    let libfoo_blob = brioche_test_support::blob(&brioche, b"libfoo").await;
+
+    // The link is included but its target isn't, so the link should still
+    // resolve to the target's contents
+    assert_eq!(
+        bake_without_meta(&brioche, unarchive_tar(archive_blob, 1, &["bin"])).await?,
+        brioche_test_support::dir(
+            &brioche,
+            [("bin/foo", brioche_test_support::file(libfoo_blob, false))]
+        )
+        .await,
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_unarchive_tar_include_hardlink_chain() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let archive_blob = tar_blob(
+        &brioche,
+        &[
+            TarEntry::Directory("pkg-1.0/"),
+            TarEntry::Directory("pkg-1.0/lib/"),
+            TarEntry::File("pkg-1.0/lib/libfoo.so.1.0", b"libfoo"),
+            TarEntry::Hardlink("pkg-1.0/lib/libfoo.so.1", "pkg-1.0/lib/libfoo.so.1.0"),
+            TarEntry::File("pkg-1.0/lib/libbar.so", b"libbar"),
+            TarEntry::Directory("pkg-1.0/bin/"),
+            TarEntry::Hardlink("pkg-1.0/bin/foo", "pkg-1.0/lib/libfoo.so.1"),
+        ],
+    )
+    .await;
+    let libfoo_blob = brioche_test_support::blob(&brioche, b"libfoo").await;
+
+    // The link should resolve through the excluded link to its target
+    assert_eq!(
+        bake_without_meta(&brioche, unarchive_tar(archive_blob, 1, &["bin"])).await?,
+        brioche_test_support::dir(
+            &brioche,
+            [("bin/foo", brioche_test_support::file(libfoo_blob, false))]
+        )
+        .await,
+    );
+
+    // Excluded files that no included link points to shouldn't get saved
+    let libbar_blob = BlobHash::for_content(b"libbar");
+    assert!(!brioche_core::blob::local_blob_path(&brioche, libbar_blob).exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_unarchive_tar_invalid_include() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let archive_blob = example_archive(&brioche).await;
+
+    assert_matches!(
+        bake_without_meta(&brioche, unarchive_tar(archive_blob, 0, &["!docs"])).await,
+        Err(_)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_unarchive_ar() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let archive_blob = brioche_test_support::blob(
+        &brioche,
+        ar_archive(&[
+            ("debian-binary", b"2.0\n", 0o644),
+            ("run.sh", b"#!/bin/sh", 0o755),
+        ]),
+    )
+    .await;
+    let version_blob = brioche_test_support::blob(&brioche, b"2.0\n").await;
+    let run_blob = brioche_test_support::blob(&brioche, b"#!/bin/sh").await;
+
+    assert_eq!(
+        bake_without_meta(
+            &brioche,
+            unarchive(
+                archive_blob,
+                ArchiveFormat::Ar,
+                CompressionFormat::None,
+                0,
+                &[]
+            )
+        )
+        .await?,
+        brioche_test_support::dir(
+            &brioche,
+            [
+                (
+                    "debian-binary",
+                    brioche_test_support::file(version_blob, false)
+                ),
+                ("run.sh", brioche_test_support::file(run_blob, true)),
+            ]
+        )
+        .await,
+    );
+
+    assert_eq!(
+        bake_without_meta(
+            &brioche,
+            unarchive(
+                archive_blob,
+                ArchiveFormat::Ar,
+                CompressionFormat::None,
+                0,
+                &["*.sh"]
+            )
+        )
+        .await?,
+        brioche_test_support::dir(
+            &brioche,
+            [("run.sh", brioche_test_support::file(run_blob, true))]
+        )
+        .await,
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_unarchive_cpio() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let entries = [
+        cpio_dir(1, "pkg"),
+        cpio_file(2, "pkg/README", b"readme", 0o644),
+        cpio_dir(3, "pkg/bin"),
+        cpio_file(4, "pkg/bin/run.sh", b"#!/bin/sh", 0o755),
+        cpio_symlink(5, "pkg/readme", "README"),
+    ];
+
+    let readme_blob = brioche_test_support::blob(&brioche, b"readme").await;
+    let run_blob = brioche_test_support::blob(&brioche, b"#!/bin/sh").await;
+    let expected = brioche_test_support::dir(
+        &brioche,
+        [
+            ("README", brioche_test_support::file(readme_blob, false)),
+            ("bin/run.sh", brioche_test_support::file(run_blob, true)),
+            ("readme", brioche_test_support::symlink("README")),
+        ],
+    )
+    .await;
+
+    for archive in [cpio_newc_archive(&entries), cpio_odc_archive(&entries)] {
+        let archive_blob = brioche_test_support::blob(&brioche, archive).await;
+        assert_eq!(
+            bake_without_meta(
+                &brioche,
+                unarchive(
+                    archive_blob,
+                    ArchiveFormat::Cpio,
+                    CompressionFormat::None,
+                    1,
+                    &[]
+                )
+            )
+            .await?,
+            expected,
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_unarchive_cpio_path_escape_fails() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let entries = [
+        cpio_file(1, "README", b"readme", 0o644),
+        cpio_file(2, "../escaped", b"escaped", 0o644),
+    ];
+    let archive_blob = brioche_test_support::blob(&brioche, cpio_newc_archive(&entries)).await;
+
+    assert_matches!(
+        bake_without_meta(
+            &brioche,
+            unarchive(
+                archive_blob,
+                ArchiveFormat::Cpio,
+                CompressionFormat::None,
+                0,
+                &[]
+            )
+        )
+        .await,
+        Err(_)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_unarchive_cpio_newc_hardlinks() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    // newc archives only store the contents of a hardlinked file with the
+    // last entry for the file
+    let hardlink = |ino, path, content| CpioEntry {
+        ino,
+        mode: 0o100_644,
+        nlink: 2,
+        path,
+        content,
+    };
+    let archive_blob = brioche_test_support::blob(
+        &brioche,
+        cpio_newc_archive(&[
+            hardlink(1, "a", b""),
+            hardlink(1, "b", b"linked"),
+            hardlink(2, "empty-a", b""),
+            hardlink(2, "empty-b", b""),
+        ]),
+    )
+    .await;
+
+    let linked_blob = brioche_test_support::blob(&brioche, b"linked").await;
+    let empty_blob = brioche_test_support::blob(&brioche, b"").await;
+
+    assert_eq!(
+        bake_without_meta(
+            &brioche,
+            unarchive(
+                archive_blob,
+                ArchiveFormat::Cpio,
+                CompressionFormat::None,
+                0,
+                &[]
+            )
+        )
+        .await?,
+        brioche_test_support::dir(
+            &brioche,
+            [
+                ("a", brioche_test_support::file(linked_blob, false)),
+                ("b", brioche_test_support::file(linked_blob, false)),
+                ("empty-a", brioche_test_support::file(empty_blob, false)),
+                ("empty-b", brioche_test_support::file(empty_blob, false)),
+            ]
+        )
+        .await,
+    );
+
+    // The linked contents are kept even if the entry storing them is
+    // filtered out
+    assert_eq!(
+        bake_without_meta(
+            &brioche,
+            unarchive(
+                archive_blob,
+                ArchiveFormat::Cpio,
+                CompressionFormat::None,
+                0,
+                &["a"]
+            )
+        )
+        .await?,
+        brioche_test_support::dir(
+            &brioche,
+            [("a", brioche_test_support::file(linked_blob, false))]
+        )
+        .await,
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bake_unarchive_compression() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let archive = ar_archive(&[("hello.txt", b"hello", 0o644)]);
+
+    let mut brotli_archive = vec![];
+    async_compression::tokio::bufread::BrotliEncoder::new(&archive[..])
+        .read_to_end(&mut brotli_archive)
+        .await?;
+
+    let mut lz4_encoder = lz4_flex::frame::FrameEncoder::new(vec![]);
+    lz4_encoder.write_all(&archive)?;
+    let lz4_archive = lz4_encoder.finish()?;
+
+    let hello_blob = brioche_test_support::blob(&brioche, b"hello").await;
+    let expected = brioche_test_support::dir(
+        &brioche,
+        [("hello.txt", brioche_test_support::file(hello_blob, false))],
+    )
+    .await;
+
+    for (compression, compressed_archive) in [
+        (CompressionFormat::Brotli, brotli_archive),
+        (CompressionFormat::Lz4, lz4_archive),
+        (CompressionFormat::Lzip, HELLO_AR_LZIP.to_vec()),
+    ] {
+        let archive_blob = brioche_test_support::blob(&brioche, compressed_archive).await;
+        assert_eq!(
+            bake_without_meta(
+                &brioche,
+                unarchive(archive_blob, ArchiveFormat::Ar, compression, 0, &[])
+            )
+            .await?,
+            expected,
+            "unexpected result for {compression:?}",
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+
+use assert_matches::assert_matches;
+use brioche_core::{
+    Brioche,
+    cache::{
+        CacheClient,
+        server::{CacheServerOptions, CacheServerSource},
+    },
+    config::{CacheAuthConfig, CacheCompressionConfig, CacheConfig, SecretSource},
+    recipe::{DownloadRecipe, Recipe},
+    reporter::job::CacheFetchKind,
+};
+
+async fn start_cache_server(brioche: &Brioche, options: CacheServerOptions) -> url::Url {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let brioche = brioche.clone();
+    tokio::spawn(
+        async move { brioche_core::cache::server::serve(&brioche, options, listener).await },
+    );
+
+    format!("http://{addr}/").parse().unwrap()
+}
+
+fn http_cache_config(url: url::Url, writable: bool) -> CacheConfig {
+    CacheConfig {
+        url,
+        max_concurrent_operations: brioche_core::cache::DEFAULT_CACHE_MAX_CONCURRENT_OPERATIONS,
+        read_only: !writable,
+        allow_http: Some(true),
+        priority: 0,
+        trusted_keys: vec![],
+        signing_key_path: None,
+        auth: None,
+        headers: BTreeMap::new(),
+        compression: CacheCompressionConfig::default(),
+    }
+}
+
+async fn http_cache_client(url: url::Url, writable: bool) -> CacheClient {
+    cache_client_from_config(&http_cache_config(url, writable)).await
+}
+
+async fn cache_client_from_config(config: &CacheConfig) -> CacheClient {
+    brioche_core::cache::cache_client_from_config_or_default(
+        Some(config),
+        &brioche_core::network::NetworkOptions::default(),
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_cache_server_save_and_load() -> anyhow::Result<()> {
+    let (server_brioche, server_context) = brioche_test_support::brioche_test().await;
+    let cache_dir = server_context.mkdir("cache").await;
+    let cache_url = start_cache_server(
+        &server_brioche,
+        CacheServerOptions {
+            source: CacheServerSource::Directory(cache_dir),
+            writable: true,
+            write_token: None,
+            signing_key: None,
+        },
+    )
+    .await;
+
+    // Large enough to be split into chunks
+    let content = (0..4_000_000_u32)
+        .map(|n| u8::try_from(n % 251).unwrap())
+        .collect::<Vec<_>>();
+    let recipe_hash = Recipe::Download(DownloadRecipe {
+        url: "https://example.com/file.txt".parse()?,
+        hash: brioche_test_support::sha256(&content),
+        mirrors: vec![],
+    })
+    .hash();
+
+    let artifact;
+    {
+        let writer_cache_client = http_cache_client(cache_url.clone(), true).await;
+        let (brioche, _context) = brioche_test_support::brioche_test_with(|builder| {
+            builder.cache_client(writer_cache_client)
+        })
+        .await;
+
+        let blob = brioche_test_support::blob(&brioche, &content).await;
+        artifact = brioche_test_support::file(blob, false);
+
+        brioche_core::cache::save_artifact(&brioche, artifact.clone()).await?;
+        brioche_core::cache::save_bake(&brioche, recipe_hash, artifact.hash()).await?;
+    }
+
+    {
+        let reader_cache_client = http_cache_client(cache_url.clone(), false).await;
+        let (brioche, _context) = brioche_test_support::brioche_test_with(|builder| {
+            builder.cache_client(reader_cache_client)
+        })
+        .await;
+
+        let loaded_bake = brioche_core::cache::load_bake(&brioche, recipe_hash).await?;
+        assert_eq!(loaded_bake, Some(artifact.hash()));
+
+        let loaded_artifact =
+            brioche_core::cache::load_artifact(&brioche, artifact.hash(), CacheFetchKind::Bake)
+                .await?;
+        assert_eq!(loaded_artifact, Some(artifact.clone()));
+
+        let brioche_core::recipe::Artifact::File(file) = &artifact else {
+            panic!("expected file artifact");
+        };
+        let blob_path = brioche_core::blob::local_blob_path(&brioche, file.content_blob);
+        assert_eq!(tokio::fs::read(&blob_path).await?, content);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cache_server_read_only() -> anyhow::Result<()> {
+    let (server_brioche, server_context) = brioche_test_support::brioche_test().await;
+    let cache_dir = server_context.mkdir("cache").await;
+    let cache_url = start_cache_server(
+        &server_brioche,
+        CacheServerOptions {
+            source: CacheServerSource::Directory(cache_dir),
+            writable: false,
+            write_token: None,
+            signing_key: None,
+        },
+    )
+    .await;
+
+    let cache_client = http_cache_client(cache_url, true).await;
+    let (brioche, _context) =
+        brioche_test_support::brioche_test_with(|builder| builder.cache_client(cache_client)).await;
+
+    let blob = brioche_test_support::blob(&brioche, "hello").await;
+    let artifact = brioche_test_support::file(blob, false);
+    let result = brioche_core::cache::save_artifact(&brioche, artifact).await;
+    assert_matches!(result, Err(_));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cache_server_write_token() -> anyhow::Result<()> {
+    let (server_brioche, server_context) = brioche_test_support::brioche_test().await;
+    let cache_dir = server_context.mkdir("cache").await;
+    let cache_url = start_cache_server(
+        &server_brioche,
+        CacheServerOptions {
+            source: CacheServerSource::Directory(cache_dir),
+            writable: true,
+            write_token: Some("secret".to_string()),
+            signing_key: None,
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let object_url = cache_url.join("bakes/test/output.json")?;
+
+    let response = client.put(object_url.clone()).body("{}").send().await?;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let response = client
+        .put(object_url.clone())
+        .bearer_auth("wrong")
+        .body("{}")
+        .send()
+        .await?;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let response = client
+        .put(object_url.clone())
+        .bearer_auth("secret")
+        .body("{}")
+        .send()
+        .await?;
+    assert!(response.status().is_success());
+
+    // Reads don't need the token
+    let response = client.get(object_url).send().await?;
+    assert!(response.status().is_success());
+    assert_eq!(response.text().await?, "{}");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cache_server_write_with_auth_token() -> anyhow::Result<()> {
+    let (server_brioche, server_context) = brioche_test_support::brioche_test().await;
+    let cache_dir = server_context.mkdir("cache").await;
+    let token_path = server_context.write_file("token", "secret\n").await;
+    let cache_url = start_cache_server(
+        &server_brioche,
+        CacheServerOptions {
+            source: CacheServerSource::Directory(cache_dir),
+            writable: true,
+            write_token: Some("secret".to_string()),
+            signing_key: None,
+        },
+    )
+    .await;
+
+    let recipe_hash = Recipe::Download(DownloadRecipe {
+        url: "https://example.com/file.txt".parse()?,
+        hash: brioche_test_support::sha256("hello"),
+        mirrors: vec![],
+    })
+    .hash();
+
+    let cache_client = cache_client_from_config(&CacheConfig {
+        auth: Some(CacheAuthConfig::Bearer {
+            token: SecretSource::File(token_path),
+        }),
+        ..http_cache_config(cache_url.clone(), true)
+    })
+    .await;
+    let (brioche, _context) =
+        brioche_test_support::brioche_test_with(|builder| builder.cache_client(cache_client)).await;
+
+    let blob = brioche_test_support::blob(&brioche, "hello").await;
+    let artifact = brioche_test_support::file(blob, false);
+    brioche_core::cache::save_artifact(&brioche, artifact.clone()).await?;
+    brioche_core::cache::save_bake(&brioche, recipe_hash, artifact.hash()).await?;
+
+    let loaded_bake = brioche_core::cache::load_bake(&brioche, recipe_hash).await?;
+    assert_eq!(loaded_bake, Some(artifact.hash()));
+
+    // Writing without the token fails
+    let cache_client = http_cache_client(cache_url, true).await;
+    let (brioche, _context) =
+        brioche_test_support::brioche_test_with(|builder| builder.cache_client(cache_client)).await;
+    let other_recipe_hash = Recipe::Download(DownloadRecipe {
+        url: "https://example.com/other.txt".parse()?,
+        hash: brioche_test_support::sha256("hello"),
+        mirrors: vec![],
+    })
+    .hash();
+    let result = brioche_core::cache::save_bake(&brioche, other_recipe_hash, artifact.hash()).await;
+    assert_matches!(result, Err(_));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_cache_sends_auth_headers() -> anyhow::Result<()> {
+    let (brioche, context) = brioche_test_support::brioche_test().await;
+    let password_path = context.write_file("password", "pass").await;
+    let api_key_path = context.write_file("api-key", "key123\n").await;
+
+    let recipe_hash = Recipe::Download(DownloadRecipe {
+        url: "https://example.com/file.txt".parse()?,
+        hash: brioche_test_support::sha256("hello"),
+        mirrors: vec![],
+    })
+    .hash();
+    let blob = brioche_test_support::blob(&brioche, "hello").await;
+    let artifact_hash = brioche_test_support::file(blob, false).hash();
+
+    let mut server = mockito::Server::new_async().await;
+    let bake_endpoint = server
+        .mock("GET", &*format!("/bakes/{recipe_hash}/output.json"))
+        // "user:pass" encoded as base64
+        .match_header("authorization", "Basic dXNlcjpwYXNz")
+        .match_header("x-api-key", "key123")
+        .with_header("last-modified", "Thu, 01 Jan 2026 00:00:00 GMT")
+        .with_body(serde_json::to_string(&serde_json::json!({
+            "outputHash": artifact_hash,
+        }))?)
+        .expect(1)
+        .create();
+
+    let cache_client = cache_client_from_config(&CacheConfig {
+        auth: Some(CacheAuthConfig::Basic {
+            username: "user".to_string(),
+            password: SecretSource::File(password_path),
+        }),
+        headers: BTreeMap::from_iter([("X-Api-Key".to_string(), SecretSource::File(api_key_path))]),
+        ..http_cache_config(format!("{}/", server.url()).parse()?, false)
+    })
+    .await;
+    let (brioche, _context) =
+        brioche_test_support::brioche_test_with(|builder| builder.cache_client(cache_client)).await;
+
+    let loaded_bake = brioche_core::cache::load_bake(&brioche, recipe_hash).await?;
+    assert_eq!(loaded_bake, Some(artifact_hash));
+
+    bake_endpoint.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cache_server_exports_local_bakes() -> anyhow::Result<()> {
+    let (server_brioche, _server_context) = brioche_test_support::brioche_test().await;
+
+    let mut server = mockito::Server::new_async().await;
+    let server_url = server.url();
+
+    let hello = "hello";
+    let hello_blob = brioche_test_support::blob(&server_brioche, hello).await;
+    let hello_endpoint = server
+        .mock("GET", "/file.txt")
+        .with_body(hello)
+        .expect(1)
+        .create();
+
+    let hello_download = Recipe::Download(DownloadRecipe {
+        hash: brioche_test_support::sha256(hello),
+        url: format!("{server_url}/file.txt").parse()?,
+        mirrors: vec![],
+    });
+    let hello_artifact =
+        brioche_test_support::bake_without_meta(&server_brioche, hello_download.clone()).await?;
+    assert_eq!(
+        hello_artifact,
+        brioche_test_support::file(hello_blob, false)
+    );
+
+    let cache_url = start_cache_server(
+        &server_brioche,
+        CacheServerOptions {
+            source: CacheServerSource::DataDir,
+            writable: false,
+            write_token: None,
+            signing_key: None,
+        },
+    )
+    .await;
+
+    let cache_client = http_cache_client(cache_url, false).await;
+    let (brioche, _context) =
+        brioche_test_support::brioche_test_with(|builder| builder.cache_client(cache_client)).await;
+
+    let loaded_bake = brioche_core::cache::load_bake(&brioche, hello_download.hash()).await?;
+    assert_eq!(loaded_bake, Some(hello_artifact.hash()));
+
+    let loaded_artifact =
+        brioche_core::cache::load_artifact(&brioche, hello_artifact.hash(), CacheFetchKind::Bake)
+            .await?;
+    assert_eq!(loaded_artifact, Some(hello_artifact));
+
+    // Unknown bakes still aren't found
+    let unknown_bake = brioche_core::cache::load_bake(
+        &brioche,
+        Recipe::Download(DownloadRecipe {
+            hash: brioche_test_support::sha256("unknown"),
+            url: format!("{server_url}/unknown.txt").parse()?,
+            mirrors: vec![],
+        })
+        .hash(),
+    )
+    .await?;
+    assert_eq!(unknown_bake, None);
+
+    hello_endpoint.assert();
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use brioche_core::{
+    Brioche,
+    cache::CacheClient,
+    recipe::{CreateDirectory, DownloadRecipe, ProcessRecipe, Recipe},
+};
+use brioche_test_support::{tpl, without_meta};
+
+#[tokio::test]
+async fn test_fetch_downloads_and_cached_bakes() -> anyhow::Result<()> {
+    let cache = brioche_test_support::new_cache();
+    let (brioche, _context) = brioche_test_with_cache(cache, true).await;
+
+    let mut server = mockito::Server::new_async().await;
+    let server_url = server.url();
+
+    let hello_endpoint = server
+        .mock("GET", "/hello.txt")
+        .with_body("hello")
+        .expect(1)
+        .create();
+    let hi_endpoint = server
+        .mock("GET", "/hi.txt")
+        .with_body("hi")
+        .expect(1)
+        .create();
+
+    let hello_download = Recipe::Download(DownloadRecipe {
+        hash: brioche_test_support::sha256("hello"),
+        url: format!("{server_url}/hello.txt").parse().unwrap(),
+        mirrors: vec![],
+    });
+    let hi_download = Recipe::Download(DownloadRecipe {
+        hash: brioche_test_support::sha256("hi"),
+        url: format!("{server_url}/hi.txt").parse().unwrap(),
+        mirrors: vec![],
+    });
+
+    // A process with a bake in the cache
+    let cached_process = Recipe::Process(ProcessRecipe {
+        command: tpl("/usr/bin/env"),
+        args: vec![tpl("sh"), tpl("-c"), tpl("cached")],
+        ..brioche_test_support::default_process_x86_64_linux()
+    });
+    let cached_blob = brioche_test_support::blob(&brioche, "cached value").await;
+    let cached_output = brioche_test_support::file(cached_blob, false);
+    brioche_core::cache::save_artifact(&brioche, cached_output.clone()).await?;
+    brioche_core::cache::save_bake(&brioche, cached_process.hash(), cached_output.hash()).await?;
+
+    // A process that isn't in the cache, with a download as an input
+    let uncached_process = Recipe::Process(ProcessRecipe {
+        command: tpl("/usr/bin/env"),
+        args: vec![
+            tpl("sh"),
+            tpl("-c"),
+            brioche_test_support::template_input(hi_download),
+        ],
+        ..brioche_test_support::default_process_x86_64_linux()
+    });
+
+    let recipe = Recipe::CreateDirectory(CreateDirectory {
+        entries: [
+            ("hello.txt".into(), without_meta(hello_download.clone())),
+            ("cached".into(), without_meta(cached_process.clone())),
+            ("uncached".into(), without_meta(uncached_process.clone())),
+        ]
+        .into_iter()
+        .collect(),
+    });
+
+    let results = brioche_core::fetch::fetch(&brioche, &recipe).await?;
+
+    assert_eq!(results.num_cached_bakes, 1);
+    assert_eq!(results.num_downloads, 2);
+    assert_eq!(
+        results
+            .unfetched
+            .iter()
+            .map(|recipe| recipe.recipe_hash)
+            .collect::<Vec<_>>(),
+        vec![uncached_process.hash()],
+    );
+
+    hello_endpoint.assert();
+    hi_endpoint.assert();
+
+    // The cached bake and downloads should now be available locally
+    assert_eq!(
+        brioche_core::bake::get_local_bake(&brioche, cached_process.hash()).await?,
+        Some(cached_output),
+    );
+    assert!(
+        brioche_core::bake::get_local_bake(&brioche, hello_download.hash())
+            .await?
+            .is_some()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fetch_nothing_to_fetch() -> anyhow::Result<()> {
+    let (brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let hello_blob = brioche_test_support::blob(&brioche, "hello").await;
+    let recipe = Recipe::CreateDirectory(CreateDirectory {
+        entries: [(
+            "hello.txt".into(),
+            without_meta(brioche_test_support::lazy_file(hello_blob, false)),
+        )]
+        .into_iter()
+        .collect(),
+    });
+
+    let results = brioche_core::fetch::fetch(&brioche, &recipe).await?;
+
+    assert_eq!(results.num_cached_bakes, 0);
+    assert_eq!(results.num_downloads, 0);
+    assert!(results.unfetched.is_empty());
+
+    Ok(())
+}
+
+async fn brioche_test_with_cache(
+    store: Arc<dyn object_store::ObjectStore>,
+    writable: bool,
+) -> (Brioche, brioche_test_support::TestContext) {
+    brioche_test_support::brioche_test_with(move |builder| {
+        builder.cache_client(CacheClient::from_store(store, writable))
+    })
+    .await
+}
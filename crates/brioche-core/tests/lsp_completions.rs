@@ -229,12 +229,7 @@ async fn test_lsp_completions_from_remote_registry_import() -> anyhow::Result<()
     let cache = brioche_test_support::new_cache();
     let (_brioche, mut context, lsp) = brioche_test_support::brioche_lsp_test_with({
         let cache = cache.clone();
-        move |builder| {
-            builder.cache_client(CacheClient {
-                store: Some(cache.clone()),
-                ..Default::default()
-            })
-        }
+        move |builder| builder.cache_client(CacheClient::from_store(cache.clone(), false))
     })
     .await;
 
@@ -98,12 +98,7 @@ async fn test_lsp_on_save_fetches_locked_dependency() -> anyhow::Result<()> {
     let cache = brioche_test_support::new_cache();
     let (brioche, mut context, lsp) = brioche_test_support::brioche_lsp_test_with({
         let cache = cache.clone();
-        move |builder| {
-            builder.cache_client(CacheClient {
-                store: Some(cache.clone()),
-                ..Default::default()
-            })
-        }
+        move |builder| builder.cache_client(CacheClient::from_store(cache.clone(), false))
     })
     .await;
 
@@ -264,12 +259,7 @@ async fn test_lsp_on_save_fetches_dependency_and_updates_lockfile() -> anyhow::R
     let cache = brioche_test_support::new_cache();
     let (brioche, mut context, lsp) = brioche_test_support::brioche_lsp_test_with({
         let cache = cache.clone();
-        move |builder| {
-            builder.cache_client(CacheClient {
-                store: Some(cache.clone()),
-                ..Default::default()
-            })
-        }
+        move |builder| builder.cache_client(CacheClient::from_store(cache.clone(), false))
     })
     .await;
 
@@ -440,12 +430,7 @@ async fn test_lsp_on_save_only_adds_new_dependencies_in_lockfile() -> anyhow::Re
     let cache = brioche_test_support::new_cache();
     let (_brioche, mut context, lsp) = brioche_test_support::brioche_lsp_test_with({
         let cache = cache.clone();
-        move |builder| {
-            builder.cache_client(CacheClient {
-                store: Some(cache.clone()),
-                ..Default::default()
-            })
-        }
+        move |builder| builder.cache_client(CacheClient::from_store(cache.clone(), false))
     })
     .await;
 
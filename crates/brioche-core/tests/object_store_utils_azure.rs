@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+
+use brioche_core::{
+    config::{CacheCompressionConfig, CacheConfig},
+    recipe::Recipe,
+};
+
+#[test]
+fn test_object_store_utils_azure_bucket_and_prefix() {
+    let url = "az://my-container/some/prefix".parse().unwrap();
+    let (container, prefix) = brioche_core::object_store_utils::bucket_and_prefix(&url).unwrap();
+    assert_eq!(container, "my-container");
+    assert_eq!(prefix, "some/prefix");
+
+    let url = "az://my-container".parse().unwrap();
+    let (container, prefix) = brioche_core::object_store_utils::bucket_and_prefix(&url).unwrap();
+    assert_eq!(container, "my-container");
+    assert_eq!(prefix, "");
+
+    let url = "azure://my-container/some/prefix".parse().unwrap();
+    let (container, prefix) = brioche_core::object_store_utils::bucket_and_prefix(&url).unwrap();
+    assert_eq!(container, "my-container");
+    assert_eq!(prefix, "some/prefix");
+
+    let url = "az:///some/prefix".parse().unwrap();
+    let result = brioche_core::object_store_utils::bucket_and_prefix(&url);
+    assert!(result.is_err());
+}
+
+/// Save and load through an `az://` cache. Requires the Azurite emulator,
+/// and is skipped unless `BRIOCHE_TEST_AZURE_CONTAINER` is set. The
+/// container must already exist, and `AZURE_STORAGE_USE_EMULATOR=true`
+/// should be set so the emulator's account and key get used.
+#[tokio::test]
+async fn test_object_store_utils_azure_emulator_save_and_load() -> anyhow::Result<()> {
+    let Ok(container) = std::env::var("BRIOCHE_TEST_AZURE_CONTAINER") else {
+        return Ok(());
+    };
+
+    let prefix = ulid::Ulid::new();
+    let config = CacheConfig {
+        url: format!("az://{container}/brioche-test-{prefix}").parse()?,
+        max_concurrent_operations: brioche_core::cache::DEFAULT_CACHE_MAX_CONCURRENT_OPERATIONS,
+        read_only: false,
+        allow_http: Some(true),
+        priority: 0,
+        trusted_keys: vec![],
+        signing_key_path: None,
+        auth: None,
+        headers: BTreeMap::new(),
+        compression: CacheCompressionConfig::default(),
+    };
+
+    let recipe_hash = Recipe::Process(brioche_test_support::default_process_x86_64_linux()).hash();
+    let artifact;
+
+    {
+        let cache_client = brioche_core::cache::cache_client_from_config_or_default(
+            Some(&config),
+            &brioche_core::network::NetworkOptions::default(),
+        )
+        .await?;
+        let (brioche, _context) =
+            brioche_test_support::brioche_test_with(|builder| builder.cache_client(cache_client))
+                .await;
+
+        let blob = brioche_test_support::blob(&brioche, "hello from azure").await;
+        artifact = brioche_test_support::dir(
+            &brioche,
+            [("hello.txt", brioche_test_support::file(blob, false))],
+        )
+        .await;
+
+        brioche_core::cache::save_artifact(&brioche, artifact.clone()).await?;
+        brioche_core::cache::save_bake(&brioche, recipe_hash, artifact.hash()).await?;
+    }
+
+    {
+        let cache_client = brioche_core::cache::cache_client_from_config_or_default(
+            Some(&CacheConfig {
+                read_only: true,
+                ..config
+            }),
+            &brioche_core::network::NetworkOptions::default(),
+        )
+        .await?;
+        let (brioche, _context) =
+            brioche_test_support::brioche_test_with(|builder| builder.cache_client(cache_client))
+                .await;
+
+        let loaded_bake = brioche_core::cache::load_bake(&brioche, recipe_hash).await?;
+        assert_eq!(loaded_bake, Some(artifact.hash()));
+
+        let loaded_artifact = brioche_core::cache::load_artifact(
+            &brioche,
+            artifact.hash(),
+            brioche_core::reporter::job::CacheFetchKind::Bake,
+        )
+        .await?;
+        assert_eq!(loaded_artifact, Some(artifact));
+    }
+
+    Ok(())
+}
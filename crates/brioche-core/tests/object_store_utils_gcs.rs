@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+
+use brioche_core::{
+    config::{CacheCompressionConfig, CacheConfig},
+    recipe::Recipe,
+};
+
+#[test]
+fn test_object_store_utils_gcs_bucket_and_prefix() {
+    let url = "gs://my-bucket/some/prefix".parse().unwrap();
+    let (bucket, prefix) = brioche_core::object_store_utils::bucket_and_prefix(&url).unwrap();
+    assert_eq!(bucket, "my-bucket");
+    assert_eq!(prefix, "some/prefix");
+
+    let url = "gs://my-bucket".parse().unwrap();
+    let (bucket, prefix) = brioche_core::object_store_utils::bucket_and_prefix(&url).unwrap();
+    assert_eq!(bucket, "my-bucket");
+    assert_eq!(prefix, "");
+
+    let url = "gs:///some/prefix".parse().unwrap();
+    let result = brioche_core::object_store_utils::bucket_and_prefix(&url);
+    assert!(result.is_err());
+}
+
+/// Save and load through a `gs://` cache. Requires a GCS emulator such as
+/// fake-gcs-server, and is skipped unless `BRIOCHE_TEST_GCS_BUCKET` is set.
+/// The emulator's URL is configured with a service account file (set with
+/// `GOOGLE_SERVICE_ACCOUNT`) containing `gcs_base_url` and
+/// `"disable_oauth": true`.
+#[tokio::test]
+async fn test_object_store_utils_gcs_emulator_save_and_load() -> anyhow::Result<()> {
+    let Ok(bucket) = std::env::var("BRIOCHE_TEST_GCS_BUCKET") else {
+        return Ok(());
+    };
+
+    let prefix = ulid::Ulid::new();
+    let config = CacheConfig {
+        url: format!("gs://{bucket}/brioche-test-{prefix}").parse()?,
+        max_concurrent_operations: brioche_core::cache::DEFAULT_CACHE_MAX_CONCURRENT_OPERATIONS,
+        read_only: false,
+        allow_http: Some(true),
+        priority: 0,
+        trusted_keys: vec![],
+        signing_key_path: None,
+        auth: None,
+        headers: BTreeMap::new(),
+        compression: CacheCompressionConfig::default(),
+    };
+
+    let recipe_hash = Recipe::Process(brioche_test_support::default_process_x86_64_linux()).hash();
+    let artifact;
+
+    {
+        let cache_client = brioche_core::cache::cache_client_from_config_or_default(
+            Some(&config),
+            &brioche_core::network::NetworkOptions::default(),
+        )
+        .await?;
+        let (brioche, _context) =
+            brioche_test_support::brioche_test_with(|builder| builder.cache_client(cache_client))
+                .await;
+
+        let blob = brioche_test_support::blob(&brioche, "hello from gcs").await;
+        artifact = brioche_test_support::dir(
+            &brioche,
+            [("hello.txt", brioche_test_support::file(blob, false))],
+        )
+        .await;
+
+        brioche_core::cache::save_artifact(&brioche, artifact.clone()).await?;
+        brioche_core::cache::save_bake(&brioche, recipe_hash, artifact.hash()).await?;
+    }
+
+    {
+        let cache_client = brioche_core::cache::cache_client_from_config_or_default(
+            Some(&CacheConfig {
+                read_only: true,
+                ..config
+            }),
+            &brioche_core::network::NetworkOptions::default(),
+        )
+        .await?;
+        let (brioche, _context) =
+            brioche_test_support::brioche_test_with(|builder| builder.cache_client(cache_client))
+                .await;
+
+        let loaded_bake = brioche_core::cache::load_bake(&brioche, recipe_hash).await?;
+        assert_eq!(loaded_bake, Some(artifact.hash()));
+
+        let loaded_artifact = brioche_core::cache::load_artifact(
+            &brioche,
+            artifact.hash(),
+            brioche_core::reporter::job::CacheFetchKind::Bake,
+        )
+        .await?;
+        assert_eq!(loaded_artifact, Some(artifact));
+    }
+
+    Ok(())
+}
@@ -23,6 +23,7 @@ pub fn example_complete_process() -> CompleteProcessRecipe {
         platform: brioche_core::platform::Platform::X86_64Linux,
         is_unsafe: false,
         networking: false,
+        jobs: None,
     }
 }
 
@@ -46,6 +47,7 @@ fn example_process_event_description() -> ProcessEventDescription {
             gid_hint: 0,
             uid_hint: 0,
             networking: false,
+            jobs: None,
         },
         created_at: Zoned::now(),
         root_dir: Default::default(),
@@ -1630,11 +1630,9 @@ async fn brioche_test_with_cache(
     writable: bool,
 ) -> (Brioche, TestContext) {
     brioche_test_support::brioche_test_with(|builder| {
-        builder.cache_client(brioche_core::cache::CacheClient {
-            store: Some(cache),
-            writable,
-            ..Default::default()
-        })
+        builder.cache_client(brioche_core::cache::CacheClient::from_store(
+            cache, writable,
+        ))
     })
     .await
 }
@@ -5,7 +5,10 @@ use std::collections::BTreeMap;
 use brioche_core::{
     Hash,
     platform::Platform,
-    recipe::{DownloadRecipe, ProcessRecipe, ProcessTemplate, ProcessTemplateComponent, Recipe},
+    recipe::{
+        ArchiveFormat, CompressionFormat, DownloadRecipe, ProcessRecipe, ProcessTemplate,
+        ProcessTemplateComponent, Recipe, Unarchive, WithMeta,
+    },
 };
 use pretty_assertions::assert_eq;
 
@@ -251,6 +254,7 @@ async fn test_recipe_hash_stable_download() -> anyhow::Result<()> {
         Recipe::Download(DownloadRecipe {
             url: "https://example.com/foo".parse()?,
             hash: Hash::Sha256 { value: vec![0x00] },
+            mirrors: vec![],
         })
         .hash()
         .to_string(),
@@ -261,12 +265,47 @@ async fn test_recipe_hash_stable_download() -> anyhow::Result<()> {
         Recipe::Download(DownloadRecipe {
             url: "https://example.com/foo".parse()?,
             hash: Hash::Sha256 { value: vec![0x01] },
+            mirrors: vec![],
         })
         .hash()
         .to_string(),
         "746f52c35bc39e72adb69f3e2daa4bceef0ea568b48c2cee23e100576b6acc92",
     ));
 
+    // Mirrors don't affect the hash
+    asserts.push((
+        Recipe::Download(DownloadRecipe {
+            url: "https://example.com/foo".parse()?,
+            hash: Hash::Sha256 { value: vec![0x00] },
+            mirrors: vec!["https://mirror.example.com/foo".parse()?],
+        })
+        .hash()
+        .to_string(),
+        "8f7d9898a19b8b2a599c78c9e59cdf0f295b7291fd2eb13ccb34f35cae0317f6",
+    ));
+
+    asserts.push((
+        Recipe::Download(DownloadRecipe {
+            url: "https://example.com/foo".parse()?,
+            hash: Hash::Sha512 { value: vec![0x00] },
+            mirrors: vec![],
+        })
+        .hash()
+        .to_string(),
+        "73fa6b8b9656a0ea07eed50fe0955a1c3c2caf09f8ab06e42354cd4e575b4f23",
+    ));
+
+    asserts.push((
+        Recipe::Download(DownloadRecipe {
+            url: "https://example.com/foo".parse()?,
+            hash: Hash::Blake3 { value: vec![0x00] },
+            mirrors: vec![],
+        })
+        .hash()
+        .to_string(),
+        "0d11ad5d2ac7e144fa70d3b753c63ef286d82bdb5533f4590807961096bd9cd8",
+    ));
+
     let left: Vec<_> = asserts.iter().map(|(left, _)| left).collect();
     let right: Vec<_> = asserts.iter().map(|(_, right)| right).collect();
 
@@ -275,6 +314,33 @@ async fn test_recipe_hash_stable_download() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_recipe_hash_stable_nested_download_mirrors() -> anyhow::Result<()> {
+    let (_brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let unarchive = |mirrors: Vec<url::Url>| {
+        Recipe::Unarchive(Unarchive {
+            file: Box::new(WithMeta::without_meta(Recipe::Download(DownloadRecipe {
+                url: "https://example.com/foo.tar".parse().unwrap(),
+                hash: Hash::Sha256 { value: vec![0x00] },
+                mirrors,
+            }))),
+            archive: ArchiveFormat::Tar,
+            compression: CompressionFormat::None,
+            strip_components: 0,
+            include: Default::default(),
+        })
+    };
+
+    // Mirrors don't affect the hash, even for a nested download
+    assert_eq!(
+        unarchive(vec!["https://mirror.example.com/foo.tar".parse()?]).hash(),
+        unarchive(vec![]).hash(),
+    );
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_recipe_hash_stable_process() -> anyhow::Result<()> {
     let (_brioche, _context) = brioche_test_support::brioche_test().await;
@@ -294,6 +360,28 @@ async fn test_recipe_hash_stable_process() -> anyhow::Result<()> {
             platform: Platform::X86_64Linux,
             is_unsafe: false,
             networking: false,
+            jobs: None,
+        })
+        .hash()
+        .to_string(),
+        "328da364438116a512c2a376700dfd3323702290a09c3f68899502bbf1d427d7",
+    ));
+
+    // The number of jobs doesn't affect the hash
+    asserts.push((
+        Recipe::Process(ProcessRecipe {
+            command: ProcessTemplate { components: vec![] },
+            args: vec![],
+            env: BTreeMap::default(),
+            dependencies: vec![],
+            work_dir: Box::new(brioche_test_support::without_meta(
+                brioche_test_support::lazy_dir_empty(),
+            )),
+            output_scaffold: None,
+            platform: Platform::X86_64Linux,
+            is_unsafe: false,
+            networking: false,
+            jobs: Some(8),
         })
         .hash()
         .to_string(),
@@ -317,6 +405,7 @@ async fn test_recipe_hash_stable_process() -> anyhow::Result<()> {
             platform: Platform::X86_64Linux,
             is_unsafe: false,
             networking: false,
+            jobs: None,
         })
         .hash()
         .to_string(),
@@ -342,6 +431,7 @@ async fn test_recipe_hash_stable_process() -> anyhow::Result<()> {
             platform: Platform::X86_64Linux,
             is_unsafe: false,
             networking: false,
+            jobs: None,
         })
         .hash()
         .to_string(),
@@ -374,6 +464,7 @@ async fn test_recipe_hash_stable_process() -> anyhow::Result<()> {
             platform: Platform::X86_64Linux,
             is_unsafe: false,
             networking: false,
+            jobs: None,
         })
         .hash()
         .to_string(),
@@ -413,6 +504,7 @@ async fn test_recipe_hash_stable_process() -> anyhow::Result<()> {
             platform: Platform::X86_64Linux,
             is_unsafe: false,
             networking: false,
+            jobs: None,
         })
         .hash()
         .to_string(),
@@ -452,6 +544,7 @@ async fn test_recipe_hash_stable_process() -> anyhow::Result<()> {
             platform: Platform::X86_64Linux,
             is_unsafe: true,
             networking: false,
+            jobs: None,
         })
         .hash()
         .to_string(),
@@ -491,6 +584,7 @@ async fn test_recipe_hash_stable_process() -> anyhow::Result<()> {
             platform: Platform::X86_64Linux,
             is_unsafe: true,
             networking: true,
+            jobs: None,
         })
         .hash()
         .to_string(),
@@ -504,3 +598,34 @@ async fn test_recipe_hash_stable_process() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_recipe_hash_stable_nested_process_jobs() -> anyhow::Result<()> {
+    let (_brioche, _context) = brioche_test_support::brioche_test().await;
+
+    let merge = |jobs: Option<u32>| Recipe::Merge {
+        directories: vec![WithMeta::without_meta(Recipe::Process(ProcessRecipe {
+            command: ProcessTemplate {
+                components: vec![ProcessTemplateComponent::Literal {
+                    value: "/usr/bin/env".into(),
+                }],
+            },
+            args: vec![],
+            env: BTreeMap::default(),
+            dependencies: vec![],
+            work_dir: Box::new(brioche_test_support::without_meta(
+                brioche_test_support::lazy_dir_empty(),
+            )),
+            output_scaffold: None,
+            platform: Platform::X86_64Linux,
+            is_unsafe: false,
+            networking: false,
+            jobs,
+        }))],
+    };
+
+    // The number of jobs doesn't affect the hash, even for a nested process
+    assert_eq!(merge(Some(8)).hash(), merge(None).hash());
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+#![cfg(target_os = "linux")]
+
+use std::collections::BTreeMap;
+
+use assert_matches::assert_matches;
+use pretty_assertions::assert_eq;
+
+use brioche_core::{
+    platform::current_platform,
+    recipe::{ProcessRecipe, Recipe},
+};
+use brioche_test_support::{bake_without_meta, default_process, output_path, template_input, tpl};
+
+fn sandbox_config() -> brioche_core::config::SandboxConfig {
+    match std::env::var("BRIOCHE_TEST_SANDBOX").as_deref() {
+        Ok("linux_namespace") => {
+            let proot = match std::env::var("BRIOCHE_TEST_SANDBOX_PROOT").as_deref() {
+                Ok("true") => Some(brioche_core::config::PRootConfig::Value(true)),
+                Ok("false") => Some(brioche_core::config::PRootConfig::Value(false)),
+                Ok("auto") => Some(brioche_core::config::PRootConfig::Auto(
+                    brioche_core::config::PRootAutoConfig::Auto,
+                )),
+                _ => None,
+            };
+            brioche_core::config::SandboxConfig::LinuxNamespace(
+                brioche_core::config::SandboxLinuxNamespaceConfig { proot },
+            )
+        }
+        _ => brioche_core::config::SandboxConfig::default(),
+    }
+}
+
+/// Start a worker on localhost, and return a Brioche instance that sends
+/// its processes to it.
+async fn remote_execution_test() -> (
+    brioche_core::Brioche,
+    brioche_test_support::TestContext,
+    brioche_core::Brioche,
+    brioche_test_support::TestContext,
+) {
+    let (worker_brioche, worker_context) = brioche_test_support::brioche_test_with(|builder| {
+        builder.config(brioche_core::config::BriocheConfig {
+            sandbox: sandbox_config(),
+            ..Default::default()
+        })
+    })
+    .await;
+    brioche_test_support::load_rootfs_recipes(&worker_brioche, current_platform()).await;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let worker_addr = listener.local_addr().unwrap();
+    tokio::spawn(brioche_core::remote_execution::worker::serve(
+        worker_brioche.clone(),
+        listener,
+    ));
+
+    let worker_url = format!("http://{worker_addr}/").parse().unwrap();
+    let (brioche, context) = brioche_test_support::brioche_test_with(|builder| {
+        builder.config(brioche_core::config::BriocheConfig {
+            remote_execution: Some(brioche_core::config::RemoteExecutionConfig { url: worker_url }),
+            ..Default::default()
+        })
+    })
+    .await;
+
+    (brioche, context, worker_brioche, worker_context)
+}
+
+#[tokio::test]
+async fn test_remote_execution_process() -> anyhow::Result<()> {
+    let (brioche, _context, worker_brioche, _worker_context) = remote_execution_test().await;
+
+    // Only the client has the input blob, so it needs to get sent to
+    // the worker
+    let input_blob = brioche_test_support::blob(&brioche, "hello").await;
+    let input_path = brioche_core::blob::local_blob_path(&worker_brioche, input_blob);
+    assert!(!tokio::fs::try_exists(&input_path).await?);
+
+    let process = Recipe::Process(ProcessRecipe {
+        command: tpl("/usr/bin/env"),
+        args: vec![
+            tpl("sh"),
+            tpl("-c"),
+            tpl(r#"cat "$INPUT" > "$BRIOCHE_OUTPUT" && echo -n ", world" >> "$BRIOCHE_OUTPUT""#),
+        ],
+        env: BTreeMap::from_iter([
+            ("BRIOCHE_OUTPUT".into(), output_path()),
+            (
+                "INPUT".into(),
+                template_input(brioche_test_support::lazy_file(input_blob, false)),
+            ),
+        ]),
+        ..default_process()
+    });
+
+    let output_blob = brioche_core::blob::BlobHash::for_content(b"hello, world");
+    assert_eq!(
+        bake_without_meta(&brioche, process).await?,
+        brioche_test_support::file(output_blob, false),
+    );
+
+    assert!(tokio::fs::try_exists(&input_path).await?);
+
+    // The output blob should be copied back from the worker
+    let output_path = brioche_core::blob::local_blob_path(&brioche, output_blob);
+    assert_eq!(tokio::fs::read(&output_path).await?, b"hello, world");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_remote_execution_process_failure() -> anyhow::Result<()> {
+    let (brioche, _context, _worker_brioche, _worker_context) = remote_execution_test().await;
+
+    let process = Recipe::Process(ProcessRecipe {
+        command: tpl("/usr/bin/env"),
+        args: vec![tpl("sh"), tpl("-c"), tpl("exit 1")],
+        ..default_process()
+    });
+
+    let result = bake_without_meta(&brioche, process).await;
+    let error = assert_matches!(result, Err(error) => error);
+    assert!(
+        format!("{error:#}").contains("failed on worker"),
+        "unexpected error: {error:#}"
+    );
+
+    Ok(())
+}
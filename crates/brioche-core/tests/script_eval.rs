@@ -622,6 +622,7 @@ async fn test_eval_brioche_download() -> anyhow::Result<()> {
         brioche_core::recipe::Recipe::Download(brioche_core::recipe::DownloadRecipe {
             url: download_url.parse().unwrap(),
             hash: hello_hash,
+            mirrors: vec![],
         })
     );
 
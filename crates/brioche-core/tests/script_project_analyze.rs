@@ -510,12 +510,73 @@ async fn test_analyze_static_brioche_download() -> anyhow::Result<()> {
         root_module.statics,
         BTreeSet::from_iter([StaticQuery::Download {
             url: "https://example.com".parse()?,
+            hash: None,
         }]),
     );
 
     Ok(())
 }
 
+#[tokio::test]
+async fn test_analyze_static_brioche_download_with_hash() -> anyhow::Result<()> {
+    let (brioche, context) = brioche_test_support::brioche_test().await;
+
+    let project_dir = context.mkdir("myproject").await;
+    context
+        .write_file(
+            "myproject/project.bri",
+            r#"
+                export function () {
+                    return Brioche.download("https://example.com", {
+                        hash: "blake3:af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+                    });
+                }
+            "#,
+        )
+        .await;
+
+    let project = analyze_project(&brioche.vfs, &project_dir).await?;
+
+    let root_module = &project.local_modules[&project.root_module];
+
+    assert_eq!(
+        root_module.statics,
+        BTreeSet::from_iter([StaticQuery::Download {
+            url: "https://example.com".parse()?,
+            hash: Some(
+                "blake3:af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+                    .parse()?
+            ),
+        }]),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_analyze_static_brioche_download_with_invalid_hash() -> anyhow::Result<()> {
+    let (brioche, context) = brioche_test_support::brioche_test().await;
+
+    let project_dir = context.mkdir("myproject").await;
+    context
+        .write_file(
+            "myproject/project.bri",
+            r#"
+                export function () {
+                    return Brioche.download("https://example.com", {
+                        hash: "md5:d41d8cd98f00b204e9800998ecf8427e",
+                    });
+                }
+            "#,
+        )
+        .await;
+
+    let result = analyze_project(&brioche.vfs, &project_dir).await;
+    assert_matches!(result, Err(_));
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_analyze_static_brioche_download_with_project_version() -> anyhow::Result<()> {
     let (brioche, context) = brioche_test_support::brioche_test().await;
@@ -544,6 +605,7 @@ async fn test_analyze_static_brioche_download_with_project_version() -> anyhow::
         root_module.statics,
         BTreeSet::from_iter([StaticQuery::Download {
             url: "https://example.com/v1.0.0/download.tar.gz".parse()?,
+            hash: None,
         }]),
     );
 
@@ -579,6 +641,7 @@ async fn test_analyze_static_brioche_download_with_project_version_brackets() ->
         root_module.statics,
         BTreeSet::from_iter([StaticQuery::Download {
             url: "https://example.com/v1.0.0/download.tar.gz".parse()?,
+            hash: None,
         }]),
     );
 
@@ -622,6 +685,7 @@ async fn test_analyze_static_brioche_download_with_project_extras() -> anyhow::R
         root_module.statics,
         BTreeSet::from_iter([StaticQuery::Download {
             url: "https://example.com/v1.0.0/download.tar.gz".parse()?,
+            hash: None,
         }]),
     );
 
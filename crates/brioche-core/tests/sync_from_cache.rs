@@ -91,11 +91,7 @@ async fn brioche_test_with_cache(
     writable: bool,
 ) -> (Brioche, brioche_test_support::TestContext) {
     brioche_test_support::brioche_test_with(move |builder| {
-        builder.cache_client(CacheClient {
-            store: Some(store),
-            writable,
-            ..Default::default()
-        })
+        builder.cache_client(CacheClient::from_store(store, writable))
     })
     .await
 }
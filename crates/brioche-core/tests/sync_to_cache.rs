@@ -73,17 +73,165 @@ async fn test_sync_to_cache_process_and_complete_process() -> anyhow::Result<()>
     Ok(())
 }
 
+#[tokio::test]
+async fn test_sync_bakes_to_cache_filters_and_dry_run() -> anyhow::Result<()> {
+    let cache = brioche_test_support::new_cache();
+    let (brioche, _context) = brioche_test_with_cache(cache, true).await;
+
+    let small_process = Recipe::Process(brioche_core::recipe::ProcessRecipe {
+        command: tpl("/usr/bin/env"),
+        args: vec![tpl("sh"), tpl("-c"), tpl("small")],
+        ..brioche_test_support::default_process_x86_64_linux()
+    });
+    let small_blob = brioche_test_support::blob(&brioche, "small").await;
+    let small_output = brioche_test_support::file(small_blob, false);
+
+    let large_process = Recipe::Process(brioche_core::recipe::ProcessRecipe {
+        command: tpl("/usr/bin/env"),
+        args: vec![tpl("sh"), tpl("-c"), tpl("large")],
+        ..brioche_test_support::default_process_x86_64_linux()
+    });
+    let large_blob = brioche_test_support::blob(&brioche, "large".repeat(100)).await;
+    let large_output = brioche_test_support::file(large_blob, false);
+
+    let download = Recipe::Download(brioche_core::recipe::DownloadRecipe {
+        hash: brioche_test_support::sha256("download"),
+        url: "https://example.com/download.txt".parse()?,
+        mirrors: vec![],
+    });
+    let download_blob = brioche_test_support::blob(&brioche, "download").await;
+    let download_output = brioche_test_support::file(download_blob, false);
+
+    let bakes = vec![
+        (small_process.clone(), small_output.clone()),
+        (large_process.clone(), large_output.clone()),
+        (download.clone(), download_output),
+    ];
+    let options = brioche_core::sync::CacheSyncOptions {
+        processes_only: true,
+        max_artifact_size: Some(100),
+        dry_run: true,
+    };
+
+    // A dry run should report what would be synced without writing
+    let results =
+        brioche_core::sync::sync_bakes_to_cache(&brioche, bakes.clone(), &options).await?;
+    assert_eq!(results.num_bakes, 1);
+    assert_eq!(results.num_new_bakes, 1);
+    assert_eq!(results.num_new_artifacts, 1);
+    assert_eq!(results.num_skipped_bakes, 2);
+    assert_eq!(results.skipped_artifacts.len(), 1);
+    assert_eq!(
+        results.skipped_artifacts[0].artifact_hash,
+        large_output.hash()
+    );
+    assert_eq!(results.skipped_artifacts[0].size, 500);
+    assert_eq!(
+        brioche_core::cache::load_bake(&brioche, small_process.hash()).await?,
+        None
+    );
+
+    // Syncing for real should only write the bakes that passed the filters
+    let results = brioche_core::sync::sync_bakes_to_cache(
+        &brioche,
+        bakes,
+        &brioche_core::sync::CacheSyncOptions {
+            dry_run: false,
+            ..options
+        },
+    )
+    .await?;
+    assert_eq!(results.num_new_bakes, 1);
+    assert_eq!(
+        brioche_core::cache::load_bake(&brioche, small_process.hash()).await?,
+        Some(small_output.hash())
+    );
+    assert_eq!(
+        brioche_core::cache::load_bake(&brioche, large_process.hash()).await?,
+        None
+    );
+    assert_eq!(
+        brioche_core::cache::load_bake(&brioche, download.hash()).await?,
+        None
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_to_cache_max_artifact_size_with_lazy_blobs() -> anyhow::Result<()> {
+    let source_cache = brioche_test_support::new_cache();
+    let large_size = 4 * 1024 * 1024;
+    let large_blob;
+    let large_output;
+
+    {
+        let (brioche, _context) = brioche_test_with_cache(source_cache.clone(), true).await;
+        // Big enough to be chunked, so it can be loaded lazily
+        large_blob = brioche_test_support::blob(&brioche, vec![1; large_size]).await;
+        large_output = brioche_test_support::file(large_blob, false);
+        brioche_core::cache::save_artifact(&brioche, large_output.clone()).await?;
+    }
+
+    let (brioche, _context) = brioche_test_support::brioche_test_with(|builder| {
+        builder
+            .cache_client(CacheClient {
+                lazy_fetch: true,
+                ..CacheClient::from_store(source_cache, false)
+            })
+            .registry_client(RegistryClient::disabled())
+    })
+    .await;
+
+    // Load the artifact without fetching its blob
+    let loaded = brioche_core::cache::load_artifact_lazy(
+        &brioche,
+        large_output.hash(),
+        brioche_core::reporter::job::CacheFetchKind::Bake,
+    )
+    .await?;
+    assert_eq!(loaded, Some(large_output.clone()));
+    let large_blob_path = brioche_core::blob::local_blob_path(&brioche, large_blob);
+    assert!(!tokio::fs::try_exists(&large_blob_path).await?);
+
+    let large_process = Recipe::Process(brioche_core::recipe::ProcessRecipe {
+        command: tpl("/usr/bin/env"),
+        args: vec![tpl("sh"), tpl("-c"), tpl("large")],
+        ..brioche_test_support::default_process_x86_64_linux()
+    });
+
+    // The blob isn't local, but it should still count towards the size
+    let results = brioche_core::sync::sync_bakes_to_cache(
+        &brioche,
+        vec![(large_process, large_output.clone())],
+        &brioche_core::sync::CacheSyncOptions {
+            processes_only: false,
+            max_artifact_size: Some(1024 * 1024),
+            dry_run: true,
+        },
+    )
+    .await?;
+    assert_eq!(results.num_bakes, 0);
+    assert_eq!(results.skipped_artifacts.len(), 1);
+    assert_eq!(
+        results.skipped_artifacts[0].artifact_hash,
+        large_output.hash()
+    );
+    assert_eq!(
+        results.skipped_artifacts[0].size,
+        u64::try_from(large_size)?
+    );
+
+    Ok(())
+}
+
 async fn brioche_test_with_cache(
     store: Arc<dyn object_store::ObjectStore>,
     writable: bool,
 ) -> (Brioche, brioche_test_support::TestContext) {
     brioche_test_support::brioche_test_with(move |builder| {
         builder
-            .cache_client(CacheClient {
-                store: Some(store),
-                writable,
-                ..Default::default()
-            })
+            .cache_client(CacheClient::from_store(store, writable))
             .registry_client(RegistryClient::disabled())
     })
     .await
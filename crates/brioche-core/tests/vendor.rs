@@ -0,0 +1,107 @@
+use brioche_core::{BriocheBuilder, config::BriocheConfig};
+
+#[tokio::test]
+async fn test_vendor_project_builds_offline() -> anyhow::Result<()> {
+    let (brioche, context) = brioche_test_support::brioche_test().await;
+
+    let mut server = mockito::Server::new_async().await;
+    let server_url = server.url();
+
+    let hello = "hello";
+    let hello_blob = brioche_test_support::blob(&brioche, hello).await;
+    let hello_endpoint = server
+        .mock("GET", "/file.txt")
+        .with_body(hello)
+        .expect(1)
+        .create();
+
+    let project_dir = context.mkdir("myproject").await;
+    context
+        .write_file(
+            "myproject/project.bri",
+            r#"
+                globalThis.Brioche = {
+                    download: (url) => {
+                        return {
+                            briocheSerialize: async () => {
+                                return Deno.core.ops.op_brioche_get_static(
+                                    import.meta.url,
+                                    {
+                                        type: "download",
+                                        url,
+                                    },
+                                );
+                            },
+                        };
+                    }
+                }
+
+                export default () => {
+                    return Brioche.download("<DOWNLOAD_URL>");
+                };
+            "#
+            .replace("<DOWNLOAD_URL>", &format!("{server_url}/file.txt")),
+        )
+        .await;
+
+    let (projects, project_hash) =
+        brioche_test_support::load_project(&brioche, &project_dir).await?;
+    projects.commit_dirty_lockfiles().await?;
+
+    let vendor_dir = context.mkdir("vendor").await;
+    let results = brioche_core::vendor::vendor_project(
+        &brioche,
+        &projects,
+        project_hash,
+        "default",
+        &vendor_dir,
+    )
+    .await?;
+    assert_eq!(results.num_projects, 1);
+    assert_eq!(results.num_bakes, 1);
+
+    let config =
+        tokio::fs::read_to_string(vendor_dir.join(brioche_core::vendor::VENDOR_CONFIG_FILENAME))
+            .await?;
+    let config = toml::from_str::<BriocheConfig>(&config)?;
+    assert!(config.offline);
+
+    hello_endpoint.assert();
+
+    // Build the project again from scratch, using only the generated
+    // config with an empty data dir
+    let offline_data_dir = context.mkdir("offline-brioche-data").await;
+    let (reporter, _reporter_guard) = brioche_core::reporter::start_test_reporter();
+    let offline_brioche = BriocheBuilder::new(reporter)
+        .config(config)
+        .data_dir(offline_data_dir)
+        .self_exec_processes(false)
+        .build()
+        .await?;
+    assert!(offline_brioche.offline);
+
+    let (offline_projects, offline_project_hash) =
+        brioche_test_support::load_project(&offline_brioche, &project_dir).await?;
+    assert_eq!(offline_project_hash, project_hash);
+
+    let recipe = brioche_core::script::evaluate::evaluate(
+        &offline_brioche,
+        &offline_projects,
+        offline_project_hash,
+        "default",
+    )
+    .await?;
+
+    // The vendored cache has everything, so nothing should be reported
+    // as missing before baking
+    let missing =
+        brioche_core::bake::find_offline_missing_inputs(&offline_brioche, &recipe.value).await?;
+    assert_eq!(missing, Vec::<String>::new());
+
+    let artifact = brioche_test_support::bake_without_meta(&offline_brioche, recipe.value).await?;
+    assert_eq!(artifact, brioche_test_support::file(hello_blob, false));
+
+    hello_endpoint.assert();
+
+    Ok(())
+}
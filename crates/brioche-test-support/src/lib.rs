@@ -154,6 +154,16 @@ pub async fn load_rootfs_recipes(brioche: &Brioche, platform: brioche_core::plat
             Recipe::Sync { recipe } => {
                 recipes.push_back(recipe.value);
             }
+            Recipe::Substitute(substitute) => {
+                recipes.push_back(substitute.file.value);
+                for template in substitute.replacements.into_values() {
+                    for component in template.components {
+                        if let ProcessTemplateComponent::Input { recipe } = component {
+                            recipes.push_back(recipe.value);
+                        }
+                    }
+                }
+            }
             Recipe::File {
                 content_blob: _,
                 executable: _,
@@ -161,6 +171,7 @@ pub async fn load_rootfs_recipes(brioche: &Brioche, platform: brioche_core::plat
             } => unimplemented!(),
             Recipe::Directory(_) => unimplemented!(),
             Recipe::Symlink { target: _ } => {}
+            Recipe::GitCheckout(_) => unimplemented!(),
         }
     }
 
@@ -180,6 +191,8 @@ async fn cached_download(
     // Get the path to the cached file
     let filename_safe_hash = match &download.hash {
         brioche_core::Hash::Sha256 { value } => format!("sha256-{}", hex::encode(value)),
+        brioche_core::Hash::Sha512 { value } => format!("sha512-{}", hex::encode(value)),
+        brioche_core::Hash::Blake3 { value } => format!("blake3-{}", hex::encode(value)),
     };
     let mut cached_path = cached_downloads_dir.join(format!("download-{filename_safe_hash}"));
 
@@ -482,6 +495,7 @@ pub fn default_process_x86_64_linux() -> ProcessRecipe {
         platform: brioche_core::platform::Platform::X86_64Linux,
         is_unsafe: false,
         networking: false,
+        jobs: None,
     }
 }
 
@@ -498,6 +512,7 @@ pub fn default_process() -> ProcessRecipe {
         platform: brioche_core::platform::current_platform(),
         is_unsafe: false,
         networking: false,
+        jobs: None,
     }
 }
 
@@ -690,11 +705,7 @@ impl TestContext {
             |builder| {
                 builder
                     .registry_client(self.brioche.registry_client.clone())
-                    .cache_client(brioche_core::cache::CacheClient {
-                        store: Some(cache),
-                        writable: true,
-                        ..Default::default()
-                    })
+                    .cache_client(brioche_core::cache::CacheClient::from_store(cache, true))
             }
         })
         .await;
@@ -46,6 +46,9 @@ pub struct BuildArgs {
     /// The output display format.
     #[arg(long, value_enum, default_value_t)]
     display: super::DisplayMode,
+
+    #[command(flatten)]
+    offline: super::OfflineArgs,
 }
 
 #[expect(clippy::print_stdout)]
@@ -54,7 +57,9 @@ pub async fn build(args: BuildArgs) -> anyhow::Result<ExitCode> {
         args.display.to_console_reporter_kind(),
     )?;
 
-    let brioche = brioche_core::BriocheBuilder::new(reporter.clone())
+    let brioche = args
+        .offline
+        .apply(brioche_core::BriocheBuilder::new(reporter.clone()))
         .keep_temps(args.keep_temps)
         .sync(args.sync)
         .build()
@@ -114,6 +119,8 @@ pub async fn build(args: BuildArgs) -> anyhow::Result<ExitCode> {
         )
         .await?;
 
+        super::check_offline_inputs(&brioche, &recipe.value).await?;
+
         let artifact = brioche_core::bake::bake(
             &brioche,
             recipe,
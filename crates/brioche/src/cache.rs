@@ -0,0 +1,35 @@
+use std::process::ExitCode;
+
+use clap::Subcommand;
+
+mod keygen;
+mod prune;
+
+#[derive(Debug, Subcommand)]
+pub enum CacheSubcommand {
+    /// Create a key for signing bakes and projects written to a cache
+    Keygen(keygen::KeygenArgs),
+
+    /// Delete bakes, projects, and artifacts from a cache that aren't
+    /// kept by any retention rule
+    Prune(prune::PruneArgs),
+}
+
+pub fn cache(command: CacheSubcommand) -> anyhow::Result<ExitCode> {
+    match command {
+        CacheSubcommand::Keygen(args) => {
+            keygen::keygen(&args)?;
+
+            Ok(ExitCode::SUCCESS)
+        }
+        CacheSubcommand::Prune(args) => {
+            let rt = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()?;
+
+            let exit_code = rt.block_on(prune::prune(args))?;
+
+            Ok(exit_code)
+        }
+    }
+}
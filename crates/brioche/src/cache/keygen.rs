@@ -0,0 +1,34 @@
+use std::{io::Write as _, os::unix::fs::OpenOptionsExt as _, path::PathBuf};
+
+use anyhow::Context as _;
+use brioche_core::cache::signing::SigningKey;
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+pub struct KeygenArgs {
+    /// Where to write the private key. Set it as `signing_key_path` for
+    /// a writable cache
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+#[expect(clippy::print_stdout)]
+pub fn keygen(args: &KeygenArgs) -> anyhow::Result<()> {
+    let (signing_key, encoded) = SigningKey::generate()?;
+
+    // Only the current user should be able to read the private key
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&args.output)
+        .with_context(|| format!("failed to create {}", args.output.display()))?;
+    writeln!(file, "{encoded}")
+        .with_context(|| format!("failed to write {}", args.output.display()))?;
+
+    println!("Wrote private key to {}", args.output.display());
+    println!("Add the public key to `trusted_keys` for caches that should trust it:");
+    println!("{}", signing_key.public_key());
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use std::process::ExitCode;
+
+use brioche_core::{cache::prune::PruneOptions, utils::DisplayDuration};
+use clap::Parser;
+use tracing::Instrument as _;
+
+#[derive(Debug, Parser)]
+pub struct PruneArgs {
+    /// Keep bakes and projects written within this many days
+    #[arg(long)]
+    keep_days: Option<u64>,
+
+    /// Keep this many of the most recently written projects, plus every
+    /// bake reachable from them. Reachable bakes are found from the local
+    /// database, so this should run on the machine that baked the projects
+    #[arg(long)]
+    keep_projects: Option<usize>,
+
+    /// Show what would be deleted without deleting anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// The output display format.
+    #[arg(long, value_enum, default_value_t)]
+    display: crate::DisplayMode,
+
+    #[command(flatten)]
+    offline: crate::OfflineArgs,
+}
+
+#[expect(clippy::print_stdout)]
+pub async fn prune(args: PruneArgs) -> anyhow::Result<ExitCode> {
+    let (reporter, mut guard) = brioche_core::reporter::console::start_console_reporter(
+        args.display.to_console_reporter_kind(),
+    )?;
+
+    let brioche = args
+        .offline
+        .apply(brioche_core::BriocheBuilder::new(reporter.clone()))
+        .build()
+        .await?;
+    crate::start_shutdown_handler(brioche.clone());
+
+    let options = PruneOptions {
+        max_age: args
+            .keep_days
+            .map(|days| std::time::Duration::from_secs(days.saturating_mul(24 * 60 * 60))),
+        keep_projects: args.keep_projects,
+        min_age: None,
+        dry_run: args.dry_run,
+    };
+    let results = brioche_core::cache::prune::prune(&brioche, &options)
+        .instrument(tracing::info_span!("cache_prune"))
+        .await?;
+
+    guard.shutdown_console().await;
+
+    let elapsed = DisplayDuration(reporter.elapsed());
+    let deleted_mib = results.deleted_bytes / (1024 * 1024);
+    let verb = if args.dry_run {
+        "Would delete"
+    } else {
+        "Deleted"
+    };
+    println!(
+        "{verb} {} objects ({deleted_mib} MiB) in {elapsed}: {} bakes, {} projects, {} artifacts, {} chunks, {} dictionaries",
+        results.deleted_objects(),
+        results.deleted_bakes,
+        results.deleted_projects,
+        results.deleted_artifacts,
+        results.deleted_chunks,
+        results.deleted_dictionaries,
+    );
+    println!("Kept {} objects", results.kept_objects);
+    if results.projects_without_bakes > 0 {
+        println!(
+            "Warning: {} kept projects have no bakes recorded on this machine, so their bakes were only kept if they're recent",
+            results.projects_without_bakes,
+        );
+    }
+
+    brioche.wait_for_tasks().await;
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -24,6 +24,9 @@ pub struct CheckArgs {
     /// The output display format.
     #[arg(long, value_enum, default_value_t)]
     display: super::DisplayMode,
+
+    #[command(flatten)]
+    offline: super::OfflineArgs,
 }
 
 pub async fn check(args: CheckArgs) -> anyhow::Result<ExitCode> {
@@ -31,7 +34,9 @@ pub async fn check(args: CheckArgs) -> anyhow::Result<ExitCode> {
         args.display.to_console_reporter_kind(),
     )?;
 
-    let brioche = brioche_core::BriocheBuilder::new(reporter.clone())
+    let brioche = args
+        .offline
+        .apply(brioche_core::BriocheBuilder::new(reporter.clone()))
         .build()
         .await?;
     crate::start_shutdown_handler(brioche.clone());
@@ -0,0 +1,101 @@
+use std::process::ExitCode;
+
+use brioche_core::{project::ProjectLocking, utils::DisplayDuration};
+use clap::Parser;
+use tracing::Instrument as _;
+
+#[derive(Debug, Parser)]
+pub struct FetchArgs {
+    #[command(flatten)]
+    project: super::ProjectArgs,
+
+    /// Which TypeScript export to fetch
+    #[arg(default_value = "default")]
+    export: String,
+
+    /// Validate that the lockfile is up-to-date
+    #[arg(long)]
+    locked: bool,
+
+    /// The output display format.
+    #[arg(long, value_enum, default_value_t)]
+    display: super::DisplayMode,
+}
+
+#[expect(clippy::print_stdout)]
+pub async fn fetch(args: FetchArgs) -> anyhow::Result<ExitCode> {
+    let (reporter, mut guard) = brioche_core::reporter::console::start_console_reporter(
+        args.display.to_console_reporter_kind(),
+    )?;
+
+    let brioche = brioche_core::BriocheBuilder::new(reporter.clone())
+        .build()
+        .await?;
+    crate::start_shutdown_handler(brioche.clone());
+
+    let projects = brioche_core::project::Projects::default();
+
+    let locking = if args.locked {
+        ProjectLocking::Locked
+    } else {
+        ProjectLocking::Unlocked
+    };
+
+    let fetch_future = async {
+        let project_hash = super::load_project(&brioche, &projects, &args.project, locking).await?;
+
+        // The lockfile records the resolved downloads and git refs, so it
+        // needs to be up-to-date for the project to load offline
+        if args.locked {
+            projects.validate_no_dirty_lockfiles()?;
+        } else {
+            let num_lockfiles_updated = projects.commit_dirty_lockfiles().await?;
+            if num_lockfiles_updated > 0 {
+                tracing::info!(num_lockfiles_updated, "updated lockfiles");
+            }
+        }
+
+        let recipe = brioche_core::script::evaluate::evaluate(
+            &brioche,
+            &projects,
+            project_hash,
+            &args.export,
+        )
+        .await?;
+
+        let brioche_core::fetch::FetchResults {
+            num_cached_bakes,
+            num_downloads,
+            unfetched,
+        } = brioche_core::fetch::fetch(&brioche, &recipe.value).await?;
+
+        guard.shutdown_console().await;
+
+        let elapsed = DisplayDuration(reporter.elapsed());
+        println!(
+            "Fetched {num_cached_bakes} cached bakes and {num_downloads} downloads in {elapsed}"
+        );
+
+        if unfetched.is_empty() {
+            println!("Everything needed to build is available offline");
+        } else {
+            println!(
+                "{} recipes weren't in the cache and will need to be baked:",
+                unfetched.len()
+            );
+            for recipe in &unfetched {
+                println!("- {}", recipe.description);
+            }
+        }
+
+        brioche.wait_for_tasks().await;
+
+        anyhow::Ok(ExitCode::SUCCESS)
+    };
+
+    let exit_code = fetch_future
+        .instrument(tracing::info_span!("fetch"))
+        .await?;
+
+    Ok(exit_code)
+}
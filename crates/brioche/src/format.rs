@@ -22,6 +22,9 @@ pub struct FormatArgs {
     /// The output display format.
     #[arg(long, value_enum, default_value_t)]
     display: super::DisplayMode,
+
+    #[command(flatten)]
+    offline: super::OfflineArgs,
 }
 
 pub async fn format(args: FormatArgs) -> anyhow::Result<ExitCode> {
@@ -29,7 +32,9 @@ pub async fn format(args: FormatArgs) -> anyhow::Result<ExitCode> {
         args.display.to_console_reporter_kind(),
     )?;
 
-    let brioche = brioche_core::BriocheBuilder::new(reporter.clone())
+    let brioche = args
+        .offline
+        .apply(brioche_core::BriocheBuilder::new(reporter.clone()))
         .build()
         .await?;
     crate::start_shutdown_handler(brioche.clone());
@@ -34,6 +34,9 @@ pub struct InstallArgs {
     /// The output display format.
     #[arg(long, value_enum, default_value_t)]
     display: super::DisplayMode,
+
+    #[command(flatten)]
+    offline: super::OfflineArgs,
 }
 
 pub async fn install(args: InstallArgs) -> anyhow::Result<ExitCode> {
@@ -41,7 +44,9 @@ pub async fn install(args: InstallArgs) -> anyhow::Result<ExitCode> {
         args.display.to_console_reporter_kind(),
     )?;
 
-    let brioche = brioche_core::BriocheBuilder::new(reporter.clone())
+    let brioche = args
+        .offline
+        .apply(brioche_core::BriocheBuilder::new(reporter.clone()))
         .build()
         .await?;
     crate::start_shutdown_handler(brioche.clone());
@@ -199,6 +204,8 @@ async fn run_install(
             brioche_core::script::evaluate::evaluate(brioche, projects, project_hash, export)
                 .await?;
 
+        super::check_offline_inputs(brioche, &recipe.value).await?;
+
         let artifact = brioche_core::bake::bake(
             brioche,
             recipe,
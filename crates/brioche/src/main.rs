@@ -7,7 +7,9 @@ use brioche_core::{
 use clap::Parser;
 
 mod build;
+mod cache;
 mod check;
+mod fetch;
 mod format;
 mod install;
 mod jobs;
@@ -17,6 +19,10 @@ mod publish;
 mod run;
 mod run_sandbox;
 mod self_update;
+mod serve_cache;
+mod sync;
+mod vendor;
+mod worker;
 
 #[derive(Debug, Parser)]
 #[command(version)]
@@ -44,6 +50,27 @@ enum Args {
     /// Publish a project to a registry
     Publish(publish::PublishArgs),
 
+    /// Download everything needed to build a project offline, without
+    /// baking any processes
+    Fetch(fetch::FetchArgs),
+
+    /// Write everything needed to build a project offline to a directory
+    Vendor(vendor::VendorArgs),
+
+    /// Upload the bakes from a project's exports to the writable cache
+    Sync(sync::SyncArgs),
+
+    /// Manage caches
+    #[command(subcommand)]
+    Cache(cache::CacheSubcommand),
+
+    /// Share bakes with other machines by serving them as an HTTP cache
+    ServeCache(serve_cache::ServeCacheArgs),
+
+    /// Run processes sent from other machines. Clients use the worker by
+    /// setting `remote_execution.url` in their config
+    Worker(worker::WorkerArgs),
+
     /// Start the Language Server Protocol server
     Lsp(lsp::LspArgs),
 
@@ -125,6 +152,51 @@ fn main() -> anyhow::Result<ExitCode> {
 
             Ok(exit_code)
         }
+        Args::Fetch(args) => {
+            let rt = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()?;
+
+            let exit_code = rt.block_on(fetch::fetch(args))?;
+
+            Ok(exit_code)
+        }
+        Args::Vendor(args) => {
+            let rt = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()?;
+
+            let exit_code = rt.block_on(vendor::vendor(args))?;
+
+            Ok(exit_code)
+        }
+        Args::Sync(args) => {
+            let rt = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()?;
+
+            let exit_code = rt.block_on(sync::sync(args))?;
+
+            Ok(exit_code)
+        }
+        Args::ServeCache(args) => {
+            let rt = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()?;
+
+            let exit_code = rt.block_on(serve_cache::serve_cache(args))?;
+
+            Ok(exit_code)
+        }
+        Args::Worker(args) => {
+            let rt = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()?;
+
+            let exit_code = rt.block_on(worker::worker(args))?;
+
+            Ok(exit_code)
+        }
         Args::Lsp(args) => {
             let rt = tokio::runtime::Builder::new_multi_thread()
                 .enable_all()
@@ -148,6 +220,7 @@ fn main() -> anyhow::Result<ExitCode> {
             }
         }
         Args::Jobs(command) => jobs::jobs(command),
+        Args::Cache(command) => cache::cache(command),
         Args::Analyze(args) => {
             let rt = tokio::runtime::Builder::new_multi_thread()
                 .enable_all()
@@ -261,6 +334,45 @@ struct ProjectArgs {
     registry: Option<String>,
 }
 
+#[derive(Debug, clap::Args)]
+struct OfflineArgs {
+    /// Don't access the network. Only locally available blobs, bakes,
+    /// and lockfile data will be used
+    #[arg(long)]
+    offline: bool,
+}
+
+impl OfflineArgs {
+    /// Apply `--offline` to a builder. Without the flag, `$BRIOCHE_OFFLINE`
+    /// or the config decide whether to run offline.
+    fn apply(&self, builder: brioche_core::BriocheBuilder) -> brioche_core::BriocheBuilder {
+        if self.offline {
+            builder.offline(true)
+        } else {
+            builder
+        }
+    }
+}
+
+/// When running offline, report every missing network input needed to
+/// bake `recipe` up front, rather than failing on the first one during
+/// the bake.
+async fn check_offline_inputs(
+    brioche: &brioche_core::Brioche,
+    recipe: &brioche_core::recipe::Recipe,
+) -> anyhow::Result<()> {
+    if !brioche.offline {
+        return Ok(());
+    }
+
+    let missing = brioche_core::bake::find_offline_missing_inputs(brioche, recipe).await?;
+    if !missing.is_empty() {
+        return Err(brioche_core::network::OfflineError { needed: missing }.into());
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, clap::Args)]
 #[group(required = false, multiple = false)]
 struct MultipleProjectArgs {
@@ -39,13 +39,12 @@ pub async fn migrate_registry_to_cache(args: MigrateRegistryToCacheArgs) -> anyh
     let brioche = brioche_core::BriocheBuilder::new(reporter.clone())
         .data_dir(args.data_dir)
         .registry_client(brioche_core::registry::RegistryClient::disabled())
-        .cache_client(brioche_core::cache::CacheClient {
-            store: Some(Arc::new(
-                object_store::local::LocalFileSystem::new_with_prefix(args.cache_dir)?,
-            )),
-            writable: true,
-            ..Default::default()
-        })
+        .cache_client(brioche_core::cache::CacheClient::from_store(
+            Arc::new(object_store::local::LocalFileSystem::new_with_prefix(
+                args.cache_dir,
+            )?),
+            true,
+        ))
         .build()
         .await?;
     crate::start_shutdown_handler(brioche.clone());
@@ -479,7 +478,7 @@ async fn save_project(
             StaticQuery::Include(_) | StaticQuery::Glob { .. } => {
                 continue;
             }
-            StaticQuery::Download { url } => {
+            StaticQuery::Download { url, .. } => {
                 let Some(StaticOutput::Kind(StaticOutputKind::Download { hash })) = output else {
                     continue;
                 };
@@ -41,6 +41,9 @@ pub struct RunArgs {
     /// Arguments to pass to the command
     #[arg(last = true)]
     args: Vec<std::ffi::OsString>,
+
+    #[command(flatten)]
+    offline: super::OfflineArgs,
 }
 
 #[expect(clippy::print_stderr)]
@@ -53,7 +56,9 @@ pub async fn run(args: RunArgs) -> anyhow::Result<ExitCode> {
         )?
     };
 
-    let brioche = brioche_core::BriocheBuilder::new(reporter.clone())
+    let brioche = args
+        .offline
+        .apply(brioche_core::BriocheBuilder::new(reporter.clone()))
         .keep_temps(args.keep_temps)
         .build()
         .await?;
@@ -112,6 +117,8 @@ pub async fn run(args: RunArgs) -> anyhow::Result<ExitCode> {
         )
         .await?;
 
+        super::check_offline_inputs(&brioche, &recipe.value).await?;
+
         let artifact = brioche_core::bake::bake(
             &brioche,
             recipe,
@@ -0,0 +1,92 @@
+use std::{net::SocketAddr, path::PathBuf, process::ExitCode, sync::Arc};
+
+use anyhow::Context as _;
+use brioche_core::cache::{
+    server::{CacheServerOptions, CacheServerSource},
+    signing::SigningKey,
+};
+use clap::Parser;
+use tracing::Instrument as _;
+
+#[derive(Debug, Parser)]
+pub struct ServeCacheArgs {
+    /// The address to listen on
+    #[arg(long, default_value = "127.0.0.1:2763")]
+    listen: SocketAddr,
+
+    /// Serve a cache directory, such as one written by `brioche vendor`.
+    /// By default, bakes are served directly from the Brioche data
+    /// directory
+    #[arg(long)]
+    dir: Option<PathBuf>,
+
+    /// Allow clients to write to the cache. Set
+    /// `$BRIOCHE_SERVE_CACHE_WRITE_TOKEN` to require a bearer token
+    /// for writes
+    #[arg(long)]
+    writable: bool,
+
+    /// Sign bakes exported from the data directory with this key, from
+    /// `brioche cache keygen`
+    #[arg(long)]
+    signing_key: Option<PathBuf>,
+
+    /// The output display format.
+    #[arg(long, value_enum, default_value_t)]
+    display: super::DisplayMode,
+
+    #[command(flatten)]
+    offline: super::OfflineArgs,
+}
+
+pub async fn serve_cache(args: ServeCacheArgs) -> anyhow::Result<ExitCode> {
+    let (reporter, _guard) = brioche_core::reporter::console::start_console_reporter(
+        args.display.to_console_reporter_kind(),
+    )?;
+
+    let brioche = args
+        .offline
+        .apply(brioche_core::BriocheBuilder::new(reporter))
+        .build()
+        .await?;
+    crate::start_shutdown_handler(brioche.clone());
+
+    let write_token = std::env::var("BRIOCHE_SERVE_CACHE_WRITE_TOKEN").ok();
+    if args.writable && write_token.is_none() {
+        tracing::warn!(
+            "$BRIOCHE_SERVE_CACHE_WRITE_TOKEN is not set, so anyone who can reach the server can write to the cache"
+        );
+    }
+
+    let signing_key = match &args.signing_key {
+        Some(path) => Some(Arc::new(SigningKey::load(path).await?)),
+        None => None,
+    };
+
+    let source = match args.dir {
+        Some(dir) => CacheServerSource::Directory(dir),
+        None => CacheServerSource::DataDir,
+    };
+
+    let listener = tokio::net::TcpListener::bind(args.listen)
+        .await
+        .with_context(|| format!("failed to listen on {}", args.listen))?;
+    tracing::info!("serving cache on http://{}/", listener.local_addr()?);
+
+    brioche_core::cache::server::serve(
+        &brioche,
+        CacheServerOptions {
+            source,
+            writable: args.writable,
+            write_token,
+            signing_key,
+        },
+        listener,
+    )
+    .instrument(tracing::info_span!("serve_cache"))
+    .await?;
+
+    brioche.wait_for_tasks().await;
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,160 @@
+use std::{collections::HashMap, process::ExitCode};
+
+use brioche_core::{
+    project::ProjectLocking,
+    sync::{CacheSyncOptions, CacheSyncResults},
+    utils::DisplayDuration,
+};
+use clap::Parser;
+use tracing::Instrument as _;
+
+#[derive(Debug, Parser)]
+pub struct SyncArgs {
+    #[command(flatten)]
+    project: super::ProjectArgs,
+
+    /// Which TypeScript exports to sync. The exports need to have been
+    /// built on this machine already
+    #[arg(default_value = "default")]
+    exports: Vec<String>,
+
+    /// Sync every bake from this machine within `--recent-days`, instead
+    /// of the bakes from a project's exports
+    #[arg(long, conflicts_with_all = ["exports", "project", "registry"])]
+    all_recent: bool,
+
+    /// How many days of bakes to sync with `--all-recent`
+    #[arg(long, default_value_t = 1, requires = "all_recent")]
+    recent_days: u64,
+
+    /// Only sync bakes of processes, skipping downloads and git checkouts
+    #[arg(long)]
+    processes_only: bool,
+
+    /// Skip artifacts larger than this size in MiB, along with the bakes
+    /// that output them
+    #[arg(long)]
+    max_artifact_size_mib: Option<u64>,
+
+    /// Show what would be synced without writing to the cache
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Print a JSON summary instead of a human-readable one
+    #[arg(long)]
+    json: bool,
+
+    /// Validate that the lockfile is up-to-date
+    #[arg(long)]
+    locked: bool,
+
+    /// The output display format.
+    #[arg(long, value_enum, default_value_t)]
+    display: super::DisplayMode,
+
+    #[command(flatten)]
+    offline: super::OfflineArgs,
+}
+
+#[expect(clippy::print_stdout)]
+pub async fn sync(args: SyncArgs) -> anyhow::Result<ExitCode> {
+    let (reporter, mut guard) = brioche_core::reporter::console::start_console_reporter(
+        args.display.to_console_reporter_kind(),
+    )?;
+
+    let brioche = args
+        .offline
+        .apply(brioche_core::BriocheBuilder::new(reporter.clone()))
+        .build()
+        .await?;
+    crate::start_shutdown_handler(brioche.clone());
+
+    let sync_future = async {
+        let bakes = if args.all_recent {
+            let max_age =
+                std::time::Duration::from_secs(args.recent_days.saturating_mul(24 * 60 * 60));
+            brioche_core::references::recent_bakes(&brioche, max_age).await?
+        } else {
+            let projects = brioche_core::project::Projects::default();
+            let locking = if args.locked {
+                ProjectLocking::Locked
+            } else {
+                ProjectLocking::Unlocked
+            };
+            let project_hash =
+                super::load_project(&brioche, &projects, &args.project, locking).await?;
+
+            // Exports can share bakes, so only sync each one once
+            let mut bakes = HashMap::new();
+            for export in &args.exports {
+                let export_bakes = brioche_core::references::descendent_project_bakes(
+                    &brioche,
+                    project_hash,
+                    export,
+                )
+                .await?;
+                if export_bakes.is_empty() {
+                    tracing::warn!(export, "no bakes found for export, build it first to sync");
+                }
+
+                bakes.extend(
+                    export_bakes
+                        .into_iter()
+                        .map(|(recipe, artifact)| (recipe.hash(), (recipe, artifact))),
+                );
+            }
+
+            bakes.into_values().collect()
+        };
+
+        let options = CacheSyncOptions {
+            processes_only: args.processes_only,
+            max_artifact_size: args
+                .max_artifact_size_mib
+                .map(|mib| mib.saturating_mul(1024 * 1024)),
+            dry_run: args.dry_run,
+        };
+        let results = brioche_core::sync::sync_bakes_to_cache(&brioche, bakes, &options).await?;
+
+        guard.shutdown_console().await;
+
+        if args.json {
+            let serialized = serde_json::to_string_pretty(&results)?;
+            println!("{serialized}");
+        } else {
+            print_summary(&results, DisplayDuration(reporter.elapsed()));
+        }
+
+        brioche.wait_for_tasks().await;
+
+        anyhow::Ok(ExitCode::SUCCESS)
+    };
+
+    let exit_code = sync_future.instrument(tracing::info_span!("sync")).await?;
+
+    Ok(exit_code)
+}
+
+#[expect(clippy::print_stdout)]
+fn print_summary(results: &CacheSyncResults, elapsed: DisplayDuration) {
+    let verb = if results.dry_run {
+        "Would sync"
+    } else {
+        "Synced"
+    };
+    println!(
+        "{verb} {} / {} bakes and {} / {} artifacts to cache in {elapsed}",
+        results.num_new_bakes, results.num_bakes, results.num_new_artifacts, results.num_artifacts,
+    );
+
+    if results.num_skipped_bakes > 0 {
+        println!("Skipped {} bakes", results.num_skipped_bakes);
+    }
+    for skipped in &results.skipped_artifacts {
+        let size_mib = skipped.size / (1024 * 1024);
+        println!(
+            "Skipped artifact {} ({size_mib} MiB)",
+            skipped.artifact_hash
+        );
+    }
+}
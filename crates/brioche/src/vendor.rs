@@ -0,0 +1,95 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use brioche_core::{project::ProjectLocking, utils::DisplayDuration};
+use clap::Parser;
+use tracing::Instrument as _;
+
+#[derive(Debug, Parser)]
+pub struct VendorArgs {
+    #[command(flatten)]
+    project: super::ProjectArgs,
+
+    /// Which TypeScript export to vendor
+    #[arg(default_value = "default")]
+    export: String,
+
+    /// The directory to write the vendored cache to
+    #[arg(long)]
+    to: PathBuf,
+
+    /// Validate that the lockfile is up-to-date
+    #[arg(long)]
+    locked: bool,
+
+    /// The output display format.
+    #[arg(long, value_enum, default_value_t)]
+    display: super::DisplayMode,
+}
+
+#[expect(clippy::print_stdout)]
+pub async fn vendor(args: VendorArgs) -> anyhow::Result<ExitCode> {
+    let (reporter, mut guard) = brioche_core::reporter::console::start_console_reporter(
+        args.display.to_console_reporter_kind(),
+    )?;
+
+    let brioche = brioche_core::BriocheBuilder::new(reporter.clone())
+        .build()
+        .await?;
+    crate::start_shutdown_handler(brioche.clone());
+
+    let projects = brioche_core::project::Projects::default();
+
+    let locking = if args.locked {
+        ProjectLocking::Locked
+    } else {
+        ProjectLocking::Unlocked
+    };
+
+    let vendor_future = async {
+        let project_hash = super::load_project(&brioche, &projects, &args.project, locking).await?;
+
+        // The lockfile records the resolved downloads and git refs, so it
+        // needs to be up-to-date for the project to load offline
+        if args.locked {
+            projects.validate_no_dirty_lockfiles()?;
+        } else {
+            let num_lockfiles_updated = projects.commit_dirty_lockfiles().await?;
+            if num_lockfiles_updated > 0 {
+                tracing::info!(num_lockfiles_updated, "updated lockfiles");
+            }
+        }
+
+        let brioche_core::vendor::VendorResults {
+            num_projects,
+            num_bakes,
+        } = brioche_core::vendor::vendor_project(
+            &brioche,
+            &projects,
+            project_hash,
+            &args.export,
+            &args.to,
+        )
+        .await?;
+
+        guard.shutdown_console().await;
+
+        let elapsed = DisplayDuration(reporter.elapsed());
+        println!("Vendored {num_projects} projects and {num_bakes} bakes in {elapsed}");
+
+        let config_path = args.to.join(brioche_core::vendor::VENDOR_CONFIG_FILENAME);
+        println!(
+            "To build offline, add the config from {} to your Brioche config",
+            config_path.display()
+        );
+
+        brioche.wait_for_tasks().await;
+
+        anyhow::Ok(ExitCode::SUCCESS)
+    };
+
+    let exit_code = vendor_future
+        .instrument(tracing::info_span!("vendor"))
+        .await?;
+
+    Ok(exit_code)
+}
@@ -0,0 +1,54 @@
+use std::{net::SocketAddr, process::ExitCode};
+
+use anyhow::Context as _;
+use clap::Parser;
+use tracing::Instrument as _;
+
+#[derive(Debug, Parser)]
+pub struct WorkerArgs {
+    /// The address to listen on. The worker doesn't authenticate clients,
+    /// so only expose it on a trusted network
+    #[arg(long, default_value = "127.0.0.1:2762")]
+    listen: SocketAddr,
+
+    /// Keep temporary build files. Useful for debugging build failures
+    #[arg(long)]
+    keep_temps: bool,
+
+    /// The output display format.
+    #[arg(long, value_enum, default_value_t)]
+    display: super::DisplayMode,
+
+    #[command(flatten)]
+    offline: super::OfflineArgs,
+}
+
+pub async fn worker(args: WorkerArgs) -> anyhow::Result<ExitCode> {
+    let (reporter, _guard) = brioche_core::reporter::console::start_console_reporter(
+        args.display.to_console_reporter_kind(),
+    )?;
+
+    // Always run processes locally, even if the config points at another
+    // worker
+    let brioche = args
+        .offline
+        .apply(brioche_core::BriocheBuilder::new(reporter))
+        .keep_temps(args.keep_temps)
+        .remote_execution(None)
+        .build()
+        .await?;
+    crate::start_shutdown_handler(brioche.clone());
+
+    let listener = tokio::net::TcpListener::bind(args.listen)
+        .await
+        .with_context(|| format!("failed to listen on {}", args.listen))?;
+    tracing::info!("worker listening on {}", listener.local_addr()?);
+
+    brioche_core::remote_execution::worker::serve(brioche.clone(), listener)
+        .instrument(tracing::info_span!("worker"))
+        .await?;
+
+    brioche.wait_for_tasks().await;
+
+    Ok(ExitCode::SUCCESS)
+}